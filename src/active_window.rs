@@ -0,0 +1,254 @@
+//! Which window has keyboard focus, so output can be adjusted per application.
+//!
+//! There is no portable API for this, so each platform asks whatever it has:
+//!
+//! * Windows: `GetForegroundWindow` (window class, title and owning process)
+//! * macOS: System Events via `osascript` (application name and front window
+//!   title; the title needs the accessibility permission we already ask for)
+//! * Linux/X11: `xprop` (`WM_CLASS`, `_NET_WM_NAME` and `_NET_WM_PID`)
+//! * Linux/Wayland: `hyprctl` or `swaymsg`. Other compositors don't expose the
+//!   focused window to clients, so the probe returns `None` there.
+//!
+//! The probe is best-effort: a missing helper or an unexpected answer just
+//! means "unknown window" and the defaults apply.
+
+#[cfg(not(windows))]
+use crate::credentials::child_command;
+#[cfg(target_os = "linux")]
+use crate::inject::is_wayland_session;
+use serde::{Deserialize, Serialize};
+#[cfg(not(windows))]
+use std::process::Stdio;
+
+/// The focused window.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveWindow {
+    /// Window class (X11 `WM_CLASS`, Wayland app id, Windows class name) or,
+    /// on macOS, the application name.
+    pub class: String,
+    pub title: String,
+    /// Executable name of the owning process (without `.exe`), if known.
+    pub process: String,
+}
+
+/// Asks the platform for the focused window.
+pub fn probe() -> Option<ActiveWindow> {
+    let window = platform_probe()?;
+    if window.class.is_empty() && window.title.is_empty() && window.process.is_empty() {
+        None
+    } else {
+        Some(window)
+    }
+}
+
+/// Runs a helper and returns its stdout, or `None` if it failed.
+#[cfg(not(windows))]
+fn helper_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = child_command(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(windows)]
+fn platform_probe() -> Option<ActiveWindow> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetClassNameW, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
+    };
+
+    let mut class = [0u16; 256];
+    let mut title = [0u16; 512];
+    let mut image = [0u16; 1024];
+    let mut image_len = image.len() as u32;
+    // SAFETY: the buffers outlive the calls and their lengths are passed
+    // along; the process handle is closed before leaving the block.
+    let (class_len, title_len) = unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd == 0 {
+            return None;
+        }
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process == 0
+            || QueryFullProcessImageNameW(process, 0, image.as_mut_ptr(), &mut image_len) == 0
+        {
+            image_len = 0;
+        }
+        if process != 0 {
+            CloseHandle(process);
+        }
+        (
+            GetClassNameW(hwnd, class.as_mut_ptr(), class.len() as i32),
+            GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32),
+        )
+    };
+    let image = String::from_utf16_lossy(&image[..image_len as usize]);
+    let process = std::path::Path::new(&image)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Some(ActiveWindow {
+        class: String::from_utf16_lossy(&class[..class_len.max(0) as usize]),
+        title: String::from_utf16_lossy(&title[..title_len.max(0) as usize]),
+        process,
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn platform_probe() -> Option<ActiveWindow> {
+    const SCRIPT: &str = r#"tell application "System Events"
+    set p to first application process whose frontmost is true
+    set t to ""
+    try
+        set t to name of front window of p
+    end try
+    return (name of p) & linefeed & t
+end tell"#;
+
+    let output = helper_output("osascript", &["-e", SCRIPT])?;
+    let mut lines = output.lines();
+    let name = lines.next().unwrap_or_default().trim().to_string();
+    Some(ActiveWindow {
+        class: name.clone(),
+        title: lines.next().unwrap_or_default().trim().to_string(),
+        // System Events names application processes after their executable.
+        process: name,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn platform_probe() -> Option<ActiveWindow> {
+    if is_wayland_session() {
+        probe_hyprland().or_else(probe_sway)
+    } else {
+        probe_x11()
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn platform_probe() -> Option<ActiveWindow> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn probe_x11() -> Option<ActiveWindow> {
+    // "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007"
+    let root = helper_output("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+    let id = root.split_whitespace().last()?;
+    if id == "0x0" {
+        return None;
+    }
+    let props = helper_output(
+        "xprop",
+        &["-id", id, "WM_CLASS", "_NET_WM_NAME", "_NET_WM_PID"],
+    )?;
+    let (mut window, pid) = parse_xprop(&props);
+    window.process = pid.map(process_name).unwrap_or_default();
+    Some(window)
+}
+
+/// Name of a running process, from `/proc`.
+#[cfg(target_os = "linux")]
+fn process_name(pid: u64) -> String {
+    std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Parses `xprop` output like
+/// `WM_CLASS(STRING) = "gnome-terminal-server", "Gnome-terminal"`.
+/// `WM_CLASS` holds instance and class name; the class name is kept.
+/// Returns the window and its `_NET_WM_PID`, if set.
+#[cfg(target_os = "linux")]
+fn parse_xprop(props: &str) -> (ActiveWindow, Option<u64>) {
+    let mut window = ActiveWindow::default();
+    let mut pid = None;
+    for line in props.lines() {
+        let Some((name, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        if name.starts_with("WM_CLASS") {
+            let class = value.rsplit("\", \"").next().unwrap_or_default();
+            window.class = class.trim().trim_matches('"').to_string();
+        } else if name.starts_with("_NET_WM_NAME") {
+            window.title = value.trim().trim_matches('"').to_string();
+        } else if name.starts_with("_NET_WM_PID") {
+            pid = value.trim().parse().ok();
+        }
+    }
+    (window, pid)
+}
+
+#[cfg(target_os = "linux")]
+fn probe_hyprland() -> Option<ActiveWindow> {
+    let json = helper_output("hyprctl", &["activewindow", "-j"])?;
+    let value: serde_json::Value = serde_json::from_str(&json).ok()?;
+    Some(ActiveWindow {
+        class: value["class"].as_str().unwrap_or_default().to_string(),
+        title: value["title"].as_str().unwrap_or_default().to_string(),
+        process: value["pid"].as_u64().map(process_name).unwrap_or_default(),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn probe_sway() -> Option<ActiveWindow> {
+    let json = helper_output("swaymsg", &["-t", "get_tree", "-r"])?;
+    let tree: serde_json::Value = serde_json::from_str(&json).ok()?;
+    let node = find_focused(&tree)?;
+    // Native Wayland windows have an app id, XWayland ones an X11 class.
+    let class = node["app_id"]
+        .as_str()
+        .or_else(|| node["window_properties"]["class"].as_str())
+        .unwrap_or_default();
+    Some(ActiveWindow {
+        class: class.to_string(),
+        title: node["name"].as_str().unwrap_or_default().to_string(),
+        process: node["pid"].as_u64().map(process_name).unwrap_or_default(),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn find_focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+    if node["focused"].as_bool() == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[*key].as_array())
+        .flatten()
+        .find_map(find_focused)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xprop_output_yields_class_and_title() {
+        let props = "WM_CLASS(STRING) = \"gnome-terminal-server\", \"Gnome-terminal\"\n\
+                     _NET_WM_NAME(UTF8_STRING) = \"me@host: ~\"\n\
+                     _NET_WM_PID(CARDINAL) = 4242\n";
+        assert_eq!(
+            parse_xprop(props),
+            (
+                ActiveWindow {
+                    class: "Gnome-terminal".into(),
+                    title: "me@host: ~".into(),
+                    process: String::new(),
+                },
+                Some(4242)
+            )
+        );
+    }
+}
@@ -1,9 +1,13 @@
+use crate::active_window::ActiveWindow;
 use crate::config::AppConfig;
+use crate::events::{EngineEvent, EventBus};
+use crate::preview::PreviewEvent;
 use anyhow::Context;
 use directories::ProjectDirs;
 use flume::Sender;
 use parking_lot::RwLock;
 use rdev::Event;
+use std::collections::VecDeque;
 use std::fs;
 use std::sync::Arc;
 
@@ -14,6 +18,29 @@ pub struct AppState {
     pub statistics: Arc<RwLock<Statistics>>,
     pub lifetime_statistics: Arc<RwLock<LifetimeStatistics>>,
     pub event_sender: Arc<RwLock<Option<Sender<Event>>>>,
+    /// Feeds the realtime preview overlay (set by the GUI when it creates it).
+    pub preview_sender: Arc<RwLock<Option<Sender<PreviewEvent>>>>,
+    /// The last few transcriptions, newest last.
+    pub recent_transcriptions: Arc<RwLock<VecDeque<TranscriptionRecord>>>,
+    /// True while the PTT key (or a control command) holds recording on.
+    pub recording: Arc<RwLock<bool>>,
+    /// Name of the loaded config profile; `None` is the default config.
+    pub profile: Arc<RwLock<Option<String>>>,
+    pub events: EventBus,
+}
+
+/// How many transcriptions `recent_transcriptions` keeps.
+const RECENT_TRANSCRIPTIONS: usize = 50;
+
+/// One finished transcription.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TranscriptionRecord {
+    pub text: String,
+    /// Unix timestamp of the PTT press.
+    pub started_at: i64,
+    pub duration_secs: f64,
+    /// Window that had focus when the PTT key went down.
+    pub window: Option<ActiveWindow>,
 }
 
 /// Session statistics (reset each time app starts)
@@ -49,12 +76,40 @@ pub struct LifetimeStatistics {
     pub daily_stats: std::collections::HashMap<String, DailyStats>,
 }
 
+/// `name` in the data directory, where the statistics are kept.
+pub(crate) fn data_file(name: &str) -> Option<std::path::PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "desk-talk", "desk-talk")?;
+    let data_dir = proj_dirs.data_dir();
+    let _ = fs::create_dir_all(data_dir);
+    Some(data_dir.join(name))
+}
+
+/// Locks the data file `name` against other DeskTalk processes until the
+/// returned handle is dropped, so a read, change and write isn't interleaved
+/// with theirs. `None` if the lock file can't be opened.
+pub(crate) fn lock_data_file(name: &str) -> Option<fs::File> {
+    let path = data_file(&format!("{name}.lock"))?;
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .ok()?;
+    file.lock().ok()?;
+    Some(file)
+}
+
+/// Replaces the file at `path` with `contents` in one rename, so other
+/// processes never read it half written.
+pub(crate) fn write_data_file(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
 impl LifetimeStatistics {
     fn get_stats_path() -> Option<std::path::PathBuf> {
-        let proj_dirs = ProjectDirs::from("com", "desk-talk", "desk-talk")?;
-        let data_dir = proj_dirs.data_dir();
-        let _ = fs::create_dir_all(data_dir);
-        Some(data_dir.join("lifetime_stats.json"))
+        data_file("lifetime_stats.json")
     }
 
     pub fn load() -> Self {
@@ -118,6 +173,11 @@ impl AppState {
             statistics: Arc::new(RwLock::new(Statistics::default())),
             lifetime_statistics: Arc::new(RwLock::new(lifetime_stats)),
             event_sender: Arc::new(RwLock::new(None)),
+            preview_sender: Arc::new(RwLock::new(None)),
+            recent_transcriptions: Arc::new(RwLock::new(VecDeque::new())),
+            recording: Arc::new(RwLock::new(false)),
+            profile: Arc::new(RwLock::new(None)),
+            events: EventBus::default(),
         }
     }
 
@@ -145,6 +205,64 @@ impl AppState {
         self.event_sender.read().clone()
     }
 
+    pub fn set_preview_sender(&self, sender: Sender<PreviewEvent>) {
+        *self.preview_sender.write() = Some(sender);
+    }
+
+    pub fn preview_sender(&self) -> Option<Sender<PreviewEvent>> {
+        self.preview_sender.read().clone()
+    }
+
+    pub fn record_transcription(&self, record: TranscriptionRecord) {
+        {
+            let mut recent = self.recent_transcriptions.write();
+            if recent.len() == RECENT_TRANSCRIPTIONS {
+                recent.pop_front();
+            }
+            recent.push_back(record.clone());
+        }
+        self.events.emit(EngineEvent::TranscriptionCompleted {
+            transcription: record,
+        });
+    }
+
+    pub fn set_recording(&self, recording: bool) {
+        let changed = std::mem::replace(&mut *self.recording.write(), recording) != recording;
+        if changed {
+            self.events.emit(if recording {
+                EngineEvent::RecordingStarted
+            } else {
+                EngineEvent::RecordingStopped
+            });
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        *self.recording.read()
+    }
+
+    /// Replaces the settings and tells subscribers (the engine among them).
+    pub fn set_config(&self, config: AppConfig) {
+        *self.config.write() = config;
+        self.events.emit(EngineEvent::ConfigChanged);
+    }
+
+    pub fn profile(&self) -> Option<String> {
+        self.profile.read().clone()
+    }
+
+    pub fn set_profile(&self, profile: Option<String>) {
+        *self.profile.write() = profile;
+    }
+
+    pub fn report_error(&self, message: String) {
+        self.events.emit(EngineEvent::Error { message });
+    }
+
+    pub fn last_transcription(&self) -> Option<TranscriptionRecord> {
+        self.recent_transcriptions.read().back().cloned()
+    }
+
     pub fn update_statistics(&self, words: usize, duration_secs: f64, wpm: f64) {
         // Update session statistics
         {
@@ -0,0 +1,391 @@
+//! Command-line flags that override [`AppConfig`] for one run, shared by the
+//! desktop app and the CLI.
+//!
+//! Every setting has a flag except the lists (`app_rules`, `replacements`,
+//! `hooks`), which only make sense in a config file. Switches come in pairs
+//! (`--realtime` / `--no-realtime`); the last one given wins. Anything not
+//! passed keeps its saved value.
+
+use crate::config::{
+    AppConfig, BudgetAction, EndPunctuation, InputDevice, LocalModel, OutputMode, RealtimeDelay,
+    RealtimeOutput,
+};
+use crate::easy_rdev_key::PTTKey;
+use crate::inject::PasteChord;
+use clap::Args;
+
+/// Help footer for commands that flatten [`ConfigOverrides`]; the `--no-...`
+/// forms are hidden to keep `--help` readable.
+pub const SWITCHES_HELP: &str =
+    "Every on/off flag also has a --no-<flag> form, e.g. --no-realtime.";
+
+#[derive(Args, Debug, Default)]
+pub struct ConfigOverrides {
+    /// The push to talk key
+    #[arg(short, long)]
+    ptt_key: Option<PTTKey>,
+
+    /// The push to talk key, as a raw key code.
+    /// Use this if you want to use a key that is not supported by the PTTKey enum.
+    #[arg(long, conflicts_with("ptt_key"))]
+    special_ptt_key: Option<u32>,
+
+    /// The audio device to use for recording. "default" uses the system default device.
+    #[arg(short, long)]
+    device: Option<InputDevice>,
+
+    /// Your OpenAI API key (used for this run only, not saved). Read from
+    /// OPENAI_API_KEY if that is set.
+    #[arg(short, long, env = "OPENAI_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
+    /// Use the API key stored under this name (see the `credentials`
+    /// subcommand of desk-talk-cli), even if OPENAI_API_KEY is set.
+    #[arg(long, value_name = "NAME")]
+    credential: Option<String>,
+
+    /// Also save the API key to a plain-text .env file next to the program.
+    #[arg(long, overrides_with = "no_write_env_file")]
+    write_env_file: bool,
+    #[arg(long, overrides_with = "write_env_file", hide = true)]
+    no_write_env_file: bool,
+
+    /// Use a local whisper model instead of the OpenAI API.
+    #[arg(long, overrides_with = "no_local")]
+    local: bool,
+    #[arg(long, overrides_with = "local", hide = true)]
+    no_local: bool,
+
+    /// The local whisper model to use.
+    #[arg(long, alias = "local-model", ignore_case = true, value_enum)]
+    model: Option<LocalModel>,
+
+    /// Capitalize the first letter of each transcription.
+    #[arg(short, long, overrides_with = "no_cap_first")]
+    cap_first: bool,
+    #[arg(long, overrides_with = "cap_first", hide = true)]
+    no_cap_first: bool,
+
+    /// End each transcription with a space, so consecutive dictations don't
+    /// run together.
+    #[arg(short, long, overrides_with = "no_space")]
+    space: bool,
+    #[arg(long, overrides_with = "space", hide = true)]
+    no_space: bool,
+
+    /// Type the text with synthetic key presses instead of pasting it (same
+    /// as `--output type`).
+    #[arg(short, long, overrides_with = "no_type_chars")]
+    type_chars: bool,
+    #[arg(long, overrides_with = "type_chars", hide = true)]
+    no_type_chars: bool,
+
+    /// Where transcribed text goes.
+    #[arg(long, ignore_case = true, value_enum)]
+    output: Option<OutputMode>,
+
+    /// File to append to with `--output file`.
+    #[arg(long)]
+    output_file: Option<String>,
+
+    /// Paste chord, e.g. ctrl+shift+v.
+    #[arg(long, value_parser = parse_paste_shortcut)]
+    paste_shortcut: Option<String>,
+
+    /// Ask clipboard managers to ignore the pasted text (Windows and macOS;
+    /// no effect on Linux).
+    #[arg(long, overrides_with = "no_clipboard_transient")]
+    clipboard_transient: bool,
+    #[arg(long, overrides_with = "clipboard_transient", hide = true)]
+    no_clipboard_transient: bool,
+
+    /// Milliseconds to wait before restoring the clipboard after pasting.
+    #[arg(long, value_name = "MS")]
+    paste_restore_delay: Option<u64>,
+
+    /// Serve the local control socket.
+    #[arg(long, overrides_with = "no_control_socket")]
+    control_socket: bool,
+    #[arg(long, overrides_with = "control_socket", hide = true)]
+    no_control_socket: bool,
+
+    /// Register the D-Bus service on Linux.
+    #[arg(long, overrides_with = "no_dbus")]
+    dbus: bool,
+    #[arg(long, overrides_with = "dbus", hide = true)]
+    no_dbus: bool,
+
+    /// If the transcription is missing punctuation (no periods, question
+    /// marks, or exclamation points), use an LLM to add punctuation.
+    #[arg(long, overrides_with = "no_punctuation")]
+    punctuation: bool,
+    #[arg(long, overrides_with = "punctuation", hide = true)]
+    no_punctuation: bool,
+
+    /// How to punctuate the end of each transcription.
+    #[arg(long, ignore_case = true, value_enum)]
+    end_punctuation: Option<EndPunctuation>,
+
+    /// Same as `--end-punctuation period`.
+    #[arg(long, conflicts_with_all = ["end_punctuation", "smart_punctuation", "no_smart_punctuation"])]
+    period: bool,
+
+    /// Same as `--end-punctuation smart`.
+    #[arg(long, hide = true, conflicts_with_all = ["end_punctuation", "no_smart_punctuation"])]
+    smart_punctuation: bool,
+
+    /// Same as `--end-punctuation none`.
+    #[arg(long, hide = true, conflicts_with = "end_punctuation")]
+    no_smart_punctuation: bool,
+
+    /// Number of parallel requests to race (standard mode).
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=5))]
+    parallel: Option<u64>,
+
+    /// Estimated API spend allowed per day, in US dollars.
+    #[arg(long, value_name = "USD")]
+    daily_budget: Option<f64>,
+
+    /// Estimated API spend allowed per calendar month, in US dollars.
+    #[arg(long, value_name = "USD")]
+    monthly_budget: Option<f64>,
+
+    /// What to do while a budget is used up.
+    #[arg(long, ignore_case = true, value_enum)]
+    budget_action: Option<BudgetAction>,
+
+    /// Stream audio to the OpenAI Realtime API and type as you speak.
+    #[arg(long, overrides_with = "no_realtime")]
+    realtime: bool,
+    #[arg(long, overrides_with = "realtime", hide = true)]
+    no_realtime: bool,
+
+    /// Realtime latency/accuracy tradeoff.
+    #[arg(long, ignore_case = true, value_enum)]
+    realtime_delay: Option<RealtimeDelay>,
+
+    /// Keep a realtime connection ready between recordings.
+    #[arg(long, overrides_with = "no_realtime_prewarm")]
+    realtime_prewarm: bool,
+    #[arg(long, overrides_with = "realtime_prewarm", hide = true)]
+    no_realtime_prewarm: bool,
+
+    /// Correct live text when the final transcript differs.
+    #[arg(long, overrides_with = "no_realtime_reconcile")]
+    realtime_reconcile: bool,
+    #[arg(long, overrides_with = "realtime_reconcile", hide = true)]
+    no_realtime_reconcile: bool,
+
+    /// Where live text goes while the key is held.
+    #[arg(long, ignore_case = true, value_enum)]
+    realtime_output: Option<RealtimeOutput>,
+
+    /// Type as you speak with the local model.
+    #[arg(long, overrides_with = "no_local_streaming")]
+    local_streaming: bool,
+    #[arg(long, overrides_with = "local_streaming", hide = true)]
+    no_local_streaming: bool,
+
+    /// Start the engine when the app starts.
+    #[arg(long, overrides_with = "no_auto_start")]
+    auto_start: bool,
+    #[arg(long, overrides_with = "auto_start", hide = true)]
+    no_auto_start: bool,
+
+    /// Start with the settings window hidden.
+    #[arg(long, overrides_with = "no_start_minimized")]
+    start_minimized: bool,
+    #[arg(long, overrides_with = "start_minimized", hide = true)]
+    no_start_minimized: bool,
+
+    /// Use the dark theme in the settings window.
+    #[arg(long, overrides_with = "no_dark_mode")]
+    dark_mode: bool,
+    #[arg(long, overrides_with = "dark_mode", hide = true)]
+    no_dark_mode: bool,
+
+    /// Your typing speed, for the "time saved" statistics.
+    #[arg(long, value_name = "WPM")]
+    typing_wpm: Option<u32>,
+}
+
+fn parse_paste_shortcut(chord: &str) -> Result<String, String> {
+    chord
+        .parse::<PasteChord>()
+        .map(|_| chord.to_string())
+        .map_err(|err| err.to_string())
+}
+
+/// `Some(true)` for `--x`, `Some(false)` for `--no-x`, `None` for neither.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+impl ConfigOverrides {
+    /// Applies the flags that were given on top of the saved config.
+    pub fn apply(&self, config: &mut AppConfig) {
+        fn set<T: Clone>(field: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+
+        if let Some(ptt_key) = self.ptt_key {
+            config.ptt_key = Some(ptt_key);
+            config.special_ptt_key = None;
+        } else if let Some(special_ptt_key) = self.special_ptt_key {
+            config.ptt_key = None;
+            config.special_ptt_key = Some(special_ptt_key);
+        }
+        set(&mut config.device, self.device.clone());
+        if self.api_key.is_some() {
+            config.api_key = self.api_key.clone();
+        }
+        if let Some(name) = &self.credential {
+            config.credential = Some(name.clone());
+            config.api_key = config.load_api_key().ok();
+        }
+        set(
+            &mut config.write_env_file,
+            switch(self.write_env_file, self.no_write_env_file),
+        );
+        set(&mut config.use_local, switch(self.local, self.no_local));
+        if self.model.is_some() {
+            config.local_model = self.model;
+        }
+        set(
+            &mut config.cap_first,
+            switch(self.cap_first, self.no_cap_first),
+        );
+        set(&mut config.space, switch(self.space, self.no_space));
+        set(
+            &mut config.type_chars,
+            switch(self.type_chars, self.no_type_chars),
+        );
+        set(&mut config.output, self.output);
+        if self.output_file.is_some() {
+            config.output_file = self.output_file.clone();
+        }
+        set(&mut config.paste_shortcut, self.paste_shortcut.clone());
+        set(
+            &mut config.clipboard_transient,
+            switch(self.clipboard_transient, self.no_clipboard_transient),
+        );
+        set(&mut config.paste_restore_delay_ms, self.paste_restore_delay);
+        set(
+            &mut config.control_socket,
+            switch(self.control_socket, self.no_control_socket),
+        );
+        set(&mut config.dbus_service, switch(self.dbus, self.no_dbus));
+        set(
+            &mut config.punctuation,
+            switch(self.punctuation, self.no_punctuation),
+        );
+        let end_punctuation = match self.end_punctuation {
+            Some(mode) => Some(mode),
+            None if self.period => Some(EndPunctuation::Period),
+            None if self.smart_punctuation => Some(EndPunctuation::Smart),
+            None if self.no_smart_punctuation => Some(EndPunctuation::None),
+            None => None,
+        };
+        set(&mut config.end_punctuation, end_punctuation);
+        set(
+            &mut config.parallel,
+            self.parallel.map(|parallel| parallel as usize),
+        );
+        if self.daily_budget.is_some() {
+            config.daily_budget = self.daily_budget;
+        }
+        if self.monthly_budget.is_some() {
+            config.monthly_budget = self.monthly_budget;
+        }
+        set(&mut config.budget_action, self.budget_action);
+        set(
+            &mut config.realtime,
+            switch(self.realtime, self.no_realtime),
+        );
+        set(&mut config.realtime_delay, self.realtime_delay);
+        set(
+            &mut config.realtime_prewarm,
+            switch(self.realtime_prewarm, self.no_realtime_prewarm),
+        );
+        set(
+            &mut config.realtime_reconcile,
+            switch(self.realtime_reconcile, self.no_realtime_reconcile),
+        );
+        set(&mut config.realtime_output, self.realtime_output);
+        set(
+            &mut config.local_streaming,
+            switch(self.local_streaming, self.no_local_streaming),
+        );
+        set(
+            &mut config.auto_start,
+            switch(self.auto_start, self.no_auto_start),
+        );
+        set(
+            &mut config.start_minimized,
+            switch(self.start_minimized, self.no_start_minimized),
+        );
+        set(
+            &mut config.dark_mode,
+            switch(self.dark_mode, self.no_dark_mode),
+        );
+        set(&mut config.typing_wpm, self.typing_wpm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        overrides: ConfigOverrides,
+    }
+
+    fn apply(args: &[&str]) -> AppConfig {
+        let cli =
+            Cli::try_parse_from(std::iter::once("desk-talk").chain(args.iter().copied())).unwrap();
+        let mut config = AppConfig::default();
+        cli.overrides.apply(&mut config);
+        config
+    }
+
+    #[test]
+    fn only_given_flags_override_the_config() {
+        let defaults = AppConfig::default();
+        let config = apply(&["--no-realtime", "--realtime", "--end-punctuation", "NONE"]);
+        assert!(config.realtime);
+        assert_eq!(config.end_punctuation, EndPunctuation::None);
+        assert_eq!(config.parallel, defaults.parallel);
+        assert_eq!(config.output, defaults.output);
+        assert_eq!(apply(&["--period"]).end_punctuation, EndPunctuation::Period);
+        assert_eq!(
+            apply(&["--model", "Base.en"]).local_model,
+            Some(LocalModel::BaseEn)
+        );
+    }
+
+    #[test]
+    fn flags_are_consistent() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn unknown_flags_and_bad_values_are_errors() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("desk-talk").chain(args.iter().copied()))
+        };
+        assert!(parse(&["--paralel", "2"]).is_err());
+        assert!(parse(&["--parallel", "9"]).is_err());
+        assert!(parse(&["--realtime-delay", "soon"]).is_err());
+        assert!(parse(&["--paste-shortcut", "ctrl"]).is_err());
+    }
+}
@@ -0,0 +1,481 @@
+//! Saving and restoring the whole clipboard around a paste.
+//!
+//! The `clipboard` crate only speaks plain text, so pasting used to replace
+//! a copied image or rich text with the transcription for good. This module
+//! reads every format the platform lets us get at:
+//!
+//! * Windows: all memory-backed clipboard formats, restored as they were.
+//! * macOS: every type of the first pasteboard item (via JXA / `osascript`).
+//! * Linux: `xclip` (X11) or `wl-paste` (Wayland). These helpers can only
+//!   serve one format at a time, so the most specific one is kept: an image
+//!   stays an image, rich text comes back as plain text.
+//!
+//! It can also mark our temporary entry as transient so clipboard managers
+//! and clipboard history skip it (Windows history/cloud clipboard and
+//! monitors, macOS `org.nspasteboard.TransientType`). On Linux this does
+//! nothing: `x-kde-passwordManagerHint` would have to be offered next to the
+//! text, and `xclip`/`wl-copy` serve one target per process.
+
+use anyhow::Result;
+
+/// What was on the clipboard before we put text on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardSnapshot {
+    Empty,
+    /// Only the text could be read.
+    Text(String),
+    /// Raw data per format (format id on Windows, MIME/UTI elsewhere).
+    Formats(Vec<(String, Vec<u8>)>),
+}
+
+/// Reads all formats, or `None` if this platform/session can't enumerate
+/// them and the caller should fall back to text.
+pub fn capture() -> Result<Option<ClipboardSnapshot>> {
+    platform::capture()
+}
+
+/// Puts the formats of a [`ClipboardSnapshot::Formats`] back.
+pub fn restore(formats: &[(String, Vec<u8>)]) -> Result<()> {
+    platform::restore(formats)
+}
+
+/// Sets the clipboard text marked as transient. Returns `false` (and does
+/// nothing) when the platform has no way to mark it.
+pub fn set_transient_text(text: &str) -> Result<bool> {
+    platform::set_transient_text(text)
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::ClipboardSnapshot;
+    use anyhow::{bail, Result};
+    use std::thread::sleep;
+    use std::time::Duration;
+    use windows_sys::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData, OpenClipboard,
+        RegisterClipboardFormatW, SetClipboardData,
+    };
+    use windows_sys::Win32::System::Memory::{
+        GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+    };
+
+    const CF_BITMAP: u32 = 2;
+    const CF_METAFILEPICT: u32 = 3;
+    const CF_PALETTE: u32 = 9;
+    const CF_UNICODETEXT: u32 = 13;
+    const CF_ENHMETAFILE: u32 = 14;
+    const CF_OWNERDISPLAY: u32 = 0x80;
+    const CF_DSPBITMAP: u32 = 0x82;
+    const CF_DSPMETAFILEPICT: u32 = 0x83;
+    const CF_DSPENHMETAFILE: u32 = 0x8E;
+
+    /// Formats whose data is a GDI handle rather than global memory. Their
+    /// memory-backed equivalents (CF_DIB, ...) are captured instead.
+    fn is_handle_format(format: u32) -> bool {
+        matches!(
+            format,
+            CF_BITMAP
+                | CF_METAFILEPICT
+                | CF_PALETTE
+                | CF_ENHMETAFILE
+                | CF_OWNERDISPLAY
+                | CF_DSPBITMAP
+                | CF_DSPMETAFILEPICT
+                | CF_DSPENHMETAFILE
+        )
+    }
+
+    /// Holds the clipboard open; closes it on drop.
+    struct OpenGuard;
+
+    impl OpenGuard {
+        fn open() -> Result<Self> {
+            // Another application may hold the clipboard for a moment.
+            for _ in 0..10 {
+                // SAFETY: plain Win32 call; 0 associates it with this task.
+                if unsafe { OpenClipboard(0) } != 0 {
+                    return Ok(OpenGuard);
+                }
+                sleep(Duration::from_millis(10));
+            }
+            bail!("Clipboard is in use by another application")
+        }
+    }
+
+    impl Drop for OpenGuard {
+        fn drop(&mut self) {
+            // SAFETY: only constructed after a successful OpenClipboard.
+            unsafe {
+                CloseClipboard();
+            }
+        }
+    }
+
+    /// Copies `data` into a movable global block and hands it to the clipboard.
+    fn put(format: u32, data: &[u8]) -> Result<()> {
+        // SAFETY: the block is allocated with room for `data`, locked while
+        // written, and owned by the clipboard once SetClipboardData succeeds.
+        unsafe {
+            let block = GlobalAlloc(GMEM_MOVEABLE, data.len().max(1));
+            if block == 0 {
+                bail!("Out of memory copying clipboard data");
+            }
+            let ptr = GlobalLock(block) as *mut u8;
+            if ptr.is_null() {
+                GlobalFree(block);
+                bail!("Failed to lock clipboard memory");
+            }
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+            GlobalUnlock(block);
+            if SetClipboardData(format, block) == 0 {
+                GlobalFree(block);
+                bail!("Failed to set clipboard format {format}");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn capture() -> Result<Option<ClipboardSnapshot>> {
+        let _open = OpenGuard::open()?;
+        let mut formats = Vec::new();
+        let mut format = 0;
+        loop {
+            // SAFETY: the clipboard is open; handles are only read while it is.
+            unsafe {
+                format = EnumClipboardFormats(format);
+                if format == 0 {
+                    break;
+                }
+                if is_handle_format(format) {
+                    continue;
+                }
+                let handle = GetClipboardData(format);
+                if handle == 0 {
+                    continue;
+                }
+                let size = GlobalSize(handle);
+                let ptr = GlobalLock(handle) as *const u8;
+                if ptr.is_null() {
+                    continue;
+                }
+                let data = std::slice::from_raw_parts(ptr, size).to_vec();
+                GlobalUnlock(handle);
+                formats.push((format.to_string(), data));
+            }
+        }
+        Ok(Some(if formats.is_empty() {
+            ClipboardSnapshot::Empty
+        } else {
+            ClipboardSnapshot::Formats(formats)
+        }))
+    }
+
+    pub fn restore(formats: &[(String, Vec<u8>)]) -> Result<()> {
+        let _open = OpenGuard::open()?;
+        // SAFETY: the clipboard is open.
+        unsafe {
+            EmptyClipboard();
+        }
+        for (format, data) in formats {
+            if let Ok(format) = format.parse() {
+                // One odd format shouldn't cost the user the rest.
+                let _ = put(format, data);
+            }
+        }
+        Ok(())
+    }
+
+    fn register_format(name: &str) -> u32 {
+        let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        // SAFETY: `wide` is NUL-terminated and outlives the call.
+        unsafe { RegisterClipboardFormatW(wide.as_ptr()) }
+    }
+
+    pub fn set_transient_text(text: &str) -> Result<bool> {
+        let _open = OpenGuard::open()?;
+        // SAFETY: the clipboard is open.
+        unsafe {
+            EmptyClipboard();
+        }
+        let wide: Vec<u8> = text
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        put(CF_UNICODETEXT, &wide)?;
+        // Honoured by clipboard history, cloud clipboard and monitoring tools
+        // (https://learn.microsoft.com/windows/win32/dataxchg/clipboard-formats).
+        put(
+            register_format("ExcludeClipboardContentFromMonitorProcessing"),
+            &[0],
+        )?;
+        put(
+            register_format("CanIncludeInClipboardHistory"),
+            &0u32.to_le_bytes(),
+        )?;
+        put(
+            register_format("CanUploadToCloudClipboard"),
+            &0u32.to_le_bytes(),
+        )?;
+        Ok(true)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::ClipboardSnapshot;
+    use crate::credentials::child_command;
+    use anyhow::{bail, Context, Result};
+    use base64::Engine;
+    use std::process::Stdio;
+
+    const CAPTURE: &str = r#"ObjC.import('AppKit');
+function run() {
+    var items = $.NSPasteboard.generalPasteboard.pasteboardItems;
+    var out = [];
+    if (items.count === 0) return '[]';
+    var item = items.objectAtIndex(0);
+    var types = item.types;
+    for (var i = 0; i < types.count; i++) {
+        var type = types.objectAtIndex(i);
+        var data = item.dataForType(type);
+        if (data.isNil()) continue;
+        out.push([type.js, data.base64EncodedStringWithOptions(0).js]);
+    }
+    return JSON.stringify(out);
+}"#;
+
+    const RESTORE: &str = r#"ObjC.import('AppKit');
+function run(argv) {
+    var json = $.NSString.stringWithContentsOfFileEncodingError(argv[0], $.NSUTF8StringEncoding, null).js;
+    var pb = $.NSPasteboard.generalPasteboard;
+    var item = $.NSPasteboardItem.alloc.init;
+    JSON.parse(json).forEach(function (f) {
+        item.setDataForType($.NSData.alloc.initWithBase64EncodedStringOptions($(f[1]), 0), $(f[0]));
+    });
+    pb.clearContents;
+    pb.writeObjects($([item]));
+}"#;
+
+    const SET_TRANSIENT: &str = r#"ObjC.import('AppKit');
+function run(argv) {
+    var pb = $.NSPasteboard.generalPasteboard;
+    pb.clearContents;
+    pb.setStringForType($(argv[0]), $.NSPasteboardTypeString);
+    pb.setStringForType($(''), $('org.nspasteboard.TransientType'));
+}"#;
+
+    fn jxa(script: &str, args: &[&str]) -> Result<String> {
+        let output = child_command("osascript")
+            .args(["-l", "JavaScript", "-e", script])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run osascript")?;
+        if !output.status.success() {
+            bail!(
+                "osascript failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    pub fn capture() -> Result<Option<ClipboardSnapshot>> {
+        let encoded: Vec<(String, String)> =
+            serde_json::from_str(&jxa(CAPTURE, &[])?).context("Unexpected pasteboard listing")?;
+        let formats: Vec<(String, Vec<u8>)> = encoded
+            .into_iter()
+            .filter_map(|(kind, data)| {
+                let data = base64::engine::general_purpose::STANDARD
+                    .decode(data)
+                    .ok()?;
+                Some((kind, data))
+            })
+            .collect();
+        Ok(Some(if formats.is_empty() {
+            ClipboardSnapshot::Empty
+        } else {
+            ClipboardSnapshot::Formats(formats)
+        }))
+    }
+
+    pub fn restore(formats: &[(String, Vec<u8>)]) -> Result<()> {
+        let encoded: Vec<(&str, String)> = formats
+            .iter()
+            .map(|(kind, data)| {
+                let data = base64::engine::general_purpose::STANDARD.encode(data);
+                (kind.as_str(), data)
+            })
+            .collect();
+        // Pasteboard data can be large; hand it over in a file, not argv.
+        let mut file = tempfile::NamedTempFile::new()?;
+        serde_json::to_writer(&mut file, &encoded)?;
+        let path = file.path().to_string_lossy().into_owned();
+        jxa(RESTORE, &[&path])?;
+        Ok(())
+    }
+
+    pub fn set_transient_text(text: &str) -> Result<bool> {
+        jxa(SET_TRANSIENT, &[text])?;
+        Ok(true)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::ClipboardSnapshot;
+    use crate::credentials::child_command;
+    use crate::inject::{find_program, is_wayland_session};
+    use anyhow::{Context, Result};
+    use std::io::Write;
+    use std::process::Stdio;
+
+    /// X11 targets that describe the selection rather than hold its data.
+    const META_TARGETS: &[&str] = &[
+        "TARGETS",
+        "MULTIPLE",
+        "TIMESTAMP",
+        "SAVE_TARGETS",
+        "DELETE",
+        "INSERT_SELECTION",
+        "INSERT_PROPERTY",
+    ];
+
+    fn read(program: &str, args: &[&str]) -> Option<Vec<u8>> {
+        let output = child_command(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then_some(output.stdout)
+    }
+
+    /// The one format worth keeping when only one can be served: an image
+    /// or a file list if there is one, otherwise plain text.
+    pub(super) fn preferred_format(formats: &[&str]) -> Option<String> {
+        let formats: Vec<&str> = formats
+            .iter()
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty() && !META_TARGETS.contains(f))
+            .collect();
+        formats
+            .iter()
+            .find(|f| f.starts_with("image/"))
+            .or_else(|| formats.iter().find(|f| **f == "text/uri-list"))
+            .or_else(|| formats.iter().find(|f| f.starts_with("text/plain")))
+            .or_else(|| formats.iter().find(|f| **f == "UTF8_STRING"))
+            .or_else(|| formats.first())
+            .map(|f| f.to_string())
+    }
+
+    pub fn capture() -> Result<Option<ClipboardSnapshot>> {
+        let wayland = is_wayland_session();
+        if !wayland && find_program("xclip").is_none() {
+            return Ok(None);
+        }
+        let read_format = |format: &str| {
+            if wayland {
+                read("wl-paste", &["--no-newline", "--type", format])
+            } else {
+                read("xclip", &["-selection", "clipboard", "-o", "-t", format])
+            }
+        };
+        // An empty clipboard makes the helpers fail; that's not an error.
+        let list = if wayland {
+            read("wl-paste", &["--list-types"])
+        } else {
+            read_format("TARGETS")
+        };
+        let list = String::from_utf8_lossy(&list.unwrap_or_default()).into_owned();
+        let formats: Vec<&str> = list.lines().collect();
+        let snapshot = preferred_format(&formats)
+            .and_then(|format| Some((format.clone(), read_format(&format)?)))
+            .map_or(ClipboardSnapshot::Empty, |format| {
+                ClipboardSnapshot::Formats(vec![format])
+            });
+        Ok(Some(snapshot))
+    }
+
+    pub fn restore(formats: &[(String, Vec<u8>)]) -> Result<()> {
+        let Some((format, data)) = formats.first() else {
+            return Ok(());
+        };
+        let (program, args) = if is_wayland_session() {
+            ("wl-copy", vec!["--type", format.as_str()])
+        } else {
+            (
+                "xclip",
+                vec!["-selection", "clipboard", "-i", "-t", format.as_str()],
+            )
+        };
+        // Both helpers fork to serve the selection once their input is read.
+        let mut child = child_command(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run {program}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(data)
+                .with_context(|| format!("Failed to write to {program}"))?;
+        }
+        child.wait()?;
+        Ok(())
+    }
+
+    /// The hint is a second target next to the text, which neither helper
+    /// can serve, so the caller sets plain text instead.
+    pub fn set_transient_text(_text: &str) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+mod platform {
+    use super::ClipboardSnapshot;
+    use anyhow::Result;
+
+    pub fn capture() -> Result<Option<ClipboardSnapshot>> {
+        Ok(None)
+    }
+
+    pub fn restore(_formats: &[(String, Vec<u8>)]) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn set_transient_text(_text: &str) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::platform::preferred_format;
+
+    #[test]
+    fn images_win_over_text_and_meta_targets_are_ignored() {
+        let targets = [
+            "TARGETS",
+            "TIMESTAMP",
+            "text/plain",
+            "image/png",
+            "UTF8_STRING",
+        ];
+        assert_eq!(preferred_format(&targets).as_deref(), Some("image/png"));
+        let targets = [
+            "TARGETS",
+            "text/html",
+            "UTF8_STRING",
+            "text/plain;charset=utf-8",
+        ];
+        assert_eq!(
+            preferred_format(&targets).as_deref(),
+            Some("text/plain;charset=utf-8")
+        );
+        assert_eq!(preferred_format(&["TARGETS", "MULTIPLE"]), None);
+    }
+}
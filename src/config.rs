@@ -1,23 +1,93 @@
 use anyhow::{Context, Result};
+use async_openai::config::OpenAIConfig;
+use clap::ValueEnum;
+use cpal::traits::{DeviceTrait, HostTrait};
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, str::FromStr};
 
+use crate::active_window::ActiveWindow;
+use crate::config_migration::{self, CURRENT_VERSION};
+use crate::config_share;
+use crate::credentials::{self, DEFAULT_CREDENTIAL};
 use crate::easy_rdev_key::PTTKey;
+use crate::inject::PasteChord;
 #[cfg(windows)]
 use winreg::{enums::HKEY_CURRENT_USER, RegKey};
 
+/// Replaces `config.json` in the config directory, see [`use_config_file`].
+static CONFIG_FILE: OnceCell<PathBuf> = OnceCell::new();
+
+/// Reads and saves the default config at `path` instead of `config.json` in
+/// the config directory, for the rest of the process (`--config`). Profiles
+/// and statistics stay in the config directory.
+pub fn use_config_file(path: PathBuf) {
+    let _ = CONFIG_FILE.set(path);
+}
+
+fn write_json(path: &Path, value: &serde_json::Value) -> Result<()> {
+    let contents = serde_json::to_string_pretty(value).context("Failed to serialize config")?;
+    fs::write(path, contents).context("Failed to write config file")
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// Format version of the file, see `config_migration.rs`. Always the
+    /// current version once loaded.
+    #[serde(default)]
+    pub config_version: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ptt_key: Option<PTTKey>,
     pub special_ptt_key: Option<u32>,
-    pub device: String,
+    pub device: InputDevice,
     pub use_local: bool,
-    pub local_model: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub local_model: Option<LocalModel>,
     pub cap_first: bool,
     pub space: bool,
+    /// Legacy switch for typing instead of pasting; `output = "type"` is
+    /// the same thing. Kept so older configs and the `paste` default agree.
     pub type_chars: bool,
+    /// Where transcribed text goes ("paste" types instead if `type_chars`).
+    #[serde(default, deserialize_with = "lenient")]
+    pub output: OutputMode,
+    /// File that transcriptions are appended to when `output` is "file".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+    /// Key chord sent to paste, e.g. "ctrl+v", "ctrl+shift+v" (terminals) or
+    /// "shift+insert". Modifiers: ctrl, shift, alt, cmd/super.
+    #[serde(default = "default_paste_shortcut")]
+    pub paste_shortcut: String,
+    /// Ask clipboard managers and clipboard history to ignore the text we put
+    /// on the clipboard to paste. Windows and macOS only: the Linux helpers
+    /// serve a single format, so the hint can't be attached there.
+    #[serde(default = "default_true")]
+    pub clipboard_transient: bool,
+    /// Minimum time (ms) between sending the paste chord and restoring the
+    /// previous clipboard. Longer texts wait a little more on top of this.
+    #[serde(default = "default_paste_restore_delay_ms")]
+    pub paste_restore_delay_ms: u64,
+    /// Per-application overrides, checked in order against the focused
+    /// window; the first match wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub app_rules: Vec<AppRule>,
+    /// Serve the local control socket (see `ipc.rs`) so scripts can drive
+    /// the engine.
+    #[serde(default = "default_true")]
+    pub control_socket: bool,
+    /// Register the `org.desktalk` D-Bus service (Linux only).
+    #[serde(default = "default_true")]
+    pub dbus_service: bool,
+    /// Text substitutions applied to every transcription, e.g. spoken
+    /// "new line" to a line break. App rules can add more.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replacements: Vec<Replacement>,
+    /// Run after every successful transcription (see `hooks.rs`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
     #[serde(default)]
     pub punctuation: bool,
     #[serde(default)]
@@ -28,6 +98,14 @@ pub struct AppConfig {
     pub dark_mode: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
+    /// Name of the stored API key to use (see `credentials.rs`); the
+    /// "default" key if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+    /// Also write the API key in plain text to a `.env` file next to the
+    /// executable when it is saved. The keyring is used either way.
+    #[serde(default)]
+    pub write_env_file: bool,
     /// User's typing speed in words per minute (for calculating time saved)
     #[serde(default = "default_typing_wpm")]
     pub typing_wpm: u32,
@@ -40,42 +118,495 @@ pub struct AppConfig {
     #[serde(default)]
     pub realtime: bool,
     /// Latency/accuracy tradeoff for the realtime (gpt-realtime-whisper) model.
-    /// One of: minimal, low, medium, high, xhigh. Higher = more audio context
-    /// before emitting text = better accuracy but more delay.
-    #[serde(default = "default_realtime_delay")]
-    pub realtime_delay: String,
-    /// How the end of an utterance is punctuated. One of:
-    ///   "none"   - leave the ending exactly as transcribed
-    ///   "period" - add a plain "." if it doesn't already end with . ? !
-    ///   "smart"  - use a cheap LLM to choose the correct mark (. ? !,
-    ///              language-aware)
-    /// These are mutually exclusive by design, so this replaces the old
-    /// separate `period` / `smart_punctuation` booleans.
-    #[serde(default = "default_end_punctuation", alias = "end_punct")]
-    pub end_punctuation: String,
+    /// Higher = more audio context before emitting text = better accuracy but
+    /// more delay.
+    #[serde(default, deserialize_with = "lenient")]
+    pub realtime_delay: RealtimeDelay,
+    /// Keep a configured realtime connection open between utterances so
+    /// speech starts streaming the moment the key goes down. Holds one idle
+    /// WebSocket to OpenAI while the engine is running.
+    #[serde(default)]
+    pub realtime_prewarm: bool,
+    /// When a realtime segment's final transcript differs from the deltas
+    /// already typed, erase the differing tail and retype it.
+    #[serde(default)]
+    pub realtime_reconcile: bool,
+    /// Where live text goes while the key is held, in realtime and local
+    /// streaming.
+    #[serde(default, deserialize_with = "lenient")]
+    pub realtime_output: RealtimeOutput,
+    /// Stream audio into the local whisper model while the key is held and
+    /// type stabilized text live, instead of transcribing once on release.
+    /// Only applies when using a local model.
+    #[serde(default)]
+    pub local_streaming: bool,
+    /// How the end of an utterance is punctuated. The modes are mutually
+    /// exclusive by design, so this replaces the old separate `period` /
+    /// `smart_punctuation` booleans.
+    #[serde(default, deserialize_with = "lenient")]
+    pub end_punctuation: EndPunctuation,
+    /// Estimated API spend (US dollars) allowed per day and per calendar
+    /// month; no limit if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_budget: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_budget: Option<f64>,
+    /// What the engine does while a budget is used up.
+    #[serde(default, deserialize_with = "lenient")]
+    pub budget_action: BudgetAction,
+}
+
+/// Overrides applied when the focused window (captured when the PTT key goes
+/// down) matches. `window_class` and `window_title` are case-insensitive
+/// substrings, `process` a case-insensitive executable name (without `.exe`);
+/// every matcher a rule sets has to match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    /// Paste chord to use instead of `paste_shortcut`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paste_shortcut: Option<String>,
+    /// The application mangles or ignores pastes: type the text instead.
+    #[serde(default)]
+    pub paste_hostile: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cap_first: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub space: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "lenient"
+    )]
+    pub end_punctuation: Option<EndPunctuation>,
+    /// Output mode for this application (see `AppConfig::output`).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "lenient"
+    )]
+    pub output: Option<OutputMode>,
+    /// Applied after the global `replacements`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replacements: Vec<Replacement>,
+}
+
+impl AppRule {
+    pub fn matches(&self, window: &ActiveWindow) -> bool {
+        fn contains(haystack: &str, needle: &Option<String>) -> Option<bool> {
+            let needle = needle.as_deref()?.trim();
+            Some(haystack.to_lowercase().contains(&needle.to_lowercase()))
+        }
+        let process = self
+            .process
+            .as_deref()
+            .map(|name| name.trim().eq_ignore_ascii_case(&window.process));
+        let checks = [
+            contains(&window.class, &self.window_class),
+            contains(&window.title, &self.window_title),
+            process,
+        ];
+        checks.iter().any(Option::is_some) && checks.iter().all(|check| check.unwrap_or(true))
+    }
+}
+
+/// Something to notify after a transcription: a command (run through the
+/// shell, transcript on stdin, metadata in `DESKTALK_*` variables) or a URL
+/// that gets the transcript POSTed as JSON. A hook that sets both does both.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Hook {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// A command still running after this long is killed; a request is
+    /// abandoned.
+    #[serde(default = "default_hook_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// Replaces `from` with `to` in transcribed text (ASCII case-insensitive,
+/// since transcripts capitalize unpredictably). `to` may contain "\n".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+}
+
+impl Replacement {
+    pub fn apply(&self, text: &str) -> String {
+        if self.from.is_empty() {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while !rest.is_empty() {
+            let hit = rest.len() >= self.from.len()
+                && rest.is_char_boundary(self.from.len())
+                && rest[..self.from.len()].eq_ignore_ascii_case(&self.from);
+            if hit {
+                out.push_str(&self.to);
+                rest = &rest[self.from.len()..];
+            } else {
+                let c = rest.chars().next().unwrap_or_default();
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        out
+    }
+}
+
+/// How the end of an utterance is punctuated.
+///
+/// The doc comment on each variant is its label in the settings window and
+/// its description in `--help`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EndPunctuation {
+    /// None (leave as-is)
+    #[serde(alias = "off")]
+    None,
+    /// Period (always add ".")
+    Period,
+    /// Smart (AI picks . ? !)
+    #[default]
+    Smart,
+}
+
+/// Where transcribed text goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Paste into the app
+    #[default]
+    Paste,
+    /// Type characters
+    Type,
+    /// Copy to clipboard only
+    Clipboard,
+    /// Print to console
+    Stdout,
+    /// Append to a file
+    File,
+}
+
+/// Where live text goes while the key is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RealtimeOutput {
+    /// Type into the app as I speak
+    #[default]
+    Type,
+    /// Show in a preview, insert on release
+    Preview,
 }
 
-fn default_realtime_delay() -> String {
-    "xhigh".to_string()
+/// Latency/accuracy tradeoff of the realtime model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RealtimeDelay {
+    /// Minimal (fastest)
+    Minimal,
+    /// Low
+    Low,
+    /// Medium
+    Medium,
+    /// High
+    High,
+    /// Extra high (most accurate)
+    #[default]
+    #[serde(alias = "x-high", alias = "extra-high")]
+    #[value(alias = "x-high")]
+    Xhigh,
 }
 
-fn default_end_punctuation() -> String {
-    "smart".to_string()
+/// What happens once a spending budget is used up (see `spending.rs`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    /// Warn (keep going)
+    #[default]
+    Warn,
+    /// Reduce (one request, no AI punctuation)
+    Reduce,
+    /// Local (switch to the local model)
+    Local,
 }
 
-/// Returns the mode if it's valid, otherwise the default ("smart").
-pub fn sanitize_end_punctuation(value: &str) -> String {
-    match value.to_lowercase().as_str() {
-        v @ ("none" | "period" | "smart") => v.to_string(),
-        _ => default_end_punctuation(),
+/// A local whisper model. The `.en` spellings are what whisper.cpp calls the
+/// model files, so they're accepted too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocalModel {
+    /// Tiny (English) - Fastest
+    #[serde(alias = "tiny.en")]
+    #[value(alias = "tiny.en")]
+    TinyEn,
+    /// Tiny (Multilingual)
+    Tiny,
+    /// Base (English)
+    #[serde(alias = "base.en")]
+    #[value(alias = "base.en")]
+    BaseEn,
+    /// Base (Multilingual)
+    Base,
+    /// Small (English)
+    #[serde(alias = "small.en")]
+    #[value(alias = "small.en")]
+    SmallEn,
+    /// Small (Multilingual)
+    Small,
+    /// Medium (English)
+    #[serde(alias = "medium.en")]
+    #[value(alias = "medium.en")]
+    MediumEn,
+    /// Medium (Multilingual)
+    Medium,
+    /// Large V1
+    LargeV1,
+    /// Large V2
+    LargeV2,
+    /// Large V3 - Best Quality
+    LargeV3,
+}
+
+impl LocalModel {
+    pub fn model_type(self) -> mutter::ModelType {
+        use mutter::ModelType;
+        match self {
+            LocalModel::TinyEn => ModelType::TinyEn,
+            LocalModel::Tiny => ModelType::Tiny,
+            LocalModel::BaseEn => ModelType::BaseEn,
+            LocalModel::Base => ModelType::Base,
+            LocalModel::SmallEn => ModelType::SmallEn,
+            LocalModel::Small => ModelType::Small,
+            LocalModel::MediumEn => ModelType::MediumEn,
+            LocalModel::Medium => ModelType::Medium,
+            LocalModel::LargeV1 => ModelType::LargeV1,
+            LocalModel::LargeV2 => ModelType::LargeV2,
+            LocalModel::LargeV3 => ModelType::LargeV3,
+        }
     }
 }
 
-/// Returns the delay if it's a valid level, otherwise the default ("high").
-pub fn sanitize_realtime_delay(value: &str) -> String {
-    match value.to_lowercase().as_str() {
-        v @ ("minimal" | "low" | "medium" | "high" | "xhigh") => v.to_string(),
-        _ => default_realtime_delay(),
+/// Prints the value as it's written in the config file and on the command
+/// line.
+macro_rules! display_as_value_name {
+    ($($ty:ty),*) => {$(
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let value = self.to_possible_value().expect("no skipped variants");
+                f.write_str(value.get_name())
+            }
+        }
+    )*};
+}
+
+display_as_value_name!(
+    OutputMode,
+    RealtimeOutput,
+    EndPunctuation,
+    RealtimeDelay,
+    LocalModel,
+    BudgetAction
+);
+
+/// One allowed value of a setting, for the settings window's dropdowns.
+#[derive(Debug, Clone, Serialize)]
+pub struct Choice {
+    pub value: String,
+    pub label: String,
+}
+
+/// Every value of `T` with its label (the variant's doc comment).
+pub fn choices<T: ValueEnum>() -> Vec<Choice> {
+    T::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .map(|value| Choice {
+            value: value.get_name().to_string(),
+            label: value
+                .get_help()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| value.get_name().to_string()),
+        })
+        .collect()
+}
+
+/// The microphone to record from. Stored as the device name, or "default"
+/// for the system default input.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum InputDevice {
+    #[default]
+    Default,
+    Named(String),
+}
+
+impl InputDevice {
+    /// Looks the device up on the default audio host.
+    pub fn find(&self) -> Result<cpal::Device> {
+        let host = cpal::default_host();
+        let device = match self {
+            InputDevice::Default => host.default_input_device(),
+            InputDevice::Named(name) => host
+                .input_devices()
+                .context("Failed to get list of input devices")?
+                .find(|device| device.name().is_ok_and(|n| &n == name)),
+        };
+        device.with_context(|| format!("Failed to find input device '{}'", self))
+    }
+}
+
+impl From<String> for InputDevice {
+    fn from(name: String) -> Self {
+        // Older configs (and hand-written ones) use "" or "Default" too.
+        let trimmed = name.trim();
+        if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("default") {
+            InputDevice::Default
+        } else {
+            InputDevice::Named(name)
+        }
+    }
+}
+
+impl From<InputDevice> for String {
+    fn from(device: InputDevice) -> Self {
+        device.to_string()
+    }
+}
+
+impl FromStr for InputDevice {
+    type Err = std::convert::Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(name.to_string().into())
+    }
+}
+
+impl fmt::Display for InputDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputDevice::Default => f.write_str("default"),
+            InputDevice::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A typed setting as written by hand: case and surrounding spaces don't
+/// matter.
+fn normalized(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => serde_json::Value::String(s.trim().to_lowercase()),
+        other => other.clone(),
+    }
+}
+
+/// Reads a typed setting from the config file as leniently as the old string
+/// settings were read: an unknown value falls back to the default (with a
+/// warning) instead of making the whole config unreadable. Settings from the
+/// settings window or an imported file are checked by [`check_choices`]
+/// first, so a bad value there is an error instead.
+fn lenient<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + Default,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let setting = serde_json::from_value(normalized(&value)).unwrap_or_else(|err| {
+        eprintln!("Ignoring invalid setting {}: {}", value, err);
+        T::default()
+    });
+    Ok(setting)
+}
+
+/// One [`ConfigError`] per typed setting in `value` (a whole or partial
+/// config) that [`lenient`] would replace with the default.
+pub(crate) fn check_choices(value: &serde_json::Value) -> Result<(), Vec<ConfigError>> {
+    /// Checks `value[key]`, reported as `{prefix}{key}`.
+    fn check<T: DeserializeOwned>(
+        value: &serde_json::Value,
+        prefix: &str,
+        key: &str,
+        errors: &mut Vec<ConfigError>,
+    ) {
+        if let Some(setting) = value.get(key) {
+            if let Err(err) = serde_json::from_value::<Option<T>>(normalized(setting)) {
+                errors.push(ConfigError::new(format!("{}{}", prefix, key), err));
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    check::<LocalModel>(value, "", "local_model", &mut errors);
+    check::<OutputMode>(value, "", "output", &mut errors);
+    check::<RealtimeDelay>(value, "", "realtime_delay", &mut errors);
+    check::<RealtimeOutput>(value, "", "realtime_output", &mut errors);
+    check::<EndPunctuation>(value, "", "end_punctuation", &mut errors);
+    check::<BudgetAction>(value, "", "budget_action", &mut errors);
+    let rules = value.get("app_rules").and_then(|rules| rules.as_array());
+    for (i, rule) in rules.into_iter().flatten().enumerate() {
+        let prefix = format!("app_rules[{}].", i);
+        check::<EndPunctuation>(rule, &prefix, "end_punctuation", &mut errors);
+        check::<OutputMode>(rule, &prefix, "output", &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// A setting that can't be saved. `field` is the config key (`hooks[1]` for
+/// list entries), or empty for errors that aren't about one setting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(field: impl Into<String>, message: impl fmt::Display) -> Self {
+        Self {
+            field: field.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.field.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_hook_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_paste_restore_delay_ms() -> u64 {
+    150
+}
+
+fn default_paste_shortcut() -> String {
+    if cfg!(target_os = "macos") {
+        "cmd+v".to_string()
+    } else {
+        "ctrl+v".to_string()
     }
 }
 
@@ -90,87 +621,191 @@ fn default_parallel() -> usize {
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            config_version: CURRENT_VERSION,
             ptt_key: None,
             special_ptt_key: None,
-            device: String::from("default"),
+            device: InputDevice::Default,
             use_local: false,
             local_model: None,
             cap_first: true,
             space: true,
             type_chars: false,
+            output: OutputMode::default(),
+            output_file: None,
+            paste_shortcut: default_paste_shortcut(),
+            clipboard_transient: true,
+            paste_restore_delay_ms: default_paste_restore_delay_ms(),
+            app_rules: Vec::new(),
+            control_socket: true,
+            dbus_service: true,
+            replacements: Vec::new(),
+            hooks: Vec::new(),
             punctuation: false,
             auto_start: false,
             start_minimized: false,
             dark_mode: false,
             api_key: None,
+            credential: None,
+            write_env_file: false,
             typing_wpm: default_typing_wpm(),
             parallel: default_parallel(),
             realtime: true,
-            realtime_delay: default_realtime_delay(),
-            end_punctuation: default_end_punctuation(),
+            realtime_delay: RealtimeDelay::default(),
+            realtime_prewarm: false,
+            realtime_reconcile: false,
+            realtime_output: RealtimeOutput::default(),
+            local_streaming: false,
+            end_punctuation: EndPunctuation::default(),
+            daily_budget: None,
+            monthly_budget: None,
+            budget_action: BudgetAction::default(),
         }
     }
 }
 
 impl AppConfig {
-    fn get_config_path() -> Result<PathBuf> {
+    pub(crate) fn get_config_dir() -> Result<PathBuf> {
         let proj_dirs = ProjectDirs::from("com", "desk-talk", "desk-talk")
             .context("Failed to determine project directories")?;
         let config_dir = proj_dirs.config_dir();
         fs::create_dir_all(config_dir)?;
-        Ok(config_dir.join("config.json"))
+        Ok(config_dir.to_path_buf())
+    }
+
+    /// `config.json` for the default profile, `profiles/<name>.json` for a
+    /// named one.
+    pub(crate) fn get_config_path(profile: Option<&str>) -> Result<PathBuf> {
+        let config_dir = Self::get_config_dir()?;
+        let Some(name) = profile else {
+            if let Some(path) = CONFIG_FILE.get() {
+                return Ok(path.clone());
+            }
+            return Ok(config_dir.join("config.json"));
+        };
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            anyhow::bail!(
+                "Invalid profile name \"{}\" (use letters, digits, - and _)",
+                name
+            );
+        }
+        let profiles_dir = config_dir.join("profiles");
+        fs::create_dir_all(&profiles_dir)?;
+        Ok(profiles_dir.join(format!("{}.json", name)))
+    }
+
+    /// Names of the saved profiles (besides the default one).
+    pub fn list_profiles() -> Result<Vec<String>> {
+        let profiles_dir = Self::get_config_dir()?.join("profiles");
+        let mut names: Vec<String> = match fs::read_dir(&profiles_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| {
+                    let path = entry.ok()?.path();
+                    if path.extension()? != "json" {
+                        return None;
+                    }
+                    Some(path.file_stem()?.to_string_lossy().into_owned())
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        names.sort();
+        Ok(names)
     }
 
     pub fn load() -> Result<Self> {
-        let config_path = Self::get_config_path()?;
+        Self::load_profile(None)
+    }
+
+    /// Loads a profile. A named profile (or a `--config` file) has to exist;
+    /// the default one falls back to defaults. Settings in the managed config
+    /// take precedence over the file.
+    pub fn load_profile(profile: Option<&str>) -> Result<Self> {
+        let config_path = Self::get_config_path(profile)?;
+        if !config_path.exists() {
+            if let Some(name) = profile {
+                anyhow::bail!("Profile \"{}\" not found", name);
+            }
+            if CONFIG_FILE.get().is_some() {
+                anyhow::bail!("Config file {} not found", config_path.display());
+            }
+        }
 
         let mut config = if config_path.exists() {
-            let contents =
-                fs::read_to_string(&config_path).context("Failed to read config file")?;
-            let cfg: AppConfig =
-                serde_json::from_str(&contents).context("Failed to parse config file")?;
-            cfg
+            Self::read_file(&config_path)?
         } else {
             AppConfig::default()
         };
+        config_share::apply_managed(&mut config)?;
 
         // Load API key from keyring
-        config.api_key = Self::load_api_key().ok();
-        println!(
+        config.api_key = config.load_api_key().ok();
+        // stderr, so `--print-config` output stays plain JSON.
+        eprintln!(
             "Config loaded: api_key present = {}",
             config.api_key.is_some()
         );
         if let Some(ref key) = config.api_key {
-            println!("  API key length: {}", key.len());
+            eprintln!("  API key length: {}", key.len());
         }
 
         Ok(config)
     }
 
-    pub fn save(&self) -> Result<()> {
-        let config_path = Self::get_config_path()?;
+    /// Reads a config file, upgrading it first if an older version wrote it
+    /// (the original is kept as a backup).
+    fn read_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).context("Failed to read config file")?;
+        let mut value: serde_json::Value =
+            serde_json::from_str(&contents).context("Failed to parse config file")?;
+        let version = config_migration::migrate(&mut value)?;
+        let config: AppConfig =
+            serde_json::from_value(value).context("Failed to parse config file")?;
+        if version < CURRENT_VERSION {
+            // Keep using the upgraded config even if it can't be written back.
+            match config_migration::backup(path, version).and_then(|backup| {
+                config.write_file(path)?;
+                Ok(backup)
+            }) {
+                Ok(backup) => eprintln!(
+                    "Upgraded {} from config version {} to {} (original saved as {})",
+                    path.display(),
+                    version,
+                    CURRENT_VERSION,
+                    backup.display()
+                ),
+                Err(err) => eprintln!("Warning: Failed to upgrade {}: {:?}", path.display(), err),
+            }
+        }
+        Ok(config)
+    }
 
+    /// The config as it is written to disk: without the API key.
+    pub(crate) fn to_file_json(&self) -> Result<serde_json::Value> {
         // Clone so we can strip secrets before persisting JSON
         let mut config_for_disk = self.clone();
         config_for_disk.api_key = None;
+        config_for_disk.config_version = CURRENT_VERSION;
+        serde_json::to_value(&config_for_disk).context("Failed to serialize config")
+    }
 
-        let contents =
-            serde_json::to_string_pretty(&config_for_disk).context("Failed to serialize config")?;
-        fs::write(&config_path, contents).context("Failed to write config file")?;
+    /// Writes the config as JSON, without the API key.
+    pub(crate) fn write_file(&self, path: &Path) -> Result<()> {
+        write_json(path, &self.to_file_json()?)
+    }
 
-        // Save API key to keyring/.env
-        if let Some(api_key) = &self.api_key {
-            if let Err(e) = Self::save_api_key(api_key) {
-                eprintln!(
-                    "Warning: Failed to save API key to keyring: {}. Using .env file instead.",
-                    e
-                );
-                Self::save_api_key_to_env(api_key)?;
-            } else {
-                // Keep .env in sync even if keyring worked (handy for debugging)
-                let _ = Self::save_api_key_to_env(api_key);
-            }
-        }
+    /// Writes the config file of `profile`, without the API key; a new key
+    /// is stored with [`Self::save_api_key`].
+    pub fn save_profile(&self, profile: Option<&str>) -> Result<()> {
+        let config_path = Self::get_config_path(profile)?;
+        // Settings the managed config enforces keep the user's own values on
+        // disk, so they come back if the managed config goes away.
+        let mut value = self.to_file_json()?;
+        config_share::keep_user_values(&mut value, &config_path)?;
+        write_json(&config_path, &value)?;
 
         #[cfg(windows)]
         {
@@ -184,58 +819,55 @@ impl AppConfig {
         Ok(())
     }
 
-    pub fn load_api_key() -> Result<String> {
-        // Try keyring first
-        if let Ok(entry) = keyring::Entry::new("desk-talk", "openai-api-key") {
-            if let Ok(key) = entry.get_password() {
-                return Ok(key);
-            }
-        }
-
-        // Fallback to .env file
-        Self::load_api_key_from_env()
+    /// Name of the stored API key this config uses.
+    pub fn credential_name(&self) -> &str {
+        self.credential.as_deref().unwrap_or(DEFAULT_CREDENTIAL)
     }
 
-    pub fn save_api_key(api_key: &str) -> Result<()> {
-        let entry = keyring::Entry::new("desk-talk", "openai-api-key")
-            .context("Failed to create keyring entry")?;
-        entry
-            .set_password(api_key)
-            .context("Failed to save API key to keyring")
+    /// The API key this config uses, from the keyring. A default key still
+    /// in `.env` from older versions is moved to the keyring first.
+    pub fn load_api_key(&self) -> Result<String> {
+        let name = self.credential_name();
+        if name == DEFAULT_CREDENTIAL {
+            if let Err(err) = credentials::import_env_file(self.write_env_file) {
+                eprintln!("Warning: Failed to move the .env API key: {:#}", err);
+            }
+        }
+        credentials::load_key(name)
     }
 
-    fn save_api_key_to_env(api_key: &str) -> Result<()> {
-        use std::env;
-        let exe_dir = env::current_exe()?
-            .parent()
-            .context("Failed to get exe directory")?
-            .to_path_buf();
-        let env_path = exe_dir.join(".env");
-        fs::write(&env_path, format!("OPENAI_API_KEY={}", api_key))
-            .context("Failed to write .env file")?;
-        Ok(())
+    /// Client settings for the OpenAI API (or the compatible endpoint stored
+    /// with this config's key). The key is passed explicitly, never through
+    /// the process environment.
+    pub fn openai_config(&self) -> Result<OpenAIConfig> {
+        let mut openai = OpenAIConfig::new().with_api_key(self.api_key.clone().unwrap_or_default());
+        if let Some(api_base) = credentials::api_base(self.credential_name())? {
+            openai = openai.with_api_base(api_base);
+        }
+        Ok(openai)
     }
 
-    fn load_api_key_from_env() -> Result<String> {
-        use std::env;
-
-        // Try loading from .env file next to the executable
-        let exe_dir = env::current_exe()?
-            .parent()
-            .context("Failed to get exe directory")?
-            .to_path_buf();
-        let env_path = exe_dir.join(".env");
-
-        if env_path.exists() {
-            let contents = fs::read_to_string(&env_path)?;
-            for line in contents.lines() {
-                if let Some(key) = line.strip_prefix("OPENAI_API_KEY=") {
-                    return Ok(key.to_string());
+    /// Stores `api_key` as this config's key, and in `.env` too if
+    /// `write_env_file` is on. Without a working keyring the default key
+    /// goes to `.env` instead.
+    pub fn save_api_key(&self, api_key: &str) -> Result<()> {
+        let name = self.credential_name();
+        match credentials::save_key(name, api_key, None) {
+            Ok(()) => {
+                if self.write_env_file {
+                    credentials::save_key_to_env(api_key)?;
                 }
+                Ok(())
+            }
+            Err(e) if name == DEFAULT_CREDENTIAL => {
+                eprintln!(
+                    "Warning: Failed to save API key to keyring: {}. Using .env file instead.",
+                    e
+                );
+                credentials::save_key_to_env(api_key)
             }
+            Err(e) => Err(e),
         }
-
-        Err(anyhow::anyhow!("No API key found in keyring or .env file"))
     }
 
     #[cfg(windows)]
@@ -264,6 +896,166 @@ impl AppConfig {
         Ok(())
     }
 
+    /// The effective output mode, folding the legacy `type_chars` switch into
+    /// the default "paste" mode.
+    pub fn output_mode(&self) -> OutputMode {
+        if self.output == OutputMode::Paste && self.type_chars {
+            OutputMode::Type
+        } else {
+            self.output
+        }
+    }
+
+    /// The paste chord, falling back to the platform default when unset.
+    pub fn paste_shortcut(&self) -> String {
+        if self.paste_shortcut.trim().is_empty() {
+            default_paste_shortcut()
+        } else {
+            self.paste_shortcut.clone()
+        }
+    }
+
+    /// The settings for one utterance: this config with the first app rule
+    /// matching `window` applied.
+    pub fn for_window(&self, window: Option<&ActiveWindow>) -> AppConfig {
+        let mut config = self.clone();
+        let rule = window.and_then(|w| self.app_rules.iter().find(|r| r.matches(w)));
+        let Some(rule) = rule else {
+            return config;
+        };
+        if let Some(cap_first) = rule.cap_first {
+            config.cap_first = cap_first;
+        }
+        if let Some(space) = rule.space {
+            config.space = space;
+        }
+        if let Some(end_punctuation) = rule.end_punctuation {
+            config.end_punctuation = end_punctuation;
+        }
+        if let Some(output) = rule.output {
+            config.output = output;
+            config.type_chars = false;
+        }
+        if let Some(shortcut) = &rule.paste_shortcut {
+            config.paste_shortcut = shortcut.clone();
+        }
+        if rule.paste_hostile && config.output_mode() == OutputMode::Paste {
+            config.output = OutputMode::Type;
+        }
+        config
+            .replacements
+            .extend(rule.replacements.iter().cloned());
+        config
+    }
+
+    /// Reads settings sent by the settings window. Unlike the config file, a
+    /// value a typed setting doesn't take is an error for that field rather
+    /// than the default.
+    pub fn from_input(value: serde_json::Value) -> Result<Self, Vec<ConfigError>> {
+        check_choices(&value)?;
+        serde_json::from_value(value).map_err(|err| vec![ConfigError::new("", err)])
+    }
+
+    /// Checks what the types can't: ranges, paste chords, settings that need
+    /// another one, and list entries that would never do anything. Returns
+    /// every problem, not just the first.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        fn check_chord(chord: &str, field: &str, errors: &mut Vec<ConfigError>) {
+            if let Err(err) = chord.parse::<PasteChord>() {
+                errors.push(ConfigError::new(field, err));
+            }
+        }
+
+        let mut errors = Vec::new();
+        let no_output_file = self.output_file.as_deref().unwrap_or("").trim().is_empty();
+        if self.output == OutputMode::File && no_output_file {
+            errors.push(ConfigError::new(
+                "output_file",
+                "Choose a file to append to",
+            ));
+        }
+        if !self.paste_shortcut.trim().is_empty() {
+            check_chord(&self.paste_shortcut, "paste_shortcut", &mut errors);
+        }
+        if !(1..=5).contains(&self.parallel) {
+            errors.push(ConfigError::new(
+                "parallel",
+                "Race between 1 and 5 requests",
+            ));
+        }
+        if let Some(name) = &self.credential {
+            if let Err(err) = credentials::check_name(name) {
+                errors.push(ConfigError::new("credential", err));
+            }
+        }
+        for (budget, field) in [
+            (self.daily_budget, "daily_budget"),
+            (self.monthly_budget, "monthly_budget"),
+        ] {
+            if budget.is_some_and(|budget| !budget.is_finite() || budget < 0.0) {
+                errors.push(ConfigError::new(field, "A budget can't be negative"));
+            }
+        }
+        if self.budget_action == BudgetAction::Local && self.local_model.is_none() {
+            errors.push(ConfigError::new(
+                "budget_action",
+                "Select a local model to switch to",
+            ));
+        }
+        if self.typing_wpm == 0 {
+            errors.push(ConfigError::new(
+                "typing_wpm",
+                "Typing speed must be at least 1 WPM",
+            ));
+        }
+        for (i, rule) in self.app_rules.iter().enumerate() {
+            let field = format!("app_rules[{}]", i);
+            let has_matcher = [&rule.window_class, &rule.window_title, &rule.process]
+                .iter()
+                .any(|matcher| matcher.as_deref().is_some_and(|m| !m.trim().is_empty()));
+            if !has_matcher {
+                errors.push(ConfigError::new(
+                    &field,
+                    "Set window_class, window_title or process, or the rule never applies",
+                ));
+            }
+            if let Some(chord) = &rule.paste_shortcut {
+                check_chord(chord, &format!("{}.paste_shortcut", field), &mut errors);
+            }
+            if rule.output == Some(OutputMode::File) && no_output_file {
+                errors.push(ConfigError::new(
+                    format!("{}.output", field),
+                    "Set output_file to append to",
+                ));
+            }
+        }
+        for (i, hook) in self.hooks.iter().enumerate() {
+            let set =
+                |value: &Option<String>| value.as_deref().is_some_and(|v| !v.trim().is_empty());
+            if !set(&hook.command) && !set(&hook.url) {
+                errors.push(ConfigError::new(
+                    format!("hooks[{}]", i),
+                    "Set a command or a URL",
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Runs `text` through all configured replacements.
+    pub fn apply_replacements(&self, text: &str) -> String {
+        self.replacements
+            .iter()
+            .fold(text.to_string(), |text, replacement| {
+                replacement.apply(&text)
+            })
+    }
+
     pub fn get_ptt_key(&self) -> Option<rdev::Key> {
         if let Some(ptt_key) = self.ptt_key {
             Some(ptt_key.into())
@@ -274,3 +1066,149 @@ impl AppConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal() -> ActiveWindow {
+        ActiveWindow {
+            class: "Gnome-terminal".into(),
+            title: "me@host: ~".into(),
+            process: "gnome-terminal-server".into(),
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_overrides_formatting() {
+        let mut config = AppConfig {
+            replacements: vec![Replacement {
+                from: "new line".into(),
+                to: "\n".into(),
+            }],
+            ..AppConfig::default()
+        };
+        config.app_rules = vec![
+            AppRule {
+                window_class: Some("terminal".into()),
+                process: Some("bash".into()),
+                cap_first: Some(true),
+                ..AppRule::default()
+            },
+            AppRule {
+                window_class: Some("TERMINAL".into()),
+                cap_first: Some(false),
+                end_punctuation: Some(EndPunctuation::None),
+                replacements: vec![Replacement {
+                    from: "dash".into(),
+                    to: "-".into(),
+                }],
+                ..AppRule::default()
+            },
+        ];
+
+        let terminal = config.for_window(Some(&terminal()));
+        assert!(!terminal.cap_first);
+        assert_eq!(terminal.end_punctuation, EndPunctuation::None);
+        assert_eq!(
+            terminal.apply_replacements("ls Dash l New line"),
+            "ls - l \n"
+        );
+
+        let elsewhere = config.for_window(None);
+        assert!(elsewhere.cap_first);
+        assert_eq!(elsewhere.apply_replacements("a dash"), "a dash");
+    }
+
+    #[test]
+    fn rules_pick_the_paste_chord_or_typing() {
+        let mut config = AppConfig {
+            app_rules: vec![AppRule {
+                window_class: Some("terminal".into()),
+                paste_shortcut: Some("ctrl+shift+v".into()),
+                ..AppRule::default()
+            }],
+            ..AppConfig::default()
+        };
+        let terminal_config = config.for_window(Some(&terminal()));
+        assert_eq!(terminal_config.paste_shortcut, "ctrl+shift+v");
+        assert_eq!(terminal_config.output_mode(), OutputMode::Paste);
+
+        config.app_rules[0].paste_hostile = true;
+        assert_eq!(
+            config.for_window(Some(&terminal())).output_mode(),
+            OutputMode::Type
+        );
+    }
+
+    #[test]
+    fn rules_without_matchers_match_nothing() {
+        assert!(!AppRule::default().matches(&terminal()));
+    }
+
+    #[test]
+    fn legacy_values_still_load() {
+        let config: AppConfig = serde_json::from_value(serde_json::json!({
+            "special_ptt_key": null,
+            "device": "Default",
+            "use_local": true,
+            "local_model": "Base.en",
+            "cap_first": true,
+            "space": true,
+            "type_chars": false,
+            "realtime_delay": "XHIGH",
+            "end_punctuation": "typo",
+            "output": "Clipboard",
+            "realtime_output": "overlay",
+        }))
+        .unwrap();
+        assert_eq!(config.device, InputDevice::Default);
+        assert_eq!(config.local_model, Some(LocalModel::BaseEn));
+        assert_eq!(config.realtime_delay, RealtimeDelay::Xhigh);
+        assert_eq!(config.end_punctuation, EndPunctuation::Smart);
+        assert_eq!(config.output, OutputMode::Clipboard);
+        assert_eq!(config.realtime_output, RealtimeOutput::Type);
+
+        let saved = serde_json::to_value(&config).unwrap();
+        assert_eq!(saved["device"], "default");
+        assert_eq!(saved["local_model"], "base-en");
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let config = AppConfig {
+            output: OutputMode::File,
+            parallel: 0,
+            hooks: vec![Hook::default()],
+            ..AppConfig::default()
+        };
+        let fields: Vec<String> = config
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, ["output_file", "parallel", "hooks[0]"]);
+        assert!(AppConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn settings_window_values_are_checked_strictly() {
+        let mut value = serde_json::to_value(AppConfig::default()).unwrap();
+        value["end_punctuation"] = "bogus".into();
+        value["realtime_delay"] = " High ".into();
+        value["app_rules"] = serde_json::json!([{ "process": "code", "output": "paper" }]);
+        let fields: Vec<String> = AppConfig::from_input(value.clone())
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, ["end_punctuation", "app_rules[0].output"]);
+
+        value["end_punctuation"] = "period".into();
+        value["app_rules"] = serde_json::json!([{ "process": "code", "output": null }]);
+        let config = AppConfig::from_input(value).unwrap();
+        assert_eq!(config.end_punctuation, EndPunctuation::Period);
+        assert_eq!(config.realtime_delay, RealtimeDelay::High);
+    }
+}
@@ -0,0 +1,212 @@
+//! Upgrading config files written by older versions.
+//!
+//! Every config file carries a `config_version`; files from before it was
+//! added count as version 0. On load, the raw JSON goes through each
+//! migration from its version up to [`CURRENT_VERSION`] before it is
+//! deserialized, and an upgraded file is written back after the original is
+//! copied to `<name>.v<version>.bak`.
+//!
+//! New fields with a sensible default don't need a migration (serde fills
+//! them in); renamed fields and changed meanings do.
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version written by this build.
+pub const CURRENT_VERSION: u32 = 2;
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [end_punctuation_mode, typed_values];
+
+/// Upgrades `config` in place to [`CURRENT_VERSION`] and returns the version
+/// it had. A config from a newer build is left alone (with a warning):
+/// settings this build doesn't know are ignored.
+pub fn migrate(config: &mut Value) -> Result<u32> {
+    let object = config
+        .as_object_mut()
+        .context("Config file is not a JSON object")?;
+    let version = match object.get("config_version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .context("config_version is not a number")?,
+    };
+    if version > CURRENT_VERSION {
+        eprintln!(
+            "Warning: config version {} is newer than this build supports ({}); unknown settings are ignored",
+            version, CURRENT_VERSION
+        );
+        return Ok(version);
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    object.insert("config_version".into(), CURRENT_VERSION.into());
+    Ok(version)
+}
+
+/// Copies `path` to `<path>.v<version>.bak`, keeping an existing backup of
+/// the same version (that one is closer to the original).
+pub fn backup(path: &Path, version: u32) -> Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    let backup = path.with_file_name(name);
+    if !backup.exists() {
+        fs::copy(path, &backup)
+            .with_context(|| format!("Failed to back up config to {}", backup.display()))?;
+    }
+    Ok(backup)
+}
+
+/// 0 → 1: the separate `period` / `smart_punctuation` switches (and the
+/// short-lived `end_punct` spelling) become the `end_punctuation` mode.
+fn end_punctuation_mode(config: &mut Map<String, Value>) {
+    if let Some(mode) = config.remove("end_punct") {
+        config.entry("end_punctuation").or_insert(mode);
+    }
+    let period = config.remove("period").and_then(|v| v.as_bool());
+    let smart = config.remove("smart_punctuation").and_then(|v| v.as_bool());
+    if config.contains_key("end_punctuation") || (period.is_none() && smart.is_none()) {
+        return;
+    }
+    // Smart punctuation already added a period when it fit, so it wins.
+    let mode = if smart == Some(true) {
+        "smart"
+    } else if period == Some(true) {
+        "period"
+    } else {
+        "none"
+    };
+    config.insert("end_punctuation".into(), mode.into());
+}
+
+/// 1 → 2: settings that were free strings are now fixed values. Lower-case
+/// them, spell whisper.cpp model names (`base.en`) the way the config does
+/// (`base-en`), and store an empty device as "default".
+fn typed_values(config: &mut Map<String, Value>) {
+    fn normalize(value: &mut Value) {
+        if let Value::String(s) = value {
+            *s = s.trim().to_lowercase().replace(".en", "-en");
+        }
+    }
+
+    for key in ["end_punctuation", "realtime_delay", "local_model"] {
+        if let Some(value) = config.get_mut(key) {
+            normalize(value);
+        }
+    }
+    if let Some(Value::Array(rules)) = config.get_mut("app_rules") {
+        for rule in rules {
+            if let Some(value) = rule.get_mut("end_punctuation") {
+                normalize(value);
+            }
+        }
+    }
+    if let Some(Value::String(device)) = config.get_mut("device") {
+        if device.trim().is_empty() {
+            *device = "default".into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, EndPunctuation, InputDevice, LocalModel, RealtimeDelay};
+
+    /// Migrates a fixture from `tests/fixtures/config` and loads it.
+    fn load(fixture: &str) -> (u32, AppConfig) {
+        let mut value: Value = serde_json::from_str(fixture).unwrap();
+        let version = migrate(&mut value).unwrap();
+        assert_eq!(value["config_version"], CURRENT_VERSION);
+
+        // Migrating again changes nothing.
+        let mut again = value.clone();
+        assert_eq!(migrate(&mut again).unwrap(), CURRENT_VERSION);
+        assert_eq!(again, value);
+
+        (version, serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn period_switch() {
+        let (version, config) = load(include_str!("../tests/fixtures/config/v0-period.json"));
+        assert_eq!(version, 0);
+        assert_eq!(config.end_punctuation, EndPunctuation::Period);
+        assert_eq!(config.local_model, None);
+    }
+
+    #[test]
+    fn smart_punctuation_switch() {
+        let (_, config) = load(include_str!(
+            "../tests/fixtures/config/v0-smart-punctuation.json"
+        ));
+        assert_eq!(config.end_punctuation, EndPunctuation::Smart);
+        assert_eq!(config.local_model, Some(LocalModel::TinyEn));
+
+        let (_, config) = load(include_str!(
+            "../tests/fixtures/config/v0-no-punctuation.json"
+        ));
+        assert_eq!(config.end_punctuation, EndPunctuation::None);
+    }
+
+    #[test]
+    fn end_punct_spelling() {
+        let (_, config) = load(include_str!("../tests/fixtures/config/v0-end-punct.json"));
+        assert_eq!(config.end_punctuation, EndPunctuation::None);
+        assert!(config.realtime);
+        assert_eq!(config.realtime_delay, RealtimeDelay::High);
+    }
+
+    #[test]
+    fn string_settings() {
+        let (version, config) = load(include_str!(
+            "../tests/fixtures/config/v0-string-settings.json"
+        ));
+        assert_eq!(version, 0);
+        assert_eq!(config.device, InputDevice::Default);
+        assert_eq!(config.local_model, Some(LocalModel::BaseEn));
+        assert_eq!(config.end_punctuation, EndPunctuation::Period);
+        assert_eq!(config.realtime_delay, RealtimeDelay::Xhigh);
+        assert_eq!(
+            config.app_rules[0].end_punctuation,
+            Some(EndPunctuation::None)
+        );
+        assert_eq!(config.hooks.len(), 1);
+    }
+
+    #[test]
+    fn current_version() {
+        let (version, config) = load(include_str!("../tests/fixtures/config/v2.json"));
+        assert_eq!(version, CURRENT_VERSION);
+        assert_eq!(
+            config.device,
+            InputDevice::Named("USB Microphone".to_string())
+        );
+        assert_eq!(config.local_model, Some(LocalModel::LargeV3));
+    }
+
+    #[test]
+    fn newer_versions_are_left_alone() {
+        let mut value = serde_json::json!({ "config_version": 99, "end_punct": "none" });
+        assert_eq!(migrate(&mut value).unwrap(), 99);
+        assert_eq!(value["end_punct"], "none");
+    }
+
+    #[test]
+    fn backups_keep_the_first_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, "original").unwrap();
+        let backup_path = backup(&path, 0).unwrap();
+        assert_eq!(backup_path, dir.path().join("config.json.v0.bak"));
+        fs::write(&path, "changed").unwrap();
+        backup(&path, 0).unwrap();
+        assert_eq!(fs::read_to_string(backup_path).unwrap(), "original");
+    }
+}
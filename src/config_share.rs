@@ -0,0 +1,324 @@
+//! Sharing a setup between machines: exporting the config to a file,
+//! importing one, and the managed config an administrator can put in place.
+//!
+//! Exported files are config files without the API key, so a team can keep
+//! a standard setup (replacements, app rules, hooks, key and model choices)
+//! in one place. Importing either merges the file into the current settings
+//! or replaces them, and is refused if the result doesn't validate.
+//!
+//! The managed config is a read-only, partial config file. Every setting in
+//! it overrides the user's value whenever a config is loaded:
+//!
+//! - `$DESKTALK_MANAGED_CONFIG`, if set
+//! - Linux: `/etc/desk-talk/managed.json`
+//! - macOS: `/Library/Application Support/desk-talk/managed.json`
+//! - Windows: `%ProgramData%\desk-talk\managed.json`
+
+use crate::config::{self, AppConfig, ConfigError};
+use crate::config_migration;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MANAGED_ENV: &str = "DESKTALK_MANAGED_CONFIG";
+
+/// What importing does with the current settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Keep settings the file doesn't have and add its list entries to yours
+    #[default]
+    Merge,
+    /// Replace all settings with the file's, defaults for any it leaves out
+    /// (the API key is kept)
+    Overwrite,
+}
+
+/// Writes `config` to `path` in the config file format, without the API key.
+pub fn export(config: &AppConfig, path: &Path) -> Result<()> {
+    config
+        .write_file(path)
+        .with_context(|| format!("Failed to export config to {}", path.display()))
+}
+
+/// Reads the config file at `path` (any version) and applies it to
+/// `current`. The API key is never imported. Fails with one error per
+/// setting the file has an unknown value for; the result still has to be
+/// validated.
+pub fn import(
+    current: &AppConfig,
+    path: &Path,
+    mode: ImportMode,
+) -> Result<AppConfig, Vec<ConfigError>> {
+    let failed = |err: anyhow::Error| vec![ConfigError::new("", format!("{:#}", err))];
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))
+        .map_err(failed)?;
+    let mut imported: Value = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a config file", path.display()))
+        .map_err(failed)?;
+    config_migration::migrate(&mut imported).map_err(failed)?;
+    config::check_choices(&imported)?;
+    if let Some(object) = imported.as_object_mut() {
+        if object.remove("api_key").is_some() {
+            eprintln!("Warning: Ignoring the API key in {}", path.display());
+        }
+    }
+    let value = match mode {
+        ImportMode::Merge => {
+            let mut value = current.to_file_json().map_err(failed)?;
+            merge(&mut value, imported);
+            value
+        }
+        // Settings the file leaves out go back to their defaults.
+        ImportMode::Overwrite => {
+            let mut value = AppConfig::default().to_file_json().map_err(failed)?;
+            if let (Value::Object(value), Value::Object(imported)) = (&mut value, imported) {
+                value.extend(imported);
+            }
+            value
+        }
+    };
+    let mut config: AppConfig = serde_json::from_value(value)
+        .with_context(|| format!("{} has invalid settings", path.display()))
+        .map_err(failed)?;
+    config.api_key = current.api_key.clone();
+    Ok(config)
+}
+
+/// Names of the top-level settings that differ between `before` and `after`.
+pub fn changed_settings(before: &AppConfig, after: &AppConfig) -> Vec<String> {
+    let (Ok(Value::Object(before)), Ok(Value::Object(after))) =
+        (before.to_file_json(), after.to_file_json())
+    else {
+        return Vec::new();
+    };
+    let mut changed: Vec<String> = after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .chain(
+            before
+                .keys()
+                .filter(|key| !after.contains_key(*key))
+                .cloned(),
+        )
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Merges `overlay` into `base`: objects key by key, lists by adding the
+/// entries `base` doesn't have yet (after its own, so the user's app rules
+/// still match first), anything else replaced.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) => {
+            for value in overlay {
+                if !base.contains(&value) {
+                    base.push(value);
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Where the managed config is looked for.
+pub fn managed_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(MANAGED_ENV) {
+        return PathBuf::from(path);
+    }
+    #[cfg(target_os = "linux")]
+    let dir = PathBuf::from("/etc/desk-talk");
+    #[cfg(target_os = "macos")]
+    let dir = PathBuf::from("/Library/Application Support/desk-talk");
+    #[cfg(windows)]
+    let dir =
+        PathBuf::from(std::env::var_os("ProgramData").unwrap_or_else(|| r"C:\ProgramData".into()))
+            .join("desk-talk");
+    dir.join("managed.json")
+}
+
+/// The settings the managed config enforces, if there is one.
+pub fn managed_settings() -> Result<Option<Map<String, Value>>> {
+    let path = managed_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read managed config {}", path.display()))?;
+    let mut value: Value = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse managed config {}", path.display()))?;
+    config_migration::migrate(&mut value)
+        .with_context(|| format!("Failed to read managed config {}", path.display()))?;
+    let Value::Object(mut settings) = value else {
+        bail!("Managed config {} is not a JSON object", path.display());
+    };
+    settings.remove("config_version");
+    settings.remove("api_key");
+    Ok(Some(settings))
+}
+
+/// Overrides `config` with the managed settings. A managed config that
+/// can't be read or doesn't fit the config is an error rather than ignored,
+/// since it is there to enforce something.
+pub(crate) fn apply_managed(config: &mut AppConfig) -> Result<()> {
+    let Some(settings) = managed_settings()? else {
+        return Ok(());
+    };
+    let api_key = config.api_key.take();
+    let mut value = config.to_file_json()?;
+    if let Value::Object(object) = &mut value {
+        object.extend(settings);
+    }
+    *config = serde_json::from_value(value).with_context(|| {
+        format!(
+            "Managed config {} has invalid settings",
+            managed_path().display()
+        )
+    })?;
+    config.api_key = api_key;
+    Ok(())
+}
+
+/// Puts back the user's own values (from the file at `path`, or the
+/// defaults) for the managed settings in `value`, which is about to be
+/// saved there.
+pub(crate) fn keep_user_values(value: &mut Value, path: &Path) -> Result<()> {
+    let Some(settings) = managed_settings()? else {
+        return Ok(());
+    };
+    let saved = fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+        .and_then(|mut saved| config_migration::migrate(&mut saved).ok().map(|_| saved));
+    restore(value, settings.keys(), saved.as_ref())
+}
+
+fn restore<'a>(
+    value: &mut Value,
+    keys: impl IntoIterator<Item = &'a String>,
+    saved: Option<&Value>,
+) -> Result<()> {
+    let defaults = AppConfig::default().to_file_json()?;
+    let Value::Object(object) = value else {
+        return Ok(());
+    };
+    for key in keys {
+        let user_value = saved
+            .and_then(|saved| saved.get(key))
+            .or_else(|| defaults.get(key));
+        match user_value {
+            Some(user_value) => {
+                object.insert(key.clone(), user_value.clone());
+            }
+            None => {
+                object.remove(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merging_adds_list_entries_and_replaces_values() {
+        let mut base = json!({
+            "parallel": 1,
+            "replacements": [{ "from": "new line", "to": "\n" }],
+            "nested": { "a": 1, "b": 2 },
+        });
+        merge(
+            &mut base,
+            json!({
+                "parallel": 3,
+                "replacements": [
+                    { "from": "new line", "to": "\n" },
+                    { "from": "full stop", "to": "." },
+                ],
+                "nested": { "b": 3 },
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "parallel": 3,
+                "replacements": [
+                    { "from": "new line", "to": "\n" },
+                    { "from": "full stop", "to": "." },
+                ],
+                "nested": { "a": 1, "b": 3 },
+            })
+        );
+    }
+
+    #[test]
+    fn import_keeps_the_api_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("team.json");
+        fs::write(
+            &path,
+            r#"{ "parallel": 3, "end_punct": "period", "api_key": "sk-team" }"#,
+        )
+        .unwrap();
+        let current = AppConfig {
+            api_key: Some("sk-mine".into()),
+            dark_mode: true,
+            ..AppConfig::default()
+        };
+
+        let merged = import(&current, &path, ImportMode::Merge).unwrap();
+        assert_eq!(merged.api_key.as_deref(), Some("sk-mine"));
+        assert_eq!(merged.parallel, 3);
+        assert!(merged.dark_mode);
+        assert_eq!(
+            changed_settings(&current, &merged),
+            ["end_punctuation", "parallel"]
+        );
+
+        let replaced = import(&current, &path, ImportMode::Overwrite).unwrap();
+        assert_eq!(replaced.api_key.as_deref(), Some("sk-mine"));
+        assert_eq!(replaced.parallel, 3);
+        assert!(!replaced.dark_mode);
+    }
+
+    #[test]
+    fn import_rejects_unknown_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("team.json");
+        fs::write(&path, r#"{ "parallel": 3, "budget_action": "panic" }"#).unwrap();
+        let errors = import(&AppConfig::default(), &path, ImportMode::Merge).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "budget_action");
+    }
+
+    #[test]
+    fn saving_keeps_the_users_value_of_managed_settings() {
+        let mut value = json!({ "parallel": 5, "cap_first": true, "output": "type" });
+        let saved = json!({ "parallel": 2 });
+        let keys = ["parallel".to_string(), "output".to_string()];
+        restore(&mut value, &keys, Some(&saved)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "parallel": 2, "cap_first": true, "output": "paste" })
+        );
+    }
+}
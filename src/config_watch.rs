@@ -0,0 +1,131 @@
+//! Picks up edits to the config file made outside the app (a text editor,
+//! dotfile sync, ...) so they apply without a restart.
+//!
+//! The file of the active profile is polled for a new modification time;
+//! nothing platform specific is needed and a missed event can't leave the
+//! settings stale. A changed file is loaded and validated like at startup
+//! and, if it differs from the settings in use, replaces them through
+//! [`AppState::set_config`], which a running engine applies before its next
+//! utterance. Saves from the settings window change the file too, but load
+//! back identical and are ignored.
+
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The file being watched and when it was last seen modified.
+struct Watched {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl Watched {
+    fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    /// Whether the file changed since the last call (or since `new`).
+    fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        // A deleted file keeps the settings in use.
+        modified.is_some()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|meta| meta.modified()).ok()
+}
+
+/// Watches the active profile's config file on a background thread.
+pub fn spawn(state: AppState) {
+    thread::Builder::new()
+        .name("config-watch".into())
+        .spawn(move || {
+            let mut profile = state.profile();
+            let mut watched = AppConfig::get_config_path(profile.as_deref())
+                .ok()
+                .map(Watched::new);
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                // Switching profiles loads the new file already; start
+                // watching it from here.
+                let current = state.profile();
+                if current != profile {
+                    profile = current;
+                    watched = AppConfig::get_config_path(profile.as_deref())
+                        .ok()
+                        .map(Watched::new);
+                    continue;
+                }
+                if let Some(watched) = &mut watched {
+                    if watched.changed() {
+                        reload(&state, profile.as_deref(), &watched.path);
+                    }
+                }
+            }
+        })
+        .expect("Failed to spawn config watcher thread");
+}
+
+fn reload(state: &AppState, profile: Option<&str>, path: &Path) {
+    let config = match AppConfig::load_profile(profile) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Ignoring changed {}: {:#}", path.display(), err);
+            return;
+        }
+    };
+    if let Err(errors) = config.validate() {
+        for error in &errors {
+            eprintln!("Ignoring changed {}: {}", path.display(), error);
+        }
+        state.report_error(format!(
+            "{} has invalid settings; keeping the current ones",
+            path.display()
+        ));
+        return;
+    }
+    let same =
+        serde_json::to_value(&config).ok() == serde_json::to_value(&*state.config.read()).ok();
+    if !same {
+        println!("{} changed, applying new settings", path.display());
+        state.set_config(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    #[test]
+    fn notices_new_modification_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, "{}").unwrap();
+        let mut watched = Watched::new(path.clone());
+        assert!(!watched.changed());
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(watched.changed());
+        assert!(!watched.changed());
+
+        fs::remove_file(&path).unwrap();
+        assert!(!watched.changed());
+    }
+}
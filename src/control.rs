@@ -0,0 +1,137 @@
+//! Starting, stopping and steering the transcription engine.
+//!
+//! The tray, the settings window and the control socket all go through one
+//! [`EngineController`], so they agree on whether the engine is running and
+//! every change is announced on the [`EventBus`](crate::events::EventBus).
+
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use crate::events::EngineEvent;
+use crate::transcription_engine::TranscriptionEngine;
+use crate::{config_watch, hooks, ipc};
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use rdev::{Event, EventType};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+#[derive(Clone)]
+pub struct EngineController {
+    state: AppState,
+    engine: Arc<Mutex<Option<TranscriptionEngine>>>,
+}
+
+impl EngineController {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            engine: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Starts what the config enables around the engine: hooks, the control
+    /// socket and (on Linux) the D-Bus service, plus the config file watcher.
+    pub fn spawn_services(&self) {
+        let config = self.state.config.read().clone();
+        hooks::spawn(self.state.clone());
+        config_watch::spawn(self.state.clone());
+        if config.control_socket {
+            ipc::spawn(self.clone());
+        }
+        #[cfg(target_os = "linux")]
+        if config.dbus_service {
+            crate::dbus::spawn(self.clone());
+        }
+    }
+
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.engine.lock().is_some()
+    }
+
+    /// Starts the engine with the current config. Does nothing if it's
+    /// already running.
+    pub fn start(&self) -> Result<()> {
+        let mut engine_slot = self.engine.lock();
+        if engine_slot.is_some() {
+            println!("Engine already running");
+            return Ok(());
+        }
+        let engine = TranscriptionEngine::new(self.state.clone());
+        if let Err(err) = engine.start() {
+            self.state
+                .report_error(format!("Failed to start engine: {}", err));
+            return Err(err);
+        }
+        *engine_slot = Some(engine);
+        drop(engine_slot);
+        self.state.events.emit(EngineEvent::EngineStarted);
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        let Some(engine) = self.engine.lock().take() else {
+            return;
+        };
+        engine.stop();
+        self.state.set_recording(false);
+        self.state.events.emit(EngineEvent::EngineStopped);
+    }
+
+    /// Loads another config profile (`None` = the default config) and
+    /// restarts the engine with it if it was running.
+    pub fn switch_profile(&self, profile: Option<String>) -> Result<()> {
+        let config = AppConfig::load_profile(profile.as_deref())?;
+        let was_running = self.is_running();
+        self.stop();
+        self.state.set_profile(profile.clone());
+        self.state.set_config(config);
+        println!(
+            "Switched to profile {}",
+            profile.as_deref().unwrap_or("(default)")
+        );
+        self.state
+            .events
+            .emit(EngineEvent::ProfileChanged { profile });
+        if was_running {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Starts or stops recording as if the PTT key went down or up.
+    pub fn set_recording(&self, recording: bool) -> Result<()> {
+        let sender = self
+            .state
+            .event_sender()
+            .ok_or_else(|| anyhow!("Engine is not running"))?;
+        let key = self
+            .state
+            .config
+            .read()
+            .get_ptt_key()
+            .ok_or_else(|| anyhow!("No push-to-talk key configured"))?;
+        let event_type = if recording {
+            EventType::KeyPress(key)
+        } else {
+            EventType::KeyRelease(key)
+        };
+        sender
+            .send(Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type,
+            })
+            .map_err(|_| anyhow!("Engine is not running"))
+    }
+
+    /// Flips recording; returns whether it is now on.
+    pub fn toggle_recording(&self) -> Result<bool> {
+        let recording = !self.state.is_recording();
+        self.set_recording(recording)?;
+        Ok(recording)
+    }
+}
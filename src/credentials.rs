@@ -0,0 +1,307 @@
+//! API keys, kept in the system keyring under names so one machine can hold
+//! several (a personal and a work account, or an OpenAI-compatible proxy).
+//!
+//! Each profile picks a key with its `credential` setting; without one it
+//! uses the `default` key, which is the keyring entry older versions wrote.
+//! The keyring can't list its entries, so the names (plus the endpoint and
+//! when each key was added or last replaced) are kept in `credentials.json`
+//! in the config directory. The keys themselves never touch that file.
+
+use crate::config::AppConfig;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The key used by profiles that don't name one.
+pub const DEFAULT_CREDENTIAL: &str = "default";
+
+const KEYRING_SERVICE: &str = "desk-talk";
+/// Keyring entry of the default key (and prefix of the named ones).
+const KEYRING_USER: &str = "openai-api-key";
+
+/// A stored API key, without the key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Credential {
+    pub name: String,
+    /// Base URL of an OpenAI-compatible API to use with this key; the OpenAI
+    /// API if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_base: Option<String>,
+    /// Unix timestamps. `None` for a default key saved by an older version.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub rotated_at: Option<i64>,
+}
+
+fn index_path() -> Result<PathBuf> {
+    Ok(AppConfig::get_config_dir()?.join("credentials.json"))
+}
+
+fn read_index() -> Result<Vec<Credential>> {
+    let path = index_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(&path).context("Failed to read credentials.json")?;
+    serde_json::from_str(&contents).context("Failed to parse credentials.json")
+}
+
+fn write_index(credentials: &[Credential]) -> Result<()> {
+    let contents = serde_json::to_string_pretty(credentials)?;
+    fs::write(index_path()?, contents).context("Failed to write credentials.json")
+}
+
+pub(crate) fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!(
+            "Invalid credential name \"{}\" (use letters, digits, - and _)",
+            name
+        );
+    }
+    Ok(())
+}
+
+fn entry(name: &str) -> Result<keyring::Entry> {
+    check_name(name)?;
+    let user = if name == DEFAULT_CREDENTIAL {
+        KEYRING_USER.to_string()
+    } else {
+        format!("{}/{}", KEYRING_USER, name)
+    };
+    keyring::Entry::new(KEYRING_SERVICE, &user).context("Failed to create keyring entry")
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// The stored keys, sorted by name. The default key is listed if it exists,
+/// even if an older version saved it.
+pub fn list() -> Result<Vec<Credential>> {
+    let mut credentials = read_index()?;
+    let has_default = credentials.iter().any(|c| c.name == DEFAULT_CREDENTIAL);
+    if !has_default && load_key(DEFAULT_CREDENTIAL).is_ok() {
+        credentials.push(Credential {
+            name: DEFAULT_CREDENTIAL.to_string(),
+            api_base: None,
+            created_at: None,
+            rotated_at: None,
+        });
+    }
+    credentials.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(credentials)
+}
+
+/// Looks up one stored key's details.
+pub fn get(name: &str) -> Result<Option<Credential>> {
+    Ok(list()?.into_iter().find(|c| c.name == name))
+}
+
+/// The endpoint stored with the key `name`, if it has one.
+pub fn api_base(name: &str) -> Result<Option<String>> {
+    Ok(read_index()?
+        .into_iter()
+        .find(|c| c.name == name)
+        .and_then(|c| c.api_base))
+}
+
+/// The key stored as `name`. Without a working keyring the default key is
+/// read from the `.env` file next to the executable.
+pub fn load_key(name: &str) -> Result<String> {
+    let stored = entry(name)?.get_password();
+    match stored {
+        Ok(key) => Ok(key),
+        Err(keyring::Error::NoEntry) => bail!("No API key named \"{}\"", name),
+        Err(_) if name == DEFAULT_CREDENTIAL => load_key_from_env(),
+        Err(err) => Err(err).context("Failed to read API key from keyring"),
+    }
+}
+
+/// Stores `key` as `name`, adding the name or, if it had a different key,
+/// recording the rotation. `api_base` replaces the endpoint if given.
+pub fn save_key(name: &str, key: &str, api_base: Option<String>) -> Result<()> {
+    let previous = entry(name)?.get_password().ok();
+    entry(name)?
+        .set_password(key)
+        .context("Failed to save API key to keyring")?;
+
+    let mut credentials = read_index()?;
+    match credentials.iter_mut().find(|c| c.name == name) {
+        Some(credential) => {
+            if previous.as_deref() != Some(key) {
+                credential.rotated_at = Some(now());
+            }
+            if api_base.is_some() {
+                credential.api_base = api_base;
+            }
+        }
+        None => credentials.push(Credential {
+            name: name.to_string(),
+            api_base,
+            created_at: Some(now()),
+            rotated_at: None,
+        }),
+    }
+    write_index(&credentials)
+}
+
+/// Deletes the key stored as `name` from the keyring, and the default key
+/// from `.env` too.
+pub fn remove(name: &str) -> Result<()> {
+    if name == DEFAULT_CREDENTIAL {
+        scrub_env_file()?;
+    }
+    match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(err) => return Err(err).context("Failed to delete API key from keyring"),
+    }
+    let mut credentials = read_index()?;
+    let before = credentials.len();
+    credentials.retain(|c| c.name != name);
+    if credentials.len() == before && name != DEFAULT_CREDENTIAL {
+        bail!("No API key named \"{}\"", name);
+    }
+    write_index(&credentials)
+}
+
+/// Environment variables that can hold an API key. DeskTalk never sets them,
+/// but it may have been started with one.
+const KEY_VARIABLES: &[&str] = &["OPENAI_API_KEY"];
+
+/// A command for a child process (ffmpeg, hooks, clipboard and window
+/// helpers) without any API key in its environment.
+pub(crate) fn child_command(program: impl AsRef<OsStr>) -> Command {
+    let mut command = Command::new(program);
+    for name in KEY_VARIABLES {
+        command.env_remove(name);
+    }
+    command
+}
+
+fn env_path() -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let exe_dir = exe.parent().context("Failed to get exe directory")?;
+    Ok(exe_dir.join(".env"))
+}
+
+/// Writes `key` to a plaintext `.env` next to the executable, for setups
+/// without a keyring (`write_env_file`).
+pub fn save_key_to_env(key: &str) -> Result<()> {
+    fs::write(env_path()?, format!("OPENAI_API_KEY={}", key)).context("Failed to write .env file")
+}
+
+fn key_in_env(contents: &str) -> Option<&str> {
+    contents
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("OPENAI_API_KEY="))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+fn load_key_from_env() -> Result<String> {
+    let env_path = env_path()?;
+    if env_path.exists() {
+        let contents = fs::read_to_string(&env_path)?;
+        if let Some(key) = key_in_env(&contents) {
+            return Ok(key.to_string());
+        }
+    }
+    bail!("No API key found in keyring or .env file")
+}
+
+/// The key in a `.env` file in the working directory, which the CLI reads as
+/// a fallback like it reads `OPENAI_API_KEY`. It isn't put in the environment.
+pub fn key_in_working_dir() -> Option<String> {
+    let contents = fs::read_to_string(".env").ok()?;
+    key_in_env(&contents).map(str::to_string)
+}
+
+/// `contents` without the key, or `None` if nothing else is left.
+fn without_key(contents: &str) -> Option<String> {
+    let rest: Vec<&str> = contents
+        .lines()
+        .filter(|line| !line.trim().starts_with("OPENAI_API_KEY="))
+        .collect();
+    if rest.iter().all(|line| line.trim().is_empty()) {
+        None
+    } else {
+        Some(rest.join("\n") + "\n")
+    }
+}
+
+/// Takes the key out of `.env`, deleting the file if that was all it held.
+fn scrub_env_file() -> Result<()> {
+    let path = env_path()?;
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    if key_in_env(&contents).is_none() {
+        return Ok(());
+    }
+    match without_key(&contents) {
+        Some(rest) => fs::write(&path, rest),
+        None => fs::remove_file(&path),
+    }
+    .context("Failed to remove the API key from the .env file")
+}
+
+/// Moves a default key that older versions left in `.env` into the keyring.
+/// The plaintext copy is removed unless `keep` (`write_env_file`) is set; it
+/// stays if the keyring doesn't work, since it's the only copy then.
+pub fn import_env_file(keep: bool) -> Result<()> {
+    let path = env_path()?;
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let Some(key) = key_in_env(&contents) else {
+        return Ok(());
+    };
+    match entry(DEFAULT_CREDENTIAL)?.get_password() {
+        Ok(_) => {}
+        Err(keyring::Error::NoEntry) => {
+            save_key(DEFAULT_CREDENTIAL, key, None)?;
+            println!("Moved the API key from {} to the keyring", path.display());
+        }
+        Err(_) => return Ok(()),
+    }
+    if !keep {
+        scrub_env_file()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_checked() {
+        assert!(check_name("work-2").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("../default").is_err());
+    }
+
+    #[test]
+    fn scrubbing_env_keeps_other_variables() {
+        let contents = "OPENAI_API_KEY=sk-test\nOTHER=1\n";
+        assert_eq!(key_in_env(contents), Some("sk-test"));
+        assert_eq!(without_key(contents).as_deref(), Some("OTHER=1\n"));
+        assert_eq!(without_key("OPENAI_API_KEY=sk-test"), None);
+    }
+
+    #[test]
+    fn index_entries_without_dates_load() {
+        let credentials: Vec<Credential> =
+            serde_json::from_str(r#"[{ "name": "default" }]"#).unwrap();
+        assert_eq!(credentials[0].created_at, None);
+        assert_eq!(credentials[0].api_base, None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Running without a window or tray (`desk-talk-cli daemon`), e.g. on a
+//! server or under a minimal window manager.
+//!
+//! The daemon is the engine plus the global key listener, configured from the
+//! config file like the desktop app. It is steered through the control socket,
+//! D-Bus (Linux) and these signals:
+//!
+//! | Signal | |
+//! |--------|-|
+//! | `SIGUSR1` | Toggle recording |
+//! | `SIGUSR2` | Start or stop the engine |
+//! | `SIGHUP` | Re-read the config file and restart the engine |
+//! | `SIGTERM`, `SIGINT` | Stop the engine and exit |
+//!
+//! Unless run in the foreground, the daemon re-starts itself in the
+//! background with its output going to a log file.
+
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use crate::control::EngineController;
+use crate::listener;
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Set on the background process so it knows not to detach again.
+const DETACHED_ENV: &str = "DESKTALK_DAEMON_DETACHED";
+
+/// Whether this process is a daemon started by [`detach`].
+pub fn is_detached() -> bool {
+    std::env::var_os(DETACHED_ENV).is_some()
+}
+
+/// `desk-talk.log` in the config directory.
+pub fn default_log_file() -> Result<PathBuf> {
+    Ok(AppConfig::get_config_dir()?.join("desk-talk.log"))
+}
+
+/// Starts this executable again, with the same arguments, as a background
+/// process whose output is appended to `log_file`. Returns its PID.
+pub fn detach(log_file: &Path) -> Result<u32> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("Failed to open log file {}", log_file.display()))?;
+    let exe = std::env::current_exe().context("Failed to locate the executable")?;
+    let mut command = Command::new(exe);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(DETACHED_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // Leave the terminal's process group so closing it doesn't take the
+    // daemon with it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+    let child = command.spawn().context("Failed to start the daemon")?;
+    Ok(child.id())
+}
+
+/// Runs the engine and key listener until `SIGTERM`/`SIGINT` (Ctrl+C on
+/// Windows). If the engine fails to start, the daemon keeps running so it can
+/// be started later over the control socket, D-Bus or `SIGUSR2`.
+pub fn run(config: AppConfig, profile: Option<String>) -> Result<()> {
+    println!(
+        "desk-talk daemon started at {} (pid {})",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        std::process::id()
+    );
+    let app_state = AppState::new(config);
+    app_state.set_profile(profile);
+    let controller = EngineController::new(app_state.clone());
+    controller.spawn_services();
+    if let Err(err) = controller.start() {
+        eprintln!("Engine not started: {:?}", err);
+    }
+    // Runs for the life of the process; a failure (e.g. no X display) is
+    // logged and the other controls keep working.
+    let _listener = listener::start_global_event_listener(app_state);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(handle_signals(&controller))?;
+
+    controller.stop();
+    println!("desk-talk daemon stopped");
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn handle_signals(controller: &EngineController) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut usr1 = signal(SignalKind::user_defined1())?;
+    let mut usr2 = signal(SignalKind::user_defined2())?;
+    loop {
+        tokio::select! {
+            _ = terminate.recv() => break,
+            _ = interrupt.recv() => break,
+            _ = hangup.recv() => {
+                println!("SIGHUP: reloading config");
+                let profile = controller.state().profile();
+                if let Err(err) = controller.switch_profile(profile) {
+                    eprintln!("Failed to reload config: {:?}", err);
+                }
+            }
+            _ = usr1.recv() => {
+                if let Err(err) = controller.toggle_recording() {
+                    eprintln!("SIGUSR1: {}", err);
+                }
+            }
+            _ = usr2.recv() => {
+                if controller.is_running() {
+                    controller.stop();
+                } else if let Err(err) = controller.start() {
+                    eprintln!("SIGUSR2: {:?}", err);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn handle_signals(_controller: &EngineController) -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! `org.desktalk` D-Bus service, so desktop shortcuts (GNOME/KDE custom
+//! shortcuts, Wayland compositors where the global key listener can't see
+//! keys) and scripts can drive the engine:
+//!
+//! ```text
+//! busctl --user call org.desktalk /org/desktalk org.desktalk.Engine ToggleRecording
+//! ```
+//!
+//! Methods go through the same [`EngineController`] as the tray and the
+//! control socket; engine events are re-broadcast as signals.
+
+use crate::control::EngineController;
+use crate::events::EngineEvent;
+use crate::statistics;
+use std::thread;
+use zbus::blocking::{connection, Connection};
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+const NAME: &str = "org.desktalk";
+const PATH: &str = "/org/desktalk";
+const INTERFACE: &str = "org.desktalk.Engine";
+
+struct Engine {
+    controller: EngineController,
+}
+
+fn failed(err: anyhow::Error) -> fdo::Error {
+    fdo::Error::Failed(err.to_string())
+}
+
+#[zbus::interface(name = "org.desktalk.Engine")]
+impl Engine {
+    fn start_engine(&self) -> fdo::Result<()> {
+        self.controller.start().map_err(failed)
+    }
+
+    fn stop_engine(&self) {
+        self.controller.stop();
+    }
+
+    fn is_running(&self) -> bool {
+        self.controller.is_running()
+    }
+
+    fn start_recording(&self) -> fdo::Result<()> {
+        self.controller.set_recording(true).map_err(failed)
+    }
+
+    fn stop_recording(&self) -> fdo::Result<()> {
+        self.controller.set_recording(false).map_err(failed)
+    }
+
+    /// Returns whether recording is now on.
+    fn toggle_recording(&self) -> fdo::Result<bool> {
+        self.controller.toggle_recording().map_err(failed)
+    }
+
+    /// The statistics shown in the settings window, as JSON.
+    fn get_statistics(&self) -> fdo::Result<String> {
+        let stats = statistics::combined_statistics(self.controller.state());
+        serde_json::to_string(&stats).map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+
+    /// Text of the last transcription, empty if there was none.
+    fn last_transcript(&self) -> String {
+        self.controller
+            .state()
+            .last_transcription()
+            .map(|record| record.text)
+            .unwrap_or_default()
+    }
+
+    // Emitted by the thread started in `spawn`; declared here so they show
+    // up in introspection.
+    #[zbus(signal)]
+    async fn recording_started(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn recording_stopped(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn transcription_completed(
+        emitter: &SignalEmitter<'_>,
+        text: &str,
+        duration_secs: f64,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn error(emitter: &SignalEmitter<'_>, message: &str) -> zbus::Result<()>;
+}
+
+/// Registers the service on the session bus and forwards engine events as
+/// signals. Failing to connect (no session bus, name taken) is logged and
+/// otherwise ignored.
+pub fn spawn(controller: EngineController) {
+    let events = controller.state().events.subscribe();
+    let connection = connection::Builder::session()
+        .and_then(|builder| builder.name(NAME))
+        .and_then(|builder| builder.serve_at(PATH, Engine { controller }))
+        .and_then(|builder| builder.build());
+    let connection = match connection {
+        Ok(connection) => connection,
+        Err(err) => {
+            eprintln!("D-Bus service not available: {}", err);
+            return;
+        }
+    };
+    println!("D-Bus service {} registered", NAME);
+
+    thread::Builder::new()
+        .name("dbus-signals".into())
+        .spawn(move || {
+            for event in events.iter() {
+                let sent = match event {
+                    EngineEvent::RecordingStarted => emit(&connection, "RecordingStarted", &()),
+                    EngineEvent::RecordingStopped => emit(&connection, "RecordingStopped", &()),
+                    EngineEvent::TranscriptionCompleted { transcription } => emit(
+                        &connection,
+                        "TranscriptionCompleted",
+                        &(transcription.text, transcription.duration_secs),
+                    ),
+                    EngineEvent::Error { message } => emit(&connection, "Error", &(message,)),
+                    _ => Ok(()),
+                };
+                if let Err(err) = sent {
+                    eprintln!("Failed to emit D-Bus signal: {}", err);
+                }
+            }
+        })
+        .expect("Failed to spawn D-Bus signal thread");
+}
+
+fn emit<B>(connection: &Connection, signal: &str, body: &B) -> zbus::Result<()>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    connection.emit_signal(None::<&str>, PATH, INTERFACE, signal, body)
+}
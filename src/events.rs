@@ -0,0 +1,51 @@
+//! Engine events, for anything that follows the engine from outside: control
+//! socket subscribers and the tray.
+
+use crate::app_state::TranscriptionRecord;
+use flume::{Receiver, Sender};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EngineEvent {
+    EngineStarted,
+    EngineStopped,
+    RecordingStarted,
+    RecordingStopped,
+    TranscriptionCompleted {
+        transcription: TranscriptionRecord,
+    },
+    /// `profile` is `None` for the default profile.
+    ProfileChanged {
+        profile: Option<String>,
+    },
+    /// The settings changed (saved from the GUI, or the config file was
+    /// edited). A running engine applies them before the next utterance.
+    ConfigChanged,
+    Error {
+        message: String,
+    },
+}
+
+/// Fans events out to every subscriber. Subscribers that went away are
+/// dropped on the next emit.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<EngineEvent>>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Receiver<EngineEvent> {
+        let (tx, rx) = flume::unbounded();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    pub fn emit(&self, event: EngineEvent) {
+        self.subscribers
+            .lock()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,377 @@
+//! Transcribing existing audio files (the CLI's `transcribe` subcommand),
+//! e.g. meeting recordings.
+//!
+//! Files are decoded to 16 kHz mono and cut into chunks of about
+//! `chunk_secs`, each ending at the quietest moment near its end so words
+//! aren't cut in half. Every chunk goes through the same backend as live
+//! dictation (OpenAI with racing, or a local model), which keeps uploads under
+//! the API size limit and gives each piece of text a timestamp for SRT/VTT.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use rodio::source::UniformSourceIterator;
+use rodio::Decoder;
+use serde::Serialize;
+use serde_json::json;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 16_000;
+/// Extensions picked up when a directory is given.
+const AUDIO_EXTENSIONS: [&str; 3] = ["wav", "mp3", "flac"];
+/// How far back from a chunk's nominal end to look for a pause.
+const PAUSE_SEARCH: Duration = Duration::from_secs(5);
+/// Window over which loudness is measured when looking for a pause.
+const PAUSE_WINDOW: Duration = Duration::from_millis(100);
+/// Whisper can't handle less than 0.1 s; like live dictation, skip anything
+/// shorter than 0.2 s.
+const MIN_CHUNK: Duration = Duration::from_millis(200);
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Srt,
+    Vtt,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Json => "json",
+            OutputFormat::Srt => "srt",
+            OutputFormat::Vtt => "vtt",
+        }
+    }
+}
+
+/// A piece of transcribed text and where it is in the file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Segment {
+    #[serde(rename = "start_secs", serialize_with = "as_secs")]
+    pub start: Duration,
+    #[serde(rename = "end_secs", serialize_with = "as_secs")]
+    pub end: Duration,
+    pub text: String,
+}
+
+fn as_secs<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+#[derive(Debug, Clone)]
+pub struct FileTranscript {
+    pub duration: Duration,
+    pub segments: Vec<Segment>,
+}
+
+impl FileTranscript {
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| segment.text.trim())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn render(&self, format: OutputFormat, path: &Path) -> String {
+        match format {
+            OutputFormat::Text => format!("{}\n", self.text()),
+            OutputFormat::Json => {
+                let value = json!({
+                    "file": path.display().to_string(),
+                    "duration_secs": self.duration.as_secs_f64(),
+                    "text": self.text(),
+                    "segments": self.segments,
+                });
+                format!("{}\n", value)
+            }
+            OutputFormat::Srt => {
+                let mut out = String::new();
+                for (i, segment) in self.segments.iter().enumerate() {
+                    out.push_str(&format!(
+                        "{}\n{} --> {}\n{}\n\n",
+                        i + 1,
+                        timestamp(segment.start, ','),
+                        timestamp(segment.end, ','),
+                        segment.text.trim()
+                    ));
+                }
+                out
+            }
+            OutputFormat::Vtt => {
+                let mut out = String::from("WEBVTT\n\n");
+                for segment in &self.segments {
+                    out.push_str(&format!(
+                        "{} --> {}\n{}\n\n",
+                        timestamp(segment.start, '.'),
+                        timestamp(segment.end, '.'),
+                        segment.text.trim().replace("-->", "->")
+                    ));
+                }
+                out
+            }
+        }
+    }
+}
+
+/// `HH:MM:SS,mmm` (SRT) or `HH:MM:SS.mmm` (VTT).
+fn timestamp(at: Duration, decimal: char) -> String {
+    let ms = at.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        decimal,
+        ms % 1000
+    )
+}
+
+/// Expands directories (recursively) to the audio files in them, sorted.
+/// Files given directly must have a supported extension too.
+pub fn collect_inputs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    fn is_audio(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+    }
+
+    fn walk(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+        let mut paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                walk(&path, out)?;
+            } else if is_audio(&path) {
+                out.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut inputs = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, &mut inputs)?;
+        } else if !path.exists() {
+            bail!("{} does not exist", path.display());
+        } else if is_audio(path) {
+            inputs.push(path.clone());
+        } else {
+            bail!(
+                "{} is not a supported audio file ({})",
+                path.display(),
+                AUDIO_EXTENSIONS.join(", ")
+            );
+        }
+    }
+    Ok(inputs)
+}
+
+/// Where the transcript of each of `inputs` goes in `dir`: the file name with
+/// `extension`. Fails if two inputs would write the same file, e.g.
+/// `a/talk.mp3` and `b/talk.wav`.
+pub fn output_paths(inputs: &[PathBuf], dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut outputs: Vec<PathBuf> = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut name = input.file_stem().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(extension);
+        let output = dir.join(name);
+        if let Some(i) = outputs.iter().position(|other| *other == output) {
+            bail!(
+                "{} and {} would both be written to {}",
+                inputs[i].display(),
+                input.display(),
+                output.display()
+            );
+        }
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
+
+/// Transcribes one file, chunk by chunk. `transcribe` gets the path of a WAV
+/// chunk and its length in seconds.
+pub fn transcribe_file<F>(path: &Path, chunk_secs: u64, mut transcribe: F) -> Result<FileTranscript>
+where
+    F: FnMut(&Path, f64) -> Result<String>,
+{
+    let samples = decode(path)?;
+    let tmp_dir = tempfile::tempdir().context("Failed to create temp dir")?;
+    let chunk_path = tmp_dir.path().join("chunk.wav");
+
+    let mut segments = Vec::new();
+    let chunks = chunk_ranges(&samples, samples_in(Duration::from_secs(chunk_secs.max(1))));
+    let count = chunks.len();
+    for (i, range) in chunks.into_iter().enumerate() {
+        let start = duration_of(range.start);
+        let end = duration_of(range.end);
+        if end - start < MIN_CHUNK {
+            continue;
+        }
+        eprintln!(
+            "{}: chunk {}/{} ({} - {})",
+            path.display(),
+            i + 1,
+            count,
+            timestamp(start, '.'),
+            timestamp(end, '.')
+        );
+        write_wav(&chunk_path, &samples[range])?;
+        let text = transcribe(&chunk_path, (end - start).as_secs_f64())?;
+        let text = text.trim();
+        if !text.is_empty() {
+            segments.push(Segment {
+                start,
+                end,
+                text: text.to_string(),
+            });
+        }
+    }
+    Ok(FileTranscript {
+        duration: duration_of(samples.len()),
+        segments,
+    })
+}
+
+/// Decodes any format rodio understands to 16 kHz mono.
+fn decode(path: &Path) -> Result<Vec<i16>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let source = Decoder::new(BufReader::new(file))
+        .with_context(|| format!("Failed to decode {}", path.display()))?;
+    Ok(UniformSourceIterator::<_, i16>::new(source, 1, SAMPLE_RATE).collect())
+}
+
+fn write_wav(path: &Path, samples: &[i16]) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn samples_in(duration: Duration) -> usize {
+    (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize
+}
+
+fn duration_of(samples: usize) -> Duration {
+    Duration::from_secs_f64(samples as f64 / SAMPLE_RATE as f64)
+}
+
+/// Splits `samples` into chunks of at most `chunk_len` samples. Each chunk
+/// except the last ends at the quietest [`PAUSE_WINDOW`] within the last
+/// [`PAUSE_SEARCH`] before its nominal end.
+fn chunk_ranges(samples: &[i16], chunk_len: usize) -> Vec<Range<usize>> {
+    let window = samples_in(PAUSE_WINDOW).max(1);
+    let search = samples_in(PAUSE_SEARCH).min(chunk_len / 2);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < samples.len() {
+        let nominal_end = start + chunk_len;
+        if nominal_end >= samples.len() {
+            ranges.push(start..samples.len());
+            break;
+        }
+        let mut end = nominal_end;
+        let mut quietest = u64::MAX;
+        let mut at = nominal_end - search;
+        while at + window <= nominal_end {
+            let energy: u64 = samples[at..at + window]
+                .iter()
+                .map(|&s| (s as i64 * s as i64) as u64)
+                .sum();
+            if energy < quietest {
+                quietest = energy;
+                // Cut in the middle of the pause.
+                end = at + window / 2;
+            }
+            at += window;
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_use_the_format_separator() {
+        let at = Duration::from_millis(3_723_045);
+        assert_eq!(timestamp(at, ','), "01:02:03,045");
+        assert_eq!(timestamp(at, '.'), "01:02:03.045");
+    }
+
+    #[test]
+    fn outputs_with_the_same_name_are_refused() {
+        let out = Path::new("out");
+        let inputs = [
+            PathBuf::from("a/talk.v1.mp3"),
+            PathBuf::from("a/talk.v2.mp3"),
+        ];
+        assert_eq!(
+            output_paths(&inputs, out, "srt").unwrap(),
+            [out.join("talk.v1.srt"), out.join("talk.v2.srt")]
+        );
+        let inputs = [PathBuf::from("a/talk.mp3"), PathBuf::from("b/talk.wav")];
+        assert!(output_paths(&inputs, out, "srt").is_err());
+    }
+
+    #[test]
+    fn chunks_end_in_pauses() {
+        let second = SAMPLE_RATE as usize;
+        // 25 s of "speech" with a silent stretch from 17 s to 17.5 s.
+        let mut samples = vec![1000i16; 25 * second];
+        samples[17 * second..17 * second + second / 2].fill(0);
+        let ranges = chunk_ranges(&samples, 20 * second);
+        assert_eq!(ranges.len(), 2);
+        let cut = ranges[0].end;
+        assert!(cut > 17 * second && cut < 17 * second + second / 2);
+        assert_eq!(ranges[1], cut..samples.len());
+    }
+
+    #[test]
+    fn srt_numbers_segments() {
+        let transcript = FileTranscript {
+            duration: Duration::from_secs(40),
+            segments: vec![
+                Segment {
+                    start: Duration::ZERO,
+                    end: Duration::from_millis(29_500),
+                    text: "Hello.".into(),
+                },
+                Segment {
+                    start: Duration::from_millis(29_500),
+                    end: Duration::from_secs(40),
+                    text: " Bye.".into(),
+                },
+            ],
+        };
+        let path = Path::new("meeting.wav");
+        assert_eq!(
+            transcript.render(OutputFormat::Srt, path),
+            "1\n00:00:00,000 --> 00:00:29,500\nHello.\n\n\
+             2\n00:00:29,500 --> 00:00:40,000\nBye.\n\n"
+        );
+        assert_eq!(transcript.render(OutputFormat::Text, path), "Hello. Bye.\n");
+    }
+}
@@ -0,0 +1,252 @@
+//! The desktop app: settings window, tray icon and preview overlay around
+//! the shared engine.
+
+use crate::app_state::AppState;
+use crate::config::AppConfig;
+use crate::control::EngineController;
+use crate::events::EngineEvent;
+use crate::{listener, preview, tauri_commands};
+use std::thread;
+use tauri::{
+    menu::{Menu, MenuItem},
+    tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
+    AppHandle, Manager, Runtime, State,
+};
+
+fn auto_start_if_possible<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+    let config = state.config.read().clone();
+
+    if config.get_ptt_key().is_none() {
+        println!("Auto-start skipped: no PTT key configured");
+        return;
+    }
+
+    if !config.use_local && config.api_key.is_none() {
+        println!("Auto-start skipped: no OpenAI API key configured");
+        return;
+    }
+
+    if config.use_local && config.local_model.is_none() {
+        println!("Auto-start skipped: no local model selected");
+        return;
+    }
+
+    let controller = app.state::<EngineController>();
+    if controller.is_running() {
+        println!("Auto-start skipped: engine already running");
+        return;
+    }
+
+    println!("Auto-starting transcription engine...");
+    match controller.start() {
+        Ok(_) => println!("Auto-start successful"),
+        Err(err) => println!("Auto-start failed: {}", err),
+    }
+}
+
+#[tauri::command]
+async fn start_engine(
+    state: State<'_, AppState>,
+    controller: State<'_, EngineController>,
+) -> Result<(), String> {
+    if controller.is_running() {
+        println!("Engine already running");
+        return Ok(());
+    }
+
+    println!("Starting transcription engine...");
+
+    // Debug: print config
+    let config = state.config.read();
+    println!("Config - PTT Key: {:?}", config.ptt_key);
+    println!("Config - Device: {}", config.device);
+    println!("Config - Use Local: {}", config.use_local);
+    println!("Config - Has API Key: {}", config.api_key.is_some());
+    drop(config);
+
+    match controller.start() {
+        Ok(_) => {
+            println!("Transcription engine started successfully!");
+            Ok(())
+        }
+        Err(e) => {
+            println!("Failed to start engine: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+fn stop_engine(controller: State<EngineController>) -> Result<(), String> {
+    controller.stop();
+    Ok(())
+}
+
+/// Keeps the tray tooltip in step with the engine, whoever started or
+/// stopped it (settings window, auto-start or the control socket).
+fn follow_engine_in_tray<R: Runtime>(app: &AppHandle<R>, state: &AppState) {
+    let app = app.clone();
+    let events = state.events.subscribe();
+    thread::spawn(move || {
+        for event in events.iter() {
+            let tooltip = match event {
+                EngineEvent::EngineStarted => "DeskTalk - Running",
+                EngineEvent::EngineStopped => "DeskTalk - Stopped",
+                _ => continue,
+            };
+            if let Some(tray) = app.tray_by_id("main") {
+                let _ = tray.set_tooltip(Some(tooltip));
+            }
+        }
+    });
+}
+
+fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<Menu<R>> {
+    let open_settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(app, &[&open_settings, &quit_item])
+}
+
+fn handle_tray_event<R: Runtime>(app: &AppHandle<R>, event: TrayIconEvent) {
+    if let TrayIconEvent::Click {
+        button: MouseButton::Left,
+        button_state: MouseButtonState::Up,
+        ..
+    } = event
+    {
+        if let Some(window) = app.get_webview_window("settings") {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    }
+}
+
+fn handle_menu_event<R: Runtime>(app: &AppHandle<R>, id: &str) {
+    match id {
+        "settings" => {
+            if let Some(window) = app.get_webview_window("settings") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        "quit" => {
+            app.exit(0);
+        }
+        _ => {}
+    }
+}
+
+/// Runs the app with `config` (loaded from `profile`) until the user quits.
+/// `headless` keeps the settings window closed; it can still be opened from
+/// the tray.
+pub fn run(config: AppConfig, profile: Option<String>, headless: bool) {
+    let app_state = AppState::new(config);
+    app_state.set_profile(profile);
+    let controller = EngineController::new(app_state.clone());
+    controller.spawn_services();
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            // When a second instance is launched, bring the existing window to front
+            println!("Second instance detected - bringing existing window to front");
+            if let Some(window) = app.get_webview_window("settings") {
+                let _ = window.show();
+                let _ = window.set_focus();
+                let _ = window.unminimize();
+            }
+        }))
+        .manage(controller)
+        .manage(app_state)
+        .invoke_handler(tauri::generate_handler![
+            tauri_commands::get_config,
+            tauri_commands::save_config,
+            tauri_commands::get_config_options,
+            tauri_commands::export_config,
+            tauri_commands::import_config,
+            tauri_commands::get_managed_settings,
+            tauri_commands::list_credentials,
+            tauri_commands::load_credential_key,
+            tauri_commands::remove_credential,
+            tauri_commands::get_statistics,
+            tauri_commands::get_last_transcription,
+            tauri_commands::get_audio_devices,
+            tauri_commands::get_available_ptt_keys,
+            tauri_commands::start_transcription,
+            tauri_commands::stop_transcription,
+            tauri_commands::is_running,
+            tauri_commands::validate_api_key,
+            tauri_commands::test_openai_key,
+            tauri_commands::detect_key_press,
+            tauri_commands::open_url,
+            start_engine,
+            stop_engine,
+        ])
+        .on_menu_event(|app, event| {
+            handle_menu_event(app, event.id().as_ref());
+        })
+        .setup(move |app| {
+            let handle = app.handle().clone();
+            let handle_for_tray = app.handle().clone();
+            let handle_for_auto_start = app.handle().clone();
+
+            // Check if we should start minimized BEFORE creating anything
+            let state = app.state::<AppState>();
+            let should_minimize = headless || state.config.read().start_minimized;
+
+            // Create tray menu
+            let menu = create_tray_menu(&handle)?;
+            let icon = app.default_window_icon().cloned();
+
+            let mut builder = TrayIconBuilder::with_id("main")
+                .menu(&menu)
+                .tooltip("DeskTalk");
+            if let Some(icon) = icon {
+                builder = builder.icon(icon);
+            }
+
+            let _tray = builder
+                .on_tray_icon_event(move |_tray, event| {
+                    handle_tray_event(&handle_for_tray, event);
+                })
+                .build(app)?;
+            follow_engine_in_tray(&handle, &state);
+
+            if let Some(window) = app.get_webview_window("settings") {
+                let window_handle = window.clone();
+                window.on_window_event(move |event| {
+                    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                        api.prevent_close();
+                        let _ = window_handle.hide();
+                    }
+                });
+
+                // Show window if NOT configured to start minimized
+                if !should_minimize {
+                    let _ = window.show();
+                    println!("Starting with window visible");
+                } else {
+                    println!("Starting minimized to tray");
+                }
+            }
+
+            // Preview overlay for live text (used when realtime output is
+            // "preview"). Created hidden up front so showing it is instant.
+            let (preview_tx, preview_rx) = flume::unbounded();
+            state.set_preview_sender(preview_tx);
+            preview::spawn_overlay(&handle, preview_rx);
+
+            // Start global event listener for PTT handling
+            let state_for_listener = app.state::<AppState>();
+            listener::start_global_event_listener(state_for_listener.inner().clone());
+
+            // Attempt to auto-start transcription if configuration is ready
+            auto_start_if_possible(&handle_for_auto_start);
+
+            Ok(())
+        })
+        .run(tauri::generate_context!("tauri.conf.json"))
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,198 @@
+//! Post-transcription hooks: hand every finished transcription to local
+//! commands or URLs, e.g. to file dictations into a notes or ticket tool.
+//!
+//! Hooks follow the [`EventBus`](crate::events::EventBus) instead of being
+//! called from the key handler, and every hook runs on its own thread with a
+//! timeout, so a slow or broken hook can't hold up typing or other hooks.
+//!
+//! A command gets the transcript on stdin and these environment variables:
+//!
+//! | Variable | |
+//! |----------|-|
+//! | `DESKTALK_STARTED_AT` | Unix time the recording started |
+//! | `DESKTALK_DURATION_SECS` | Recording length |
+//! | `DESKTALK_WINDOW_CLASS`, `DESKTALK_WINDOW_TITLE`, `DESKTALK_WINDOW_PROCESS` | Target window, empty if unknown |
+//! | `DESKTALK_PROFILE` | Active config profile, empty for the default |
+//!
+//! A URL gets the same as a JSON object (`text`, `started_at`,
+//! `duration_secs`, `window`, `profile`).
+
+use crate::app_state::{AppState, TranscriptionRecord};
+use crate::config::Hook;
+use crate::credentials::child_command;
+use crate::events::EngineEvent;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::json;
+use std::io::Write;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Runs the configured hooks for every transcription, until the app exits.
+pub fn spawn(state: AppState) {
+    let events = state.events.subscribe();
+    thread::Builder::new()
+        .name("hooks".into())
+        .spawn(move || {
+            for event in events.iter() {
+                let EngineEvent::TranscriptionCompleted { transcription } = event else {
+                    continue;
+                };
+                let hooks = state.config.read().hooks.clone();
+                let profile = state.profile();
+                for hook in hooks {
+                    let transcription = transcription.clone();
+                    let profile = profile.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = run(&hook, &transcription, profile.as_deref()) {
+                            eprintln!("Hook failed: {:?}", err);
+                        }
+                    });
+                }
+            }
+        })
+        .expect("Failed to spawn hooks thread");
+}
+
+fn run(hook: &Hook, transcription: &TranscriptionRecord, profile: Option<&str>) -> Result<()> {
+    let timeout = Duration::from_millis(hook.timeout_ms);
+    if let Some(command) = hook.command.as_deref().filter(|c| !c.trim().is_empty()) {
+        run_command(command, transcription, profile, timeout)
+            .with_context(|| format!("command `{}`", command))?;
+    }
+    if let Some(url) = hook.url.as_deref().filter(|u| !u.trim().is_empty()) {
+        post(url, transcription, profile, timeout).with_context(|| format!("POST {}", url))?;
+    }
+    Ok(())
+}
+
+fn run_command(
+    command: &str,
+    transcription: &TranscriptionRecord,
+    profile: Option<&str>,
+    timeout: Duration,
+) -> Result<()> {
+    let window = transcription.window.clone().unwrap_or_default();
+    let mut shell = if cfg!(windows) {
+        let mut shell = child_command("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = child_command("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    let mut child = shell
+        .env("DESKTALK_STARTED_AT", transcription.started_at.to_string())
+        .env(
+            "DESKTALK_DURATION_SECS",
+            format!("{:.2}", transcription.duration_secs),
+        )
+        .env("DESKTALK_WINDOW_CLASS", &window.class)
+        .env("DESKTALK_WINDOW_TITLE", &window.title)
+        .env("DESKTALK_WINDOW_PROCESS", &window.process)
+        .env("DESKTALK_PROFILE", profile.unwrap_or_default())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    // Feed stdin from another thread so a hook that doesn't read it (or
+    // reads slowly) can't block us past the timeout.
+    if let Some(mut stdin) = child.stdin.take() {
+        let text = transcription.text.clone();
+        thread::spawn(move || {
+            let _ = stdin.write_all(text.as_bytes());
+        });
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                bail!("exited with {}", status);
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("timed out after {:?}", timeout);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn post(
+    url: &str,
+    transcription: &TranscriptionRecord,
+    profile: Option<&str>,
+    timeout: Duration,
+) -> Result<()> {
+    let body = json!({
+        "text": transcription.text,
+        "started_at": transcription.started_at,
+        "duration_secs": transcription.duration_secs,
+        "window": transcription.window,
+        "profile": profile,
+    });
+    ureq::AgentBuilder::new()
+        .timeout(timeout)
+        .build()
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .map_err(|err| anyhow!("{}", err))?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::active_window::ActiveWindow;
+
+    fn transcription() -> TranscriptionRecord {
+        TranscriptionRecord {
+            text: "Buy milk.".into(),
+            started_at: 1_700_000_000,
+            duration_secs: 1.5,
+            window: Some(ActiveWindow {
+                class: "Obsidian".into(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn commands_get_text_on_stdin_and_metadata_in_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.txt");
+        let command = format!(
+            "{{ cat; echo \" $DESKTALK_WINDOW_CLASS $DESKTALK_PROFILE\"; }} > '{}'",
+            out.display()
+        );
+        run_command(
+            &command,
+            &transcription(),
+            Some("work"),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(out).unwrap(),
+            "Buy milk. Obsidian work\n"
+        );
+    }
+
+    #[test]
+    fn slow_commands_are_killed() {
+        let started = Instant::now();
+        let result = run_command(
+            "sleep 5",
+            &transcription(),
+            None,
+            Duration::from_millis(100),
+        );
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,422 @@
+//! Keyboard and clipboard backends used by the output sinks.
+//!
+//! `enigo` and the `clipboard` crate talk to X11. Under a Wayland session they
+//! only reach XWayland windows (or nothing at all), so keystrokes and pastes
+//! silently go nowhere. When a Wayland session is detected the helpers below
+//! are used instead:
+//!
+//! * keyboard: `wtype` (virtual-keyboard protocol; wlroots compositors, KDE)
+//!   or `ydotool` (uinput; works everywhere but needs `ydotoold` running)
+//! * clipboard: `wl-copy` / `wl-paste` from wl-clipboard
+//!
+//! If none of them is installed, building the backend fails with an error
+//! that says what to install, instead of typing into the void.
+
+use crate::clipboard_snapshot::{self, ClipboardSnapshot};
+use crate::credentials::child_command;
+use anyhow::{anyhow, bail, Context, Result};
+use clipboard::{ClipboardContext, ClipboardProvider};
+use enigo::{Enigo, Key, KeyboardControllable};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Linux input event codes used with `ydotool key`.
+const KEY_BACKSPACE: u32 = 14;
+const KEY_LEFTCTRL: u32 = 29;
+const KEY_LEFTSHIFT: u32 = 42;
+const KEY_LEFTALT: u32 = 56;
+const KEY_LEFTMETA: u32 = 125;
+const KEY_INSERT: u32 = 110;
+
+/// Input event codes of the letter keys (US layout), `a` to `z`.
+const LETTER_CODES: [u32; 26] = [
+    30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17, 45,
+    21, 44,
+];
+
+/// Non-modifier key of a [`PasteChord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordKey {
+    /// A letter or digit.
+    Char(char),
+    Insert,
+}
+
+/// A paste key chord such as Ctrl+V, Ctrl+Shift+V or Shift+Insert, parsed
+/// from strings like `"ctrl+shift+v"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasteChord {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    /// Cmd on macOS, the Windows/Super key elsewhere.
+    pub meta: bool,
+    pub key: ChordKey,
+}
+
+impl std::str::FromStr for PasteChord {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut modifiers = (false, false, false, false);
+        let mut key = None;
+        for part in value.split('+').map(|p| p.trim().to_lowercase()) {
+            match part.as_str() {
+                "ctrl" | "control" => modifiers.0 = true,
+                "shift" => modifiers.1 = true,
+                "alt" | "option" => modifiers.2 = true,
+                "cmd" | "command" | "meta" | "super" | "win" => modifiers.3 = true,
+                _ if key.is_some() => bail!("Paste shortcut \"{value}\" has more than one key"),
+                "insert" | "ins" => key = Some(ChordKey::Insert),
+                p if p.len() == 1 && p.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                    key = p.chars().next().map(ChordKey::Char)
+                }
+                _ => bail!("Unknown key \"{part}\" in paste shortcut \"{value}\""),
+            }
+        }
+        let key = key.ok_or_else(|| anyhow!("Paste shortcut \"{value}\" has no key"))?;
+        let (ctrl, shift, alt, meta) = modifiers;
+        Ok(Self {
+            ctrl,
+            shift,
+            alt,
+            meta,
+            key,
+        })
+    }
+}
+
+impl PasteChord {
+    fn enigo_modifiers(&self) -> Vec<Key> {
+        [
+            (self.ctrl, Key::Control),
+            (self.shift, Key::Shift),
+            (self.alt, Key::Alt),
+            (self.meta, Key::Meta),
+        ]
+        .into_iter()
+        .filter_map(|(held, key)| held.then_some(key))
+        .collect()
+    }
+
+    fn enigo_key(&self) -> Result<Key> {
+        match self.key {
+            ChordKey::Char(c) => Ok(Key::Layout(c)),
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            ChordKey::Insert => Ok(Key::Insert),
+            #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+            ChordKey::Insert => bail!("The Insert key can't be sent on this platform"),
+        }
+    }
+
+    fn wtype_args(&self) -> Vec<String> {
+        let modifiers: Vec<&str> = [
+            (self.ctrl, "ctrl"),
+            (self.shift, "shift"),
+            (self.alt, "alt"),
+            (self.meta, "logo"),
+        ]
+        .into_iter()
+        .filter_map(|(held, name)| held.then_some(name))
+        .collect();
+        let mut args = Vec::new();
+        for modifier in &modifiers {
+            args.extend(["-M".to_string(), modifier.to_string()]);
+        }
+        let key = match self.key {
+            ChordKey::Char(c) => c.to_string(),
+            ChordKey::Insert => "Insert".to_string(),
+        };
+        args.extend(["-k".to_string(), key]);
+        for modifier in modifiers.iter().rev() {
+            args.extend(["-m".to_string(), modifier.to_string()]);
+        }
+        args
+    }
+
+    fn ydotool_args(&self) -> Vec<String> {
+        let mut codes: Vec<u32> = [
+            (self.ctrl, KEY_LEFTCTRL),
+            (self.shift, KEY_LEFTSHIFT),
+            (self.alt, KEY_LEFTALT),
+            (self.meta, KEY_LEFTMETA),
+        ]
+        .into_iter()
+        .filter_map(|(held, code)| held.then_some(code))
+        .collect();
+        codes.push(match self.key {
+            ChordKey::Insert => KEY_INSERT,
+            ChordKey::Char('0') => 11,
+            // 1..9 are codes 2..10
+            ChordKey::Char(c @ '1'..='9') => c as u32 - '1' as u32 + 2,
+            ChordKey::Char(c) => LETTER_CODES[(c as u8 - b'a') as usize],
+        });
+        let mut args = vec!["key".to_string()];
+        args.extend(codes.iter().map(|code| format!("{code}:1")));
+        args.extend(codes.iter().rev().map(|code| format!("{code}:0")));
+        args
+    }
+}
+
+/// True when running inside a Wayland session.
+pub fn is_wayland_session() -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    std::env::var("XDG_SESSION_TYPE")
+        .map(|t| t.eq_ignore_ascii_case("wayland"))
+        .unwrap_or(false)
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Looks up an executable on `PATH`.
+pub(crate) fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Runs a helper and turns a non-zero exit into an error carrying its stderr.
+fn run(program: &str, args: &[String]) -> Result<()> {
+    let output = child_command(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !output.status.success() {
+        bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Synthetic keyboard input.
+pub enum Keyboard {
+    Enigo(Enigo),
+    Wtype,
+    Ydotool,
+}
+
+impl Keyboard {
+    /// Picks the backend for the current session.
+    pub fn detect() -> Result<Self> {
+        if !is_wayland_session() {
+            return Ok(Keyboard::Enigo(Enigo::new()));
+        }
+        if find_program("wtype").is_some() {
+            println!("Wayland session: typing with wtype");
+            Ok(Keyboard::Wtype)
+        } else if find_program("ydotool").is_some() {
+            println!("Wayland session: typing with ydotool");
+            Ok(Keyboard::Ydotool)
+        } else {
+            Err(anyhow!(
+                "Wayland session detected but no way to send keystrokes: install wtype \
+                 (wlroots compositors, KDE) or ydotool (any compositor, needs ydotoold running)"
+            ))
+        }
+    }
+
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Keyboard::Enigo(enigo) => {
+                enigo.key_sequence(text);
+                Ok(())
+            }
+            Keyboard::Wtype => run("wtype", &["--".into(), text.into()]),
+            Keyboard::Ydotool => run("ydotool", &["type".into(), "--".into(), text.into()]),
+        }
+    }
+
+    pub fn backspace(&mut self, count: usize) -> Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        match self {
+            Keyboard::Enigo(enigo) => {
+                for _ in 0..count {
+                    enigo.key_click(Key::Backspace);
+                }
+                Ok(())
+            }
+            Keyboard::Wtype => {
+                let mut args = Vec::with_capacity(count * 2);
+                for _ in 0..count {
+                    args.push("-k".to_string());
+                    args.push("BackSpace".to_string());
+                }
+                run("wtype", &args)
+            }
+            Keyboard::Ydotool => {
+                let mut args = vec!["key".to_string()];
+                for _ in 0..count {
+                    args.push(format!("{KEY_BACKSPACE}:1"));
+                    args.push(format!("{KEY_BACKSPACE}:0"));
+                }
+                run("ydotool", &args)
+            }
+        }
+    }
+
+    /// Sends a paste shortcut.
+    pub fn paste(&mut self, chord: &PasteChord) -> Result<()> {
+        match self {
+            Keyboard::Enigo(enigo) => {
+                let key = chord.enigo_key()?;
+                let modifiers = chord.enigo_modifiers();
+                for modifier in &modifiers {
+                    enigo.key_down(*modifier);
+                }
+                sleep(Duration::from_millis(100));
+                enigo.key_click(key);
+                sleep(Duration::from_millis(100));
+                for modifier in modifiers.iter().rev() {
+                    enigo.key_up(*modifier);
+                }
+                sleep(Duration::from_millis(100));
+                Ok(())
+            }
+            Keyboard::Wtype => run("wtype", &chord.wtype_args()),
+            Keyboard::Ydotool => run("ydotool", &chord.ydotool_args()),
+        }
+    }
+}
+
+/// System clipboard (text).
+pub enum Clipboard {
+    X11(ClipboardContext),
+    Wayland,
+}
+
+impl Clipboard {
+    /// Picks the backend for the current session.
+    pub fn detect() -> Result<Self> {
+        if is_wayland_session() {
+            if find_program("wl-copy").is_some() && find_program("wl-paste").is_some() {
+                return Ok(Clipboard::Wayland);
+            }
+            bail!(
+                "Wayland session detected but no clipboard tool found: install wl-clipboard \
+                 (wl-copy / wl-paste)"
+            );
+        }
+        let context = ClipboardProvider::new()
+            .map_err(|err| anyhow!("Failed to access clipboard: {:?}", err))?;
+        Ok(Clipboard::X11(context))
+    }
+
+    pub fn get_text(&mut self) -> Result<String> {
+        match self {
+            Clipboard::X11(context) => context
+                .get_contents()
+                .map_err(|err| anyhow!("Failed to read clipboard: {:?}", err)),
+            Clipboard::Wayland => {
+                let output = child_command("wl-paste")
+                    .args(["--no-newline", "--type", "text/plain"])
+                    .stdin(Stdio::null())
+                    .output()
+                    .context("Failed to run wl-paste")?;
+                if !output.status.success() {
+                    bail!("Clipboard is empty or not text");
+                }
+                Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            }
+        }
+    }
+
+    /// Saves the clipboard in every format that can be read, falling back to
+    /// its text.
+    pub fn snapshot(&mut self) -> Result<ClipboardSnapshot> {
+        match clipboard_snapshot::capture() {
+            Ok(Some(snapshot)) => return Ok(snapshot),
+            Ok(None) => {}
+            Err(err) => eprintln!("Failed to save clipboard formats, keeping text only: {err}"),
+        }
+        Ok(match self.get_text() {
+            Ok(text) => ClipboardSnapshot::Text(text),
+            Err(_) => ClipboardSnapshot::Empty,
+        })
+    }
+
+    pub fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<()> {
+        match snapshot {
+            ClipboardSnapshot::Empty => Ok(()),
+            ClipboardSnapshot::Text(text) => self.set_text(text),
+            ClipboardSnapshot::Formats(formats) => clipboard_snapshot::restore(formats),
+        }
+    }
+
+    /// Sets the text, asking clipboard managers and history to skip it where
+    /// the platform has a way to say so.
+    pub fn set_transient_text(&mut self, text: &str) -> Result<()> {
+        if let Clipboard::X11(_) = self {
+            if clipboard_snapshot::set_transient_text(text)? {
+                return Ok(());
+            }
+        }
+        self.set_text(text)
+    }
+
+    pub fn set_text(&mut self, text: &str) -> Result<()> {
+        match self {
+            Clipboard::X11(context) => context
+                .set_contents(text.to_string())
+                .map_err(|err| anyhow!("Failed to set clipboard: {:?}", err)),
+            Clipboard::Wayland => {
+                // wl-copy forks into the background to serve the selection, so
+                // this returns as soon as the text has been handed over.
+                let mut child = child_command("wl-copy")
+                    .args(["--type", "text/plain"])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .context("Failed to run wl-copy")?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin
+                        .write_all(text.as_bytes())
+                        .context("Failed to write to wl-copy")?;
+                }
+                let status = child.wait().context("Failed to wait for wl-copy")?;
+                if !status.success() {
+                    bail!("wl-copy failed to set the clipboard");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paste_chords_parse_case_insensitively() {
+        let chord: PasteChord = "Ctrl + Shift + V".parse().unwrap();
+        assert!(chord.ctrl && chord.shift && !chord.alt && !chord.meta);
+        assert_eq!(chord.key, ChordKey::Char('v'));
+        let chord: PasteChord = "shift+insert".parse().unwrap();
+        assert_eq!(chord.key, ChordKey::Insert);
+        assert!("ctrl+shift".parse::<PasteChord>().is_err());
+        assert!("ctrl+v+c".parse::<PasteChord>().is_err());
+        assert!("hyper+v".parse::<PasteChord>().is_err());
+    }
+
+    #[test]
+    fn ydotool_presses_modifiers_first_and_releases_them_last() {
+        let chord: PasteChord = "ctrl+shift+v".parse().unwrap();
+        assert_eq!(
+            chord.ydotool_args(),
+            ["key", "29:1", "42:1", "47:1", "47:0", "42:0", "29:0"]
+        );
+    }
+}
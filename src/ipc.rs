@@ -0,0 +1,280 @@
+//! Local control socket for scripting the engine (window manager bindings,
+//! foot pedals, Stream Deck buttons, ...).
+//!
+//! A Unix domain socket (a named pipe on Windows) that speaks newline
+//! delimited JSON. Each request is one object with a `cmd` field; each reply
+//! is one object with `"ok": true` plus data, or `"ok": false` and an `error`:
+//!
+//! ```text
+//! {"cmd": "toggle_recording"}                 -> {"ok": true, "recording": true}
+//! {"cmd": "status"}                           -> {"ok": true, "engine_running": true, ...}
+//! {"cmd": "switch_profile", "profile": "work"} -> {"ok": true, "profile": "work"}
+//! ```
+//!
+//! After `{"cmd": "subscribe"}` the connection receives one line per engine
+//! event (`{"event": "recording_started"}`, ...) until it is closed.
+//!
+//! The socket is only reachable by the current user: it lives in
+//! `$XDG_RUNTIME_DIR` (or a 0700 directory of ours in the temp dir) with
+//! mode 0600; the pipe rejects remote clients and other users can't write to
+//! it.
+
+use crate::config::AppConfig;
+use crate::control::EngineController;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::thread;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    StartRecording,
+    StopRecording,
+    ToggleRecording,
+    StartEngine,
+    StopEngine,
+    Status,
+    LastTranscript,
+    ListProfiles,
+    /// `profile` omitted or null switches back to the default config.
+    SwitchProfile {
+        #[serde(default)]
+        profile: Option<String>,
+    },
+    Subscribe,
+}
+
+/// Where the control socket lives. Without `$XDG_RUNTIME_DIR` it gets a
+/// directory of its own, since anyone can create files in the temp dir.
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("desk-talk.sock"),
+        None => std::env::temp_dir()
+            .join(format!("desk-talk-{}", current_uid()))
+            .join("desk-talk.sock"),
+    }
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: geteuid can't fail and has no side effects.
+    unsafe { libc::geteuid() }
+}
+
+/// Creates the socket's directory with mode 0700 if it doesn't exist, and
+/// refuses one that another user owns or can open: they could connect in the
+/// moment between binding the socket and restricting its mode.
+#[cfg(unix)]
+fn check_socket_dir(dir: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err.into()),
+    }
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() {
+        anyhow::bail!("{} is not a directory of this user", dir.display());
+    }
+    if metadata.mode() & 0o077 != 0 {
+        anyhow::bail!("{} can be opened by other users", dir.display());
+    }
+    Ok(())
+}
+
+/// Where the control socket lives.
+#[cfg(windows)]
+pub fn socket_path() -> PathBuf {
+    PathBuf::from(r"\\.\pipe\desk-talk")
+}
+
+/// Serves the control socket on a background thread.
+pub fn spawn(controller: EngineController) {
+    thread::Builder::new()
+        .name("control-socket".into())
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    eprintln!("Control socket: failed to create runtime: {:?}", err);
+                    return;
+                }
+            };
+            if let Err(err) = runtime.block_on(serve(controller)) {
+                eprintln!("Control socket stopped: {:?}", err);
+            }
+        })
+        .expect("Failed to spawn control socket thread");
+}
+
+#[cfg(unix)]
+async fn serve(controller: EngineController) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixListener;
+
+    let path = socket_path();
+    if let Some(dir) = path.parent() {
+        check_socket_dir(dir)?;
+    }
+    if path.exists() {
+        // A socket file nobody answers on is left over from a crash.
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            anyhow::bail!(
+                "{} is in use (is another desk-talk running?)",
+                path.display()
+            );
+        }
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    println!("Control socket listening on {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(handle_connection(stream, controller.clone()));
+    }
+}
+
+#[cfg(windows)]
+async fn serve(controller: EngineController) -> Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let path = socket_path();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&path)?;
+    println!("Control pipe listening on {}", path.display());
+
+    loop {
+        server.connect().await?;
+        let connected = server;
+        server = ServerOptions::new().create(&path)?;
+        tokio::spawn(handle_connection(connected, controller.clone()));
+    }
+}
+
+async fn handle_connection<S>(stream: S, controller: EngineController)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = serde_json::from_str::<Request>(&line);
+        let subscribe = matches!(request, Ok(Request::Subscribe));
+        let reply = match request {
+            Ok(request) => handle(request, &controller),
+            Err(err) => json!({ "ok": false, "error": format!("Invalid request: {}", err) }),
+        };
+        if write_line(&mut writer, &reply).await.is_err() {
+            return;
+        }
+        if subscribe {
+            let events = controller.state().events.subscribe();
+            while let Ok(event) = events.recv_async().await {
+                let event = serde_json::to_value(&event).unwrap_or(Value::Null);
+                if write_line(&mut writer, &event).await.is_err() {
+                    return;
+                }
+            }
+            return;
+        }
+    }
+}
+
+async fn write_line<W: AsyncWrite + Unpin>(writer: &mut W, value: &Value) -> std::io::Result<()> {
+    let mut line = value.to_string();
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await
+}
+
+fn handle(request: Request, controller: &EngineController) -> Value {
+    match run(request, controller) {
+        Ok(mut reply) => {
+            reply["ok"] = Value::Bool(true);
+            reply
+        }
+        Err(err) => json!({ "ok": false, "error": err.to_string() }),
+    }
+}
+
+fn run(request: Request, controller: &EngineController) -> Result<Value> {
+    let state = controller.state();
+    Ok(match request {
+        Request::StartRecording => {
+            controller.set_recording(true)?;
+            json!({ "recording": true })
+        }
+        Request::StopRecording => {
+            controller.set_recording(false)?;
+            json!({ "recording": false })
+        }
+        Request::ToggleRecording => json!({ "recording": controller.toggle_recording()? }),
+        Request::StartEngine => {
+            controller.start()?;
+            json!({})
+        }
+        Request::StopEngine => {
+            controller.stop();
+            json!({})
+        }
+        Request::Status => json!({
+            "engine_running": controller.is_running(),
+            "recording": state.is_recording(),
+            "profile": state.profile(),
+        }),
+        Request::LastTranscript => json!({ "transcription": state.last_transcription() }),
+        Request::ListProfiles => json!({
+            "profiles": AppConfig::list_profiles()?,
+            "active": state.profile(),
+        }),
+        Request::SwitchProfile { profile } => {
+            controller.switch_profile(profile.clone())?;
+            json!({ "profile": profile })
+        }
+        Request::Subscribe => json!({}),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_dirs_others_can_open_are_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("desk-talk");
+        check_socket_dir(&dir).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_socket_dir(&dir).is_err());
+    }
+
+    #[test]
+    fn requests_parse_from_json_lines() {
+        let request: Request =
+            serde_json::from_str(r#"{"cmd": "switch_profile", "profile": "work"}"#).unwrap();
+        assert!(matches!(request, Request::SwitchProfile { profile: Some(p) } if p == "work"));
+        let request: Request = serde_json::from_str(r#"{"cmd": "switch_profile"}"#).unwrap();
+        assert!(matches!(request, Request::SwitchProfile { profile: None }));
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "reboot"}"#).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! How long transcriptions take, per backend and per day, as histograms so
+//! the statistics can show percentiles without keeping every sample. Saved
+//! with the racing statistics (see `transcribe.rs`).
+
+use chrono::{Days, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Upper bounds (ms) of the histogram buckets; one more bucket takes the
+/// rest.
+const BUCKETS_MS: [u64; 20] = [
+    100, 200, 300, 400, 500, 600, 800, 1_000, 1_250, 1_500, 2_000, 2_500, 3_000, 4_000, 5_000,
+    7_500, 10_000, 15_000, 20_000, 30_000,
+];
+
+/// Days of histograms kept.
+const KEEP_DAYS: u64 = 90;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Samples per bucket of `BUCKETS_MS`, plus the overflow bucket.
+    #[serde(default)]
+    counts: Vec<u64>,
+    #[serde(default)]
+    max_ms: u64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, ms: u64) {
+        self.counts.resize(BUCKETS_MS.len() + 1, 0);
+        let bucket = BUCKETS_MS.partition_point(|&bound| bound < ms);
+        self.counts[bucket] += 1;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        self.counts.resize(BUCKETS_MS.len() + 1, 0);
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.max_ms = self.max_ms.max(other.max_ms);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The `q` quantile (0 to 1) in ms, rounded up to its bucket's bound
+    /// (and never above the slowest sample).
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((count as f64) * q).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, &samples) in self.counts.iter().enumerate() {
+            seen += samples;
+            if seen >= rank {
+                let bound = BUCKETS_MS.get(bucket).copied().unwrap_or(self.max_ms);
+                return Some(bound.min(self.max_ms));
+            }
+        }
+        Some(self.max_ms)
+    }
+}
+
+/// Percentiles of one backend, on one day or (without a date) over all the
+/// days kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub backend: String,
+    pub date: Option<String>,
+    pub count: u64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+}
+
+impl LatencySummary {
+    fn new(backend: &str, date: Option<&str>, histogram: &Histogram) -> Option<Self> {
+        Some(Self {
+            backend: backend.to_string(),
+            date: date.map(str::to_string),
+            count: histogram.count(),
+            p50_ms: histogram.quantile(0.5)?,
+            p90_ms: histogram.quantile(0.9)?,
+            p99_ms: histogram.quantile(0.99)?,
+        })
+    }
+}
+
+/// "YYYY-MM-DD" -> backend -> histogram
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyLog {
+    #[serde(default)]
+    days: BTreeMap<String, BTreeMap<String, Histogram>>,
+}
+
+impl LatencyLog {
+    /// Adds a sample for today and drops days older than `KEEP_DAYS`.
+    pub fn record(&mut self, backend: &str, elapsed: Duration) {
+        let today = Local::now().date_naive();
+        self.days
+            .entry(today.format("%Y-%m-%d").to_string())
+            .or_default()
+            .entry(backend.to_string())
+            .or_default()
+            .record(elapsed.as_millis() as u64);
+        if let Some(oldest) = today.checked_sub_days(Days::new(KEEP_DAYS)) {
+            let oldest = oldest.format("%Y-%m-%d").to_string();
+            self.days.retain(|date, _| *date >= oldest);
+        }
+    }
+
+    /// Adds the samples of `other`, e.g. those another process saved.
+    pub fn merge(&mut self, other: &LatencyLog) {
+        for (date, backends) in &other.days {
+            let day = self.days.entry(date.clone()).or_default();
+            for (backend, histogram) in backends {
+                day.entry(backend.clone()).or_default().merge(histogram);
+            }
+        }
+    }
+
+    /// Percentiles per backend for each day, oldest first.
+    pub fn daily(&self) -> Vec<LatencySummary> {
+        self.days
+            .iter()
+            .flat_map(|(date, backends)| {
+                backends.iter().filter_map(move |(backend, histogram)| {
+                    LatencySummary::new(backend, Some(date), histogram)
+                })
+            })
+            .collect()
+    }
+
+    /// Percentiles per backend over all the days kept.
+    pub fn overall(&self) -> Vec<LatencySummary> {
+        let mut merged: BTreeMap<&str, Histogram> = BTreeMap::new();
+        for backends in self.days.values() {
+            for (backend, histogram) in backends {
+                merged.entry(backend).or_default().merge(histogram);
+            }
+        }
+        merged
+            .iter()
+            .filter_map(|(backend, histogram)| LatencySummary::new(backend, None, histogram))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_come_from_the_buckets() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for ms in [90, 150, 180, 450, 2_200, 2_300, 2_400, 2_450, 2_500, 42_000] {
+            histogram.record(ms);
+        }
+        assert_eq!(histogram.count(), 10);
+        assert_eq!(histogram.quantile(0.5), Some(2_500));
+        assert_eq!(histogram.quantile(0.1), Some(100));
+        assert_eq!(histogram.quantile(0.99), Some(42_000));
+    }
+
+    #[test]
+    fn empty_histograms_are_skipped() {
+        let log: LatencyLog =
+            serde_json::from_str(r#"{ "days": { "2026-01-01": { "openai": {} } } }"#).unwrap();
+        assert!(log.daily().is_empty());
+        assert!(log.overall().is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+//! DeskTalk: push-to-talk dictation.
+//!
+//! The engine, config and transcription pipeline live here so the desktop
+//! app (`desk-talk`) and the command-line tool (`desk-talk-cli`) run the same
+//! code and read the same config file.
+
+pub mod active_window;
+pub mod app_state;
+pub mod args;
+pub mod clipboard_snapshot;
+pub mod config;
+pub mod config_migration;
+pub mod config_share;
+pub mod config_watch;
+pub mod control;
+pub mod credentials;
+pub mod daemon;
+#[cfg(target_os = "linux")]
+pub mod dbus;
+pub mod easy_rdev_key;
+pub mod events;
+pub mod file_transcribe;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod hooks;
+pub mod inject;
+pub mod ipc;
+pub mod latency;
+pub mod listener;
+pub mod local_stream;
+pub mod output;
+pub mod preview;
+pub mod realtime;
+pub mod record;
+pub mod spending;
+pub mod statistics;
+#[cfg(feature = "gui")]
+pub mod tauri_commands;
+pub mod transcribe;
+pub mod transcription_engine;
@@ -0,0 +1,88 @@
+//! The system-wide key listener shared by the GUI and the CLI.
+
+use crate::app_state::AppState;
+use default_device_sink::DefaultDeviceSink;
+use rdev::{listen, Event, EventType};
+use rodio::{source::SineWave, Decoder, Source};
+use std::io::{BufReader, Cursor};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+static FAILED_BYTES: &[u8] = include_bytes!("../assets/failed.mp3");
+
+/// Plays the "failed" sound and waits for it to finish.
+pub fn play_error_sound() {
+    let sink = DefaultDeviceSink::new();
+    if let Ok(decoder) = Decoder::new(BufReader::new(Cursor::new(FAILED_BYTES))) {
+        sink.append(decoder);
+    } else {
+        sink.append(
+            SineWave::new(440.0)
+                .take_duration(Duration::from_millis(150))
+                .amplify(0.20),
+        );
+    }
+    sink.sleep_until_end();
+}
+
+/// Listens for key events system-wide and forwards them to the running
+/// engine. Plays the error sound if the PTT key is pressed while the engine
+/// isn't running. The returned thread runs for the life of the process.
+pub fn start_global_event_listener(app_state: AppState) -> JoinHandle<()> {
+    thread::spawn(move || {
+        println!("Global event listener started");
+
+        // Channel for error sound playback (avoid blocking rdev callback)
+        let (error_tx, error_rx): (flume::Sender<()>, flume::Receiver<()>) = flume::unbounded();
+
+        // Thread to play error sounds (so rdev callback isn't blocked)
+        thread::spawn(move || {
+            for _ in error_rx.iter() {
+                play_error_sound();
+            }
+        });
+
+        let mut ptt_key_pressed = false;
+
+        let callback = move |event: Event| {
+            // Check if engine has an active event sender (fast, cached check)
+            let sender_opt = app_state.event_sender();
+            let engine_ready = sender_opt.is_some();
+
+            // Forward events to engine if it's ready
+            if engine_ready {
+                if let Some(sender) = sender_opt {
+                    let _ = sender.send(event.clone());
+                }
+            }
+
+            // Track PTT key state and play error sound if engine is NOT ready
+            if let Some(ptt_key) = app_state.config.read().get_ptt_key() {
+                match event.event_type {
+                    EventType::KeyPress(key) => {
+                        if key == ptt_key && !ptt_key_pressed {
+                            ptt_key_pressed = true;
+                            // If engine doesn't have a sender, it's not ready - play error immediately
+                            if !engine_ready {
+                                println!(
+                                    "PTT pressed but engine is NOT ready - playing error sound"
+                                );
+                                let _ = error_tx.send(()); // Non-blocking send to error sound thread
+                            }
+                        }
+                    }
+                    EventType::KeyRelease(key) => {
+                        if key == ptt_key {
+                            ptt_key_pressed = false;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        if let Err(error) = listen(callback) {
+            eprintln!("Error in global event listener: {:?}", error);
+        }
+    })
+}
@@ -0,0 +1,384 @@
+//! Local streaming transcription using the in-process whisper model.
+//!
+//! Realtime live typing normally needs the OpenAI Realtime API (see
+//! [`crate::realtime`]). This module gives local-model users the same
+//! experience without the cloud: microphone audio is captured with the realtime
+//! capture pipeline (resampled to 16 kHz for whisper), and the model
+//! re-transcribes a sliding window of it every [`STEP_SAMPLES`] of new audio.
+//!
+//! Whisper revises its guess for the last few words as more audio arrives, so
+//! text is only typed once two consecutive passes agree on it. Anything typed
+//! is therefore stable and never has to be retracted. On key release the
+//! remaining audio is transcribed one last time and whatever was not typed yet
+//! is flushed. A pass that fails is skipped; if the last one fails, the whole
+//! recording is transcribed instead.
+
+use crate::config::InputDevice;
+use crate::realtime::{build_input_stream, LiveTyper, LiveTyperOptions};
+use crate::transcribe::trans;
+use anyhow::{anyhow, Context, Result};
+use mutter::{Model, ModelType};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Sample rate whisper models expect (mono, f32).
+const LOCAL_SAMPLE_RATE: u32 = 16_000;
+
+/// Run a new transcription pass after this much new audio (~1s).
+const STEP_SAMPLES: usize = LOCAL_SAMPLE_RATE as usize;
+
+/// Whisper produces junk on very short clips, so wait for this much audio
+/// before the first pass.
+const MIN_WINDOW_SAMPLES: usize = LOCAL_SAMPLE_RATE as usize;
+
+/// Once the window grows past this (~20s), audio belonging to segments that
+/// were already typed is dropped so each pass stays fast.
+const MAX_WINDOW_SAMPLES: usize = LOCAL_SAMPLE_RATE as usize * 20;
+
+/// whisper.cpp segment timestamps are in 10ms units.
+const SAMPLES_PER_TIMESTAMP_UNIT: usize = LOCAL_SAMPLE_RATE as usize / 100;
+
+/// A live local transcription session. Created on push-to-talk key down and
+/// finished on key up via [`LocalStreamingSession::stop`], mirroring
+/// [`crate::realtime::RealtimeSession`].
+pub struct LocalStreamingSession {
+    stop_tx: flume::Sender<()>,
+    result_rx: flume::Receiver<Result<String>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LocalStreamingSession {
+    /// Starts capturing the microphone and transcribing it locally. Returns
+    /// quickly; the model is loaded on a background thread while audio is
+    /// already being buffered, so nothing spoken during the load is lost.
+    pub fn start(
+        device: InputDevice,
+        model_type: ModelType,
+        output: LiveTyperOptions,
+    ) -> Result<Self> {
+        let (stop_tx, stop_rx) = flume::bounded::<()>(1);
+        let (result_tx, result_rx) = flume::bounded::<Result<String>>(1);
+        let (ready_tx, ready_rx) = flume::bounded::<Result<()>>(1);
+
+        let handle = thread::Builder::new()
+            .name("local-stream-session".into())
+            .spawn(move || {
+                // cpal streams aren't Send, so the capture lives on this thread.
+                let (audio_tx, audio_rx) = flume::unbounded::<Vec<i16>>();
+                let stream = match build_input_stream(&device, LOCAL_SAMPLE_RATE, audio_tx) {
+                    Ok(stream) => {
+                        let _ = ready_tx.send(Ok(()));
+                        stream
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                let result = run_session(&model_type, output, &audio_rx, &stop_rx);
+                drop(stream);
+                let _ = result_tx.send(result);
+            })
+            .context("Failed to spawn local streaming thread")?;
+
+        ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("Local streaming thread exited early")))
+            .context("Failed to start microphone capture")?;
+
+        Ok(Self {
+            stop_tx,
+            result_rx,
+            handle: Some(handle),
+        })
+    }
+
+    /// Signals the session to stop capturing, runs a final pass over the
+    /// remaining audio, and returns the full text that was typed.
+    pub fn stop(mut self) -> Result<String> {
+        let _ = self.stop_tx.send(());
+        let result = self
+            .result_rx
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("Local streaming session ended unexpectedly")));
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        result
+    }
+}
+
+fn run_session(
+    model_type: &ModelType,
+    output: LiveTyperOptions,
+    audio_rx: &flume::Receiver<Vec<i16>>,
+    stop_rx: &flume::Receiver<()>,
+) -> Result<String> {
+    let model = trans::load_or_download_model(model_type)
+        .map_err(|e| anyhow!("Failed to load local model: {e}"))?;
+    println!("Local streaming: model loaded");
+
+    let mut typer = LiveTyper::new(output);
+    let mut window = StreamingWindow::default();
+    let mut samples_since_pass = 0usize;
+
+    loop {
+        if stop_rx.try_recv().is_ok() {
+            break;
+        }
+        match audio_rx.recv_timeout(Duration::from_millis(50)) {
+            Ok(samples) => {
+                samples_since_pass += samples.len();
+                window.push(&samples);
+            }
+            Err(flume::RecvTimeoutError::Timeout) => {}
+            Err(flume::RecvTimeoutError::Disconnected) => break,
+        }
+
+        if samples_since_pass >= STEP_SAMPLES && window.audio.len() >= MIN_WINDOW_SAMPLES {
+            samples_since_pass = 0;
+            match transcribe_window(&model, &window.audio) {
+                Ok(segments) => {
+                    let stable = window.advance(&segments);
+                    if !stable.is_empty() {
+                        typer.type_delta(&format!(" {stable}"));
+                    }
+                }
+                Err(err) => eprintln!("Local streaming: skipping a pass: {err:#}"),
+            }
+        }
+    }
+
+    // Final pass: everything left in the window is typed without waiting for
+    // a second opinion.
+    while let Ok(samples) = audio_rx.try_recv() {
+        window.push(&samples);
+    }
+    if !window.audio.is_empty() {
+        let rest = match transcribe_window(&model, &window.audio) {
+            Ok(segments) => Ok(window.finish(&segments)),
+            Err(err) => {
+                eprintln!("Local streaming: final pass failed ({err:#}), transcribing the whole recording");
+                transcribe_window(&model, &window.recording())
+                    .map(|segments| window.finish_recording(&segments))
+            }
+        };
+        match rest {
+            Ok(rest) if !rest.is_empty() => typer.type_delta(&format!(" {rest}")),
+            Ok(_) => {}
+            // What was typed is still the transcript.
+            Err(err) => eprintln!("Local streaming: {err:#}"),
+        }
+    }
+
+    let text = typer.into_text();
+    println!("Local streaming finished. Transcript: {text:?}");
+    Ok(text)
+}
+
+/// One whisper segment: its words and where it ends in the window (samples).
+struct Segment {
+    words: Vec<String>,
+    end_sample: usize,
+}
+
+fn transcribe_window(model: &Model, audio: &[f32]) -> Result<Vec<Segment>> {
+    let transcript = model
+        .transcribe_pcm_s16le(audio, false, false, None)
+        .map_err(|e| anyhow!("Local transcription failed: {e:?}"))?;
+    Ok(transcript
+        .utterances
+        .iter()
+        .filter(|u| !is_non_speech(&u.text))
+        .map(|u| Segment {
+            words: u.text.split_whitespace().map(str::to_string).collect(),
+            end_sample: (u.stop.max(0) as usize) * SAMPLES_PER_TIMESTAMP_UNIT,
+        })
+        .collect())
+}
+
+/// Whisper emits markers like "[BLANK_AUDIO]" or "(wind blowing)" for silence
+/// and noise; those should never be typed.
+fn is_non_speech(text: &str) -> bool {
+    let t = text.trim();
+    t.is_empty()
+        || (t.starts_with('[') && t.ends_with(']'))
+        || (t.starts_with('(') && t.ends_with(')'))
+}
+
+/// Words are compared loosely so a pass that only changes case or trailing
+/// punctuation ("hello" -> "Hello,") still counts as agreeing.
+fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Audio window plus the local-agreement bookkeeping for it.
+#[derive(Default)]
+struct StreamingWindow {
+    /// Audio not yet trimmed away, as f32 at [`LOCAL_SAMPLE_RATE`].
+    audio: Vec<f32>,
+    /// Audio trimmed away, kept for a last pass over the whole recording.
+    trimmed: Vec<f32>,
+    /// Words typed from the trimmed audio.
+    trimmed_words: usize,
+    /// Words of the current window that have already been typed.
+    committed: Vec<String>,
+    /// Uncommitted tail of the previous pass, waiting for confirmation.
+    pending: Vec<String>,
+}
+
+impl StreamingWindow {
+    fn push(&mut self, samples: &[i16]) {
+        self.audio
+            .extend(samples.iter().map(|&s| s as f32 / i16::MAX as f32));
+    }
+
+    /// Feeds one transcription pass and returns the newly stable text (words
+    /// this pass and the previous one agree on), which the caller types.
+    fn advance(&mut self, segments: &[Segment]) -> String {
+        let words: Vec<String> = segments.iter().flat_map(|s| s.words.clone()).collect();
+        let new_words = &words[self.committed.len().min(words.len())..];
+
+        let agreed = new_words
+            .iter()
+            .zip(self.pending.iter())
+            .take_while(|(a, b)| normalize_word(a) == normalize_word(b))
+            .count();
+
+        let stable = new_words[..agreed].join(" ");
+        self.committed.extend_from_slice(&new_words[..agreed]);
+        self.pending = new_words[agreed..].to_vec();
+
+        if self.audio.len() > MAX_WINDOW_SAMPLES {
+            self.trim(segments);
+        }
+        stable
+    }
+
+    /// Returns every word of the final pass that was not typed yet.
+    fn finish(&mut self, segments: &[Segment]) -> String {
+        self.finish_after(segments, self.committed.len())
+    }
+
+    /// Everything recorded, trimmed audio included.
+    fn recording(&self) -> Vec<f32> {
+        [self.trimmed.as_slice(), &self.audio].concat()
+    }
+
+    /// Like [`Self::finish`], for a pass over [`Self::recording`].
+    fn finish_recording(&mut self, segments: &[Segment]) -> String {
+        self.finish_after(segments, self.trimmed_words + self.committed.len())
+    }
+
+    fn finish_after(&mut self, segments: &[Segment], typed: usize) -> String {
+        let words: Vec<String> = segments.iter().flat_map(|s| s.words.clone()).collect();
+        let rest = words[typed.min(words.len())..].join(" ");
+        self.committed.clear();
+        self.pending.clear();
+        rest
+    }
+
+    /// Drops audio (and the matching committed words) up to the end of the
+    /// last segment that has been typed in full. Cutting on a segment boundary
+    /// keeps whisper from seeing half a word at the start of the next pass.
+    fn trim(&mut self, segments: &[Segment]) {
+        let mut words_before = 0usize;
+        let mut cut: Option<(usize, usize)> = None;
+        for segment in segments {
+            words_before += segment.words.len();
+            if words_before > self.committed.len() {
+                break;
+            }
+            cut = Some((segment.end_sample, words_before));
+        }
+        if let Some((end_sample, word_count)) = cut {
+            let end_sample = end_sample.min(self.audio.len());
+            if end_sample == 0 {
+                return;
+            }
+            self.trimmed.extend(self.audio.drain(..end_sample));
+            self.committed.drain(..word_count);
+            self.trimmed_words += word_count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, end_secs: usize) -> Segment {
+        Segment {
+            words: text.split_whitespace().map(str::to_string).collect(),
+            end_sample: end_secs * LOCAL_SAMPLE_RATE as usize,
+        }
+    }
+
+    fn window(secs: usize) -> StreamingWindow {
+        StreamingWindow {
+            audio: vec![0.0; secs * LOCAL_SAMPLE_RATE as usize],
+            ..StreamingWindow::default()
+        }
+    }
+
+    #[test]
+    fn committed_prefix_only_grows() {
+        let mut window = window(3);
+        assert_eq!(window.advance(&[segment("hello there", 2)]), "");
+        assert_eq!(
+            window.advance(&[segment("Hello, there general", 3)]),
+            "Hello, there"
+        );
+        // A later pass that rewrites typed words can't take them back.
+        assert_eq!(
+            window.advance(&[segment("Yellow there general", 3)]),
+            "general"
+        );
+        assert_eq!(window.committed, ["Hello,", "there", "general"]);
+    }
+
+    #[test]
+    fn disagreeing_hypotheses_are_not_typed() {
+        let mut window = window(2);
+        assert_eq!(window.advance(&[segment("I scream", 2)]), "");
+        assert_eq!(window.advance(&[segment("ice cream", 2)]), "");
+        assert_eq!(
+            window.advance(&[segment("ice cream sundae", 2)]),
+            "ice cream"
+        );
+        assert_eq!(window.pending, ["sundae"]);
+    }
+
+    #[test]
+    fn trimming_keeps_the_committed_text() {
+        let mut window = window(21);
+        let first = [segment("one two", 10), segment("three four", 20)];
+        assert_eq!(window.advance(&first), "");
+        let second = [segment("one two", 10), segment("three five", 20)];
+        assert_eq!(window.advance(&second), "one two three");
+        // The first segment was typed in full, so its audio is dropped.
+        assert_eq!(window.audio.len(), 11 * LOCAL_SAMPLE_RATE as usize);
+        assert_eq!(window.committed, ["three"]);
+
+        // Passes over the trimmed window don't retype "three".
+        assert_eq!(window.advance(&[segment("three five six", 11)]), "five");
+        assert_eq!(window.recording().len(), 21 * LOCAL_SAMPLE_RATE as usize);
+        let recording = [segment("one two three five six seven", 21)];
+        assert_eq!(window.finish_recording(&recording), "six seven");
+    }
+
+    #[test]
+    fn finish_flushes_the_tail() {
+        let mut window = window(2);
+        window.advance(&[segment("hello there", 2)]);
+        window.advance(&[segment("hello there general", 2)]);
+        assert_eq!(
+            window.finish(&[segment("hello there general kenobi", 2)]),
+            "general kenobi"
+        );
+        assert!(window.committed.is_empty() && window.pending.is_empty());
+    }
+}
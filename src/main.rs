@@ -1,413 +1,88 @@
 // Prevents additional console window on Windows in release mode
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod app_state;
-mod config;
-mod easy_rdev_key;
-mod realtime;
-mod record;
-mod tauri_commands;
-mod transcribe;
-mod transcription_engine;
-
-use app_state::AppState;
-use config::AppConfig;
-use default_device_sink::DefaultDeviceSink;
-use parking_lot::Mutex;
-use rdev::{listen, Event, EventType};
-use rodio::{source::SineWave, Decoder, Source};
-use std::io::{BufReader, Cursor};
-use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
-use tauri::{
-    menu::{Menu, MenuItem},
-    tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Manager, Runtime, State,
-};
-use transcription_engine::TranscriptionEngine;
-
-static FAILED_BYTES: &[u8] = include_bytes!("../assets/failed.mp3");
-
-struct AppEngine {
-    engine: Arc<Mutex<Option<TranscriptionEngine>>>,
-}
-
-pub fn play_error_sound() {
-    let sink = DefaultDeviceSink::new();
-    if let Ok(decoder) = Decoder::new(BufReader::new(Cursor::new(FAILED_BYTES))) {
-        sink.append(decoder);
-    } else {
-        sink.append(
-            SineWave::new(440.0)
-                .take_duration(Duration::from_millis(150))
-                .amplify(0.20),
-        );
-    }
-    sink.sleep_until_end();
+use clap::Parser;
+use desk_talk::args::{ConfigOverrides, SWITCHES_HELP};
+use desk_talk::config::{self, AppConfig};
+use desk_talk::gui;
+use std::path::PathBuf;
+
+/// DeskTalk: hold a key, speak, and the text is typed where your cursor is.
+///
+/// Settings come from the saved config; flags override them for this run.
+#[derive(Parser, Debug)]
+#[command(version, after_help = SWITCHES_HELP)]
+struct Args {
+    /// Read and save the config at this path instead of the default location.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Start with this saved profile.
+    #[arg(long, conflicts_with = "config")]
+    profile: Option<String>,
+
+    /// Don't open the settings window; run from the tray only.
+    #[arg(long)]
+    headless: bool,
+
+    /// Print the effective config (after flags, without the API key) as JSON
+    /// and exit.
+    #[arg(long)]
+    print_config: bool,
+
+    #[command(flatten)]
+    overrides: ConfigOverrides,
 }
 
-fn start_global_event_listener(app_state: AppState) {
-    thread::spawn(move || {
-        println!("Global event listener started");
-
-        // Channel for error sound playback (avoid blocking rdev callback)
-        let (error_tx, error_rx): (flume::Sender<()>, flume::Receiver<()>) = flume::unbounded();
-
-        // Thread to play error sounds (so rdev callback isn't blocked)
-        thread::spawn(move || {
-            for _ in error_rx.iter() {
-                play_error_sound();
-            }
-        });
-
-        let mut ptt_key_pressed = false;
-
-        let callback = move |event: Event| {
-            // Check if engine has an active event sender (fast, cached check)
-            let sender_opt = app_state.event_sender();
-            let engine_ready = sender_opt.is_some();
-
-            // Forward events to engine if it's ready
-            if engine_ready {
-                if let Some(sender) = sender_opt {
-                    let _ = sender.send(event.clone());
-                }
-            }
-
-            // Track PTT key state and play error sound if engine is NOT ready
-            if let Some(ptt_key) = app_state.config.read().get_ptt_key() {
-                match event.event_type {
-                    EventType::KeyPress(key) => {
-                        if key == ptt_key && !ptt_key_pressed {
-                            ptt_key_pressed = true;
-                            // If engine doesn't have a sender, it's not ready - play error immediately
-                            if !engine_ready {
-                                println!(
-                                    "PTT pressed but engine is NOT ready - playing error sound"
-                                );
-                                let _ = error_tx.send(()); // Non-blocking send to error sound thread
-                            }
-                        }
-                    }
-                    EventType::KeyRelease(key) => {
-                        if key == ptt_key {
-                            ptt_key_pressed = false;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        };
-
-        if let Err(error) = listen(callback) {
-            eprintln!("Error in global event listener: {:?}", error);
-        }
-    });
-}
-
-fn auto_start_if_possible<R: Runtime>(app: &AppHandle<R>) {
-    let state = app.state::<AppState>();
-    let config = state.config.read().clone();
-
-    if config.get_ptt_key().is_none() {
-        println!("Auto-start skipped: no PTT key configured");
-        return;
-    }
-
-    if !config.use_local && config.api_key.is_none() {
-        println!("Auto-start skipped: no OpenAI API key configured");
-        return;
-    }
-
-    if config.use_local && config.local_model.is_none() {
-        println!("Auto-start skipped: no local model selected");
-        return;
-    }
+fn main() {
+    let args = Args::parse();
 
-    let engine_state = app.state::<AppEngine>();
-    if engine_state.engine.lock().is_some() {
-        println!("Auto-start skipped: engine already running");
-        return;
+    if let Some(path) = &args.config {
+        config::use_config_file(path.clone());
     }
-
-    println!("Auto-starting transcription engine...");
-    let engine = TranscriptionEngine::new(state.inner().clone());
-    match engine.start() {
-        Ok(_) => {
-            println!("Auto-start successful");
-            *engine_state.engine.lock() = Some(engine);
-            if let Some(tray) = app.tray_by_id("main") {
-                let _ = tray.set_tooltip(Some("DeskTalk - Running"));
-            }
+    let mut config = match AppConfig::load_profile(args.profile.as_deref()) {
+        Ok(config) => config,
+        // An explicitly chosen config has to load; the default one falls back.
+        Err(err) if args.config.is_some() || args.profile.is_some() => {
+            eprintln!("Error: {:#}", err);
+            std::process::exit(1);
         }
         Err(err) => {
-            println!("Auto-start failed: {}", err);
-        }
-    }
-}
-
-#[tauri::command]
-async fn start_engine<R: Runtime>(
-    app_handle: AppHandle<R>,
-    state: State<'_, AppState>,
-    engine_state: State<'_, AppEngine>,
-) -> Result<(), String> {
-    if engine_state.engine.lock().is_some() {
-        println!("Engine already running");
-        return Ok(());
-    }
-
-    println!("Starting transcription engine...");
-
-    // Debug: print config
-    let config = state.config.read();
-    println!("Config - PTT Key: {:?}", config.ptt_key);
-    println!("Config - Device: {}", config.device);
-    println!("Config - Use Local: {}", config.use_local);
-    println!("Config - Has API Key: {}", config.api_key.is_some());
-    drop(config);
-
-    let engine = TranscriptionEngine::new(state.inner().clone());
-
-    match engine.start() {
-        Ok(_) => {
-            println!("Transcription engine started successfully!");
-            *engine_state.engine.lock() = Some(engine);
-
-            // Update tray icon to show running state
-            if let Some(tray) = app_handle.tray_by_id("main") {
-                let _ = tray.set_tooltip(Some("DeskTalk - Running"));
-            }
-
-            Ok(())
-        }
-        Err(e) => {
-            println!("Failed to start engine: {}", e);
-            Err(e.to_string())
-        }
-    }
-}
-
-#[tauri::command]
-fn stop_engine<R: Runtime>(
-    app_handle: AppHandle<R>,
-    engine_state: State<AppEngine>,
-) -> Result<(), String> {
-    if let Some(engine) = engine_state.engine.lock().take() {
-        engine.stop();
-    }
-
-    // Update tray icon to show stopped state
-    if let Some(tray) = app_handle.tray_by_id("main") {
-        let _ = tray.set_tooltip(Some("DeskTalk - Stopped"));
-    }
-
-    Ok(())
-}
-
-fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<Menu<R>> {
-    let open_settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-
-    Menu::with_items(app, &[&open_settings, &quit_item])
-}
-
-fn handle_tray_event<R: Runtime>(app: &AppHandle<R>, event: TrayIconEvent) {
-    if let TrayIconEvent::Click {
-        button: MouseButton::Left,
-        button_state: MouseButtonState::Up,
-        ..
-    } = event
-    {
-        if let Some(window) = app.get_webview_window("settings") {
-            let _ = window.show();
-            let _ = window.set_focus();
-        }
-    }
-}
-
-fn handle_menu_event<R: Runtime>(app: &AppHandle<R>, id: &str) {
-    match id {
-        "settings" => {
-            if let Some(window) = app.get_webview_window("settings") {
-                let _ = window.show();
-                let _ = window.set_focus();
-            }
+            eprintln!("Failed to load config, using defaults: {:#}", err);
+            AppConfig::default()
         }
-        "quit" => {
-            app.exit(0);
+    };
+    args.overrides.apply(&mut config);
+
+    if args.print_config {
+        let mut printable = config.clone();
+        printable.api_key = None;
+        match serde_json::to_string_pretty(&printable) {
+            Ok(json) => println!("{}", json),
+            Err(err) => eprintln!("Error: {}", err),
         }
-        _ => {}
+        return;
     }
-}
 
-fn main() {
-    // Load configuration
-    let mut config = AppConfig::load().unwrap_or_default();
     println!(
         "Main: Initial config has API key: {}",
         config.api_key.is_some()
     );
-
-    // Parse --parallel flag from CLI args (overrides config)
-    let args: Vec<String> = std::env::args().collect();
-    if let Some(pos) = args.iter().position(|a| a == "--parallel") {
-        if let Some(val) = args.get(pos + 1) {
-            if let Ok(n) = val.parse::<usize>() {
-                config.parallel = n.clamp(1, 5);
-                println!("Parallel transcription set to {} via CLI", config.parallel);
-            }
-        }
-    }
-
-    // Parse --realtime / --no-realtime flags (override saved config). When
-    // neither is passed, the saved setting is kept.
-    if args.iter().any(|a| a == "--realtime") {
-        config.realtime = true;
-        println!("Realtime streaming enabled via CLI");
-    } else if args.iter().any(|a| a == "--no-realtime") {
-        config.realtime = false;
-        println!("Realtime streaming disabled via CLI");
-    }
-
-    // Parse --realtime-delay <minimal|low|medium|high|xhigh> (overrides config).
-    if let Some(pos) = args.iter().position(|a| a == "--realtime-delay") {
-        if let Some(val) = args.get(pos + 1) {
-            let sanitized = config::sanitize_realtime_delay(val);
-            if sanitized.eq_ignore_ascii_case(val) {
-                config.realtime_delay = sanitized;
-                println!("Realtime delay set to {} via CLI", config.realtime_delay);
-            } else {
-                println!(
-                    "Invalid --realtime-delay '{}'. Valid: minimal, low, medium, high, xhigh. Using {}.",
-                    val, config.realtime_delay
-                );
-            }
-        }
-    }
-
-    // Parse --end-punctuation <none|period|smart> (overrides config). This
-    // single valued flag replaces the old mutually-exclusive --period /
-    // --smart-punctuation booleans. Aliases kept for convenience.
-    if let Some(pos) = args.iter().position(|a| a == "--end-punctuation") {
-        if let Some(val) = args.get(pos + 1) {
-            let sanitized = config::sanitize_end_punctuation(val);
-            if sanitized.eq_ignore_ascii_case(val) {
-                config.end_punctuation = sanitized;
-                println!("End punctuation set to {} via CLI", config.end_punctuation);
-            } else {
-                println!(
-                    "Invalid --end-punctuation '{}'. Valid: none, period, smart. Using {}.",
-                    val, config.end_punctuation
-                );
-            }
-        }
-    } else if args.iter().any(|a| a == "--smart-punctuation") {
-        config.end_punctuation = "smart".to_string();
-        println!("End punctuation set to smart via CLI");
-    } else if args.iter().any(|a| a == "--no-smart-punctuation") {
-        config.end_punctuation = "none".to_string();
-        println!("End punctuation set to none via CLI");
-    } else if args.iter().any(|a| a == "--period") {
-        config.end_punctuation = "period".to_string();
-        println!("End punctuation set to period via CLI");
-    }
-
     println!("Parallel transcription: {}", config.parallel);
     println!("Realtime streaming: {}", config.realtime);
     println!("Realtime delay: {}", config.realtime_delay);
+    println!("Realtime pre-warm: {}", config.realtime_prewarm);
+    println!("Realtime reconcile: {}", config.realtime_reconcile);
+    println!("Realtime output: {}", config.realtime_output);
+    println!("Output: {}", config.output_mode());
+    println!("Paste shortcut: {}", config.paste_shortcut());
+    println!("Clipboard transient: {}", config.clipboard_transient);
+    println!("Paste restore delay: {} ms", config.paste_restore_delay_ms);
+    if !config.app_rules.is_empty() {
+        println!("App rules: {}", config.app_rules.len());
+    }
+    println!("Local streaming: {}", config.local_streaming);
     println!("End punctuation: {}", config.end_punctuation);
 
-    let app_state = AppState::new(config);
-
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
-            // When a second instance is launched, bring the existing window to front
-            println!("Second instance detected - bringing existing window to front");
-            if let Some(window) = app.get_webview_window("settings") {
-                let _ = window.show();
-                let _ = window.set_focus();
-                let _ = window.unminimize();
-            }
-        }))
-        .manage(app_state)
-        .manage(AppEngine {
-            engine: Arc::new(Mutex::new(None)),
-        })
-        .invoke_handler(tauri::generate_handler![
-            tauri_commands::get_config,
-            tauri_commands::save_config,
-            tauri_commands::get_statistics,
-            tauri_commands::get_audio_devices,
-            tauri_commands::get_available_ptt_keys,
-            tauri_commands::start_transcription,
-            tauri_commands::stop_transcription,
-            tauri_commands::is_running,
-            tauri_commands::validate_api_key,
-            tauri_commands::test_openai_key,
-            tauri_commands::detect_key_press,
-            tauri_commands::open_url,
-            start_engine,
-            stop_engine,
-        ])
-        .on_menu_event(|app, event| {
-            handle_menu_event(app, event.id().as_ref());
-        })
-        .setup(|app| {
-            let handle = app.handle().clone();
-            let handle_for_tray = app.handle().clone();
-            let handle_for_auto_start = app.handle().clone();
-
-            // Check if we should start minimized BEFORE creating anything
-            let state = app.state::<AppState>();
-            let should_minimize = state.config.read().start_minimized;
-
-            // Create tray menu
-            let menu = create_tray_menu(&handle)?;
-            let icon = app.default_window_icon().cloned();
-
-            let mut builder = TrayIconBuilder::with_id("main")
-                .menu(&menu)
-                .tooltip("DeskTalk");
-            if let Some(icon) = icon {
-                builder = builder.icon(icon);
-            }
-
-            let _tray = builder
-                .on_tray_icon_event(move |_tray, event| {
-                    handle_tray_event(&handle_for_tray, event);
-                })
-                .build(app)?;
-
-            if let Some(window) = app.get_webview_window("settings") {
-                let window_handle = window.clone();
-                window.on_window_event(move |event| {
-                    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                        api.prevent_close();
-                        let _ = window_handle.hide();
-                    }
-                });
-
-                // Show window if NOT configured to start minimized
-                if !should_minimize {
-                    let _ = window.show();
-                    println!("Starting with window visible");
-                } else {
-                    println!("Starting minimized to tray");
-                }
-            }
-
-            // Start global event listener for PTT handling
-            let state_for_listener = app.state::<AppState>();
-            start_global_event_listener(state_for_listener.inner().clone());
-
-            // Attempt to auto-start transcription if configuration is ready
-            auto_start_if_possible(&handle_for_auto_start);
-
-            Ok(())
-        })
-        .run(tauri::generate_context!("tauri.conf.json"))
-        .expect("error while running tauri application");
+    gui::run(config, args.profile, args.headless);
 }
@@ -1,88 +1,35 @@
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
 use async_openai::Client;
-use dotenvy::dotenv;
-use enigo::{Enigo, KeyboardControllable};
-use std::env;
-use tempfile::tempdir;
-mod transcribe;
-use std::thread::{self, sleep};
-use transcribe::trans;
-mod record;
-use clap::{Parser, Subcommand, ValueEnum};
-use clipboard::ClipboardContext;
-use clipboard::ClipboardProvider;
+use clap::{Parser, Subcommand};
 use cpal::traits::{DeviceTrait, HostTrait};
-use default_device_sink::DefaultDeviceSink;
+use desk_talk::app_state::AppState;
+use desk_talk::args::{ConfigOverrides, SWITCHES_HELP};
+use desk_talk::config::AppConfig;
+use desk_talk::config_share::{self, ImportMode};
+use desk_talk::control::EngineController;
+use desk_talk::credentials::{self, DEFAULT_CREDENTIAL};
+use desk_talk::daemon;
+use desk_talk::file_transcribe::{self, OutputFormat};
+use desk_talk::listener;
+use desk_talk::spending;
+use desk_talk::transcribe::trans;
 use rdev::{listen, Event};
-use record::rec;
-use rodio::source::{SineWave, Source};
-use rodio::Decoder;
-use std::collections::VecDeque;
-use std::error::Error;
-use std::io::{BufReader, Cursor};
-use std::sync::mpsc;
-use std::time::Duration;
-mod easy_rdev_key;
-use crate::easy_rdev_key::PTTKey;
-use mutter::ModelType;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 
+/// Runs the same engine as the desktop app, without a window. Settings come
+/// from the app's config file (or `--profile`); flags override them for this
+/// run only.
 #[derive(Parser, Debug)]
-#[command(version)]
+#[command(version, after_help = SWITCHES_HELP)]
 struct Opt {
-    /// The audio device to use for recording. Leaving this blank will use the default device.
-    #[arg(short, long, default_value_t = String::from("default"))]
-    device: String,
-
-    /// Your OpenAI API key
-    #[arg(short, long)]
-    api_key: Option<String>,
-
-    /// The push to talk key
-    #[arg(short, long)]
-    ptt_key: Option<PTTKey>,
-
-    /// Use local whisper model instead of OpenAI API
-    #[arg(long)]
-    local: bool,
-
-    /// The local whisper model to use. Requires --local.
-    #[arg(long, value_enum, requires = "local")]
-    model: Option<LocalModel>,
-
-    /// Ensures the first letter of the transcription is capitalized.
-    #[arg(short, long)]
-    cap_first: bool,
-
-    /// Ensures the transcription ends with a space character. This lets you transcribe repeatedly without typing a space character between transcriptions to separate the words.
-    ///
-    /// This is a flag and not default behavior because in some natural languages it doesn't make sense to put a space after the transcription.
-    #[arg(short, long)]
-    space: bool,
-
-    /// Passing this flag will emulate the keyboard for typing the characters, instead of pressing Ctrl-V and pasting the text, which is the default behavior.
-    /// This may be needed to pass text to a terminal, which would not accept pasting or something else.
-    #[arg(short, long)]
-    type_chars: bool,
-
-    /// If the transcription is missing punctuation (no periods, question marks, or exclamation points),
-    /// use an LLM to add punctuation. Requires OPENAI_API_KEY to be set.
-    #[arg(long)]
-    punctuation: bool,
-
-    /// Always add a period at the end if the transcription doesn't already end with punctuation (.!?)
+    /// Use this saved config profile instead of the default config.
     #[arg(long)]
-    period: bool,
-
-    /// Send multiple transcription requests in parallel and use the first
-    /// result that comes back. Higher values reduce latency at the cost of
-    /// extra API usage (e.g. 3 triples cost). Defaults to 1 (no parallelism).
-    #[arg(long, default_value_t = 1)]
-    parallel: usize,
+    profile: Option<String>,
 
-    /// The push to talk key.
-    /// Use this if you want to use a key that is not supported by the PTTKey enum.
-    #[arg(short, long, conflicts_with("ptt_key"))]
-    special_ptt_key: Option<u32>,
+    #[command(flatten)]
+    overrides: ConfigOverrides,
 
     #[clap(subcommand)]
     pub subcommands: Option<SubCommands>,
@@ -94,461 +41,405 @@ pub enum SubCommands {
     ShowKeyPresses,
     /// Lists the audio input devices on your system.
     ListDevices,
+    /// Transcribes audio files (WAV, MP3, FLAC) with the selected backend
+    /// (OpenAI, racing with --parallel, or --local). Directories are searched
+    /// recursively.
+    Transcribe {
+        /// Audio files and/or directories.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output format.
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Write `<name>.<format>` per input file into this directory instead of
+        /// printing to stdout.
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Audio is sent in chunks of about this many seconds, cut at pauses.
+        /// Each chunk becomes one SRT/VTT cue.
+        #[arg(long, default_value_t = 30)]
+        chunk_secs: u64,
+    },
+    /// Writes the settings (after flags, without the API key) to a file that
+    /// can be shared and imported elsewhere.
+    ExportConfig {
+        /// File to write.
+        path: PathBuf,
+    },
+    /// Applies a config file (e.g. from `export-config`) to the saved
+    /// settings of the default config or `--profile`. Nothing is saved if
+    /// the result has invalid settings.
+    ImportConfig {
+        /// File to import.
+        path: PathBuf,
+
+        /// How the file's settings are combined with yours.
+        #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+
+        /// Only report what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manages the API keys stored in the system keyring.
+    Credentials {
+        #[command(subcommand)]
+        command: CredentialCommand,
+    },
+    /// Runs in the background without a window or tray, controlled through
+    /// the control socket, D-Bus and signals (SIGUSR1 toggles recording,
+    /// SIGUSR2 starts/stops the engine, SIGHUP reloads the config, SIGTERM
+    /// exits).
+    Daemon {
+        /// Stay attached to the terminal and log to stdout/stderr (for
+        /// systemd and other supervisors).
+        #[arg(long)]
+        foreground: bool,
+
+        /// Where the background process logs to [default: desk-talk.log in
+        /// the config directory].
+        #[arg(long, conflicts_with = "foreground")]
+        log_file: Option<PathBuf>,
+    },
 }
 
-#[derive(ValueEnum, Clone, Debug, Copy)]
-pub enum LocalModel {
-    TinyEn,
-    Tiny,
-    BaseEn,
-    Base,
-    SmallEn,
-    Small,
-    MediumEn,
-    Medium,
-    LargeV1,
-    LargeV2,
-    LargeV3,
-}
-
-impl From<LocalModel> for ModelType {
-    fn from(model: LocalModel) -> Self {
-        match model {
-            LocalModel::TinyEn => ModelType::TinyEn,
-            LocalModel::Tiny => ModelType::Tiny,
-            LocalModel::BaseEn => ModelType::BaseEn,
-            LocalModel::Base => ModelType::Base,
-            LocalModel::SmallEn => ModelType::SmallEn,
-            LocalModel::Small => ModelType::Small,
-            LocalModel::MediumEn => ModelType::MediumEn,
-            LocalModel::Medium => ModelType::Medium,
-            LocalModel::LargeV1 => ModelType::LargeV1,
-            LocalModel::LargeV2 => ModelType::LargeV2,
-            LocalModel::LargeV3 => ModelType::LargeV3,
-        }
-    }
+/// Keys are read from standard input, so they don't end up in the shell
+/// history: `echo $KEY | desk-talk-cli credentials add work`.
+#[derive(Debug, Subcommand)]
+pub enum CredentialCommand {
+    /// Lists the stored keys (names and dates, not the keys).
+    List,
+    /// Stores a new key.
+    Add {
+        name: String,
+
+        /// Base URL of an OpenAI-compatible API to use with this key.
+        #[arg(long)]
+        api_base: Option<String>,
+    },
+    /// Replaces a stored key with a new one.
+    Rotate { name: String },
+    /// Deletes a stored key.
+    Remove { name: String },
+    /// Makes the default config (or `--profile`) use a stored key.
+    Use { name: String },
 }
 
-/// Returns true if the text is missing sentence-ending punctuation (. ! ?)
-fn needs_punctuation_fix(text: &str) -> bool {
-    !text.chars().any(|c| matches!(c, '.' | '!' | '?'))
-}
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::parse();
 
-fn capitalize_first_letter(s: &mut String) {
-    let mut c = s.chars();
-    if let Some(f) = c.next() {
-        let uppercase: String = f.to_uppercase().collect();
-        let first_char_len = f.len_utf8();
-        s.replace_range(0..first_char_len, &uppercase);
+    let mut config = AppConfig::load_profile(opt.profile.as_deref())?;
+    opt.overrides.apply(&mut config);
+    if config.api_key.is_none() {
+        config.api_key = credentials::key_in_working_dir();
     }
-}
-
-fn truncate_to_secs(d: Duration) -> Duration {
-    Duration::from_secs(d.as_secs())
-}
 
-// Using humantime for human-readable durations
+    match opt.subcommands {
+        Some(SubCommands::ShowKeyPresses) => {
+            println!("Press keys to see their codes. Press Ctrl+C to exit. Once you've figured out what key you want to use for push to talk, pass it to desk-talk using the --ptt-key argument. Or pass the number to the --special-ptt-key argument if the key is Unknown(number).");
 
-static TICK_BYTES: &[u8] = include_bytes!("../assets/tick.mp3");
-static FAILED_BYTES: &[u8] = include_bytes!("../assets/failed.mp3");
+            fn show_keys_callback(event: Event) {
+                if let rdev::EventType::KeyPress(key) = event.event_type {
+                    println!("Key pressed: {:?}", key);
+                }
+            }
 
-fn tick_loop(stop_rx: mpsc::Receiver<()>) {
-    let tick_sink = DefaultDeviceSink::new();
-    loop {
-        if stop_rx.try_recv().is_ok() {
-            tick_sink.stop();
-            break;
+            // This will block.
+            if let Err(error) = listen(show_keys_callback) {
+                println!("Error: {:?}", error)
+            }
+        }
+        Some(SubCommands::ListDevices) => {
+            let host = cpal::default_host();
+            let devices = host
+                .input_devices()
+                .context("Failed to get list of input devices")?;
+
+            for device in devices {
+                let device_name = match device.name() {
+                    Ok(name) => name,
+                    Err(err) => {
+                        println!("Error: Failed to get device name: {:?}", err);
+                        continue;
+                    }
+                };
+                println!("{:?}", device_name);
+            }
+        }
+        Some(SubCommands::Transcribe {
+            files,
+            format,
+            output_dir,
+            chunk_secs,
+        }) => transcribe_files(&config, &files, format, output_dir, chunk_secs)?,
+        Some(SubCommands::ExportConfig { path }) => {
+            config_share::export(&config, &path)?;
+            println!("Exported settings to {}", path.display());
         }
-        if tick_sink.empty() {
-            let cursor = Cursor::new(TICK_BYTES);
-            if let Ok(decoder) = Decoder::new(BufReader::new(cursor)) {
-                tick_sink.stop();
-                tick_sink.append(decoder);
+        Some(SubCommands::ImportConfig {
+            path,
+            mode,
+            dry_run,
+        }) => import_config(opt.profile.as_deref(), &path, mode, dry_run)?,
+        Some(SubCommands::Credentials { command }) => {
+            manage_credentials(opt.profile.as_deref(), &config, command)?
+        }
+        Some(SubCommands::Daemon {
+            foreground,
+            log_file,
+        }) => {
+            if config.get_ptt_key().is_none() {
+                bail!("No push to talk key configured. Set one in the app or pass --ptt-key / --special-ptt-key.");
+            }
+            if foreground || daemon::is_detached() {
+                daemon::run(config, opt.profile)?;
             } else {
-                tick_sink.stop();
-                tick_sink.append(
-                    SineWave::new(880.0)
-                        .take_duration(Duration::from_millis(50))
-                        .amplify(0.20),
+                let log_file = match log_file {
+                    Some(path) => path,
+                    None => daemon::default_log_file()?,
+                };
+                let pid = daemon::detach(&log_file)?;
+                println!(
+                    "desk-talk daemon running (pid {}), logging to {}",
+                    pid,
+                    log_file.display()
                 );
             }
         }
-        std::thread::sleep(Duration::from_millis(100));
+        // Run push-to-talk transcription
+        None => {
+            if config.get_ptt_key().is_none() {
+                bail!("No push to talk key configured. Set one in the app or pass --ptt-key / --special-ptt-key.");
+            }
+            let app_state = AppState::new(config);
+            let controller = EngineController::new(app_state.clone());
+            controller.spawn_services();
+            controller.start()?;
+
+            // This blocks for as long as the listener runs.
+            let _ = listener::start_global_event_listener(app_state).join();
+        }
     }
+    Ok(())
 }
 
-fn play_failure_sound() {
-    let sink = DefaultDeviceSink::new();
-    if let Ok(decoder) = Decoder::new(BufReader::new(Cursor::new(FAILED_BYTES))) {
-        sink.append(decoder);
-    } else {
-        sink.append(
-            SineWave::new(440.0)
-                .take_duration(Duration::from_millis(150))
-                .amplify(0.20),
-        );
+fn manage_credentials(
+    profile: Option<&str>,
+    config: &AppConfig,
+    command: CredentialCommand,
+) -> anyhow::Result<()> {
+    match command {
+        CredentialCommand::List => {
+            let in_use = config.credential_name();
+            let date = |at: Option<i64>| {
+                at.and_then(|at| chrono::DateTime::from_timestamp(at, 0))
+                    .map_or("-".to_string(), |at| at.format("%Y-%m-%d").to_string())
+            };
+            for credential in credentials::list()? {
+                println!(
+                    "{}{}\tadded {}\trotated {}\t{}",
+                    if credential.name == in_use {
+                        "* "
+                    } else {
+                        "  "
+                    },
+                    credential.name,
+                    date(credential.created_at),
+                    date(credential.rotated_at),
+                    credential.api_base.as_deref().unwrap_or("OpenAI")
+                );
+            }
+        }
+        CredentialCommand::Add { name, api_base } => {
+            if credentials::get(&name)?.is_some() {
+                bail!(
+                    "There already is a key named \"{}\"; use rotate to replace it",
+                    name
+                );
+            }
+            let key = read_key(&name)?;
+            credentials::save_key(&name, &key, api_base)?;
+            println!("Stored key \"{}\"", name);
+        }
+        CredentialCommand::Rotate { name } => {
+            if credentials::get(&name)?.is_none() {
+                bail!("No API key named \"{}\"", name);
+            }
+            let key = read_key(&name)?;
+            credentials::save_key(&name, &key, None)?;
+            if config.write_env_file && name == config.credential_name() {
+                credentials::save_key_to_env(&key)?;
+            }
+            println!("Replaced key \"{}\"", name);
+        }
+        CredentialCommand::Remove { name } => {
+            credentials::remove(&name)?;
+            println!("Removed key \"{}\"", name);
+            if name == config.credential_name() {
+                eprintln!(
+                    "Warning: this config uses that key; choose another with `credentials use`"
+                );
+            }
+        }
+        CredentialCommand::Use { name } => {
+            if credentials::get(&name)?.is_none() {
+                bail!("No API key named \"{}\"", name);
+            }
+            let mut saved = AppConfig::load_profile(profile)?;
+            saved.credential = (name != DEFAULT_CREDENTIAL).then_some(name.clone());
+            saved.save_profile(profile)?;
+            match profile {
+                Some(profile) => println!("Profile {} now uses key \"{}\"", profile, name),
+                None => println!("Now using key \"{}\"", name),
+            }
+        }
     }
-    sink.sleep_until_end();
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let opt = Opt::parse();
-    let _ = dotenv();
-
-    let mut clipboard: ClipboardContext = ClipboardProvider::new().unwrap();
-
-    match opt.subcommands {
-        Some(subcommand) => {
-            match subcommand {
-                SubCommands::ShowKeyPresses => {
-                    println!("Press keys to see their codes. Press Ctrl+C to exit. Once you've figured out what key you want to use for push to talk, pass it to desk-talk using the --ptt-key argument. Or pass the number to the --special-ptt-key argument if the key is Unknown(number).");
-
-                    fn show_keys_callback(event: Event) {
-                        if let rdev::EventType::KeyPress(key) = event.event_type {
-                            println!("Key pressed: {:?}", key);
-                        }
-                    }
-
-                    // This will block.
-                    if let Err(error) = listen(show_keys_callback) {
-                        println!("Error: {:?}", error)
-                    }
-                }
-                SubCommands::ListDevices => {
-                    let host = cpal::default_host();
-
-                    let test = host.default_output_device().unwrap();
-
-                    println!("default output_device: {:?}", test.name());
-                    // Set up the input device and stream with the default input config.
-                    host.default_input_device();
-                    let devices = host
-                        .input_devices()
-                        .context("Failed to get list of input devices")?;
-
-                    for device in devices {
-                        let device_name = match device.name() {
-                            Ok(name) => name,
-                            Err(err) => {
-                                println!("Error: Failed to get device name: {:?}", err);
-                                continue;
-                            }
-                        };
-                        println!("{:?}", device_name);
-                    }
+/// Reads an API key from standard input.
+fn read_key(name: &str) -> anyhow::Result<String> {
+    if io::stdin().is_terminal() {
+        eprint!("API key for \"{}\": ", name);
+    }
+    let mut key = String::new();
+    io::stdin().read_line(&mut key)?;
+    let key = key.trim();
+    if key.is_empty() {
+        bail!("No API key given");
+    }
+    Ok(key.to_string())
+}
 
-                    // devices
-                    //     .filter_map(|device| device.name().ok())
-                    //     .for_each(|device_name| println!("{:?}", device_name));
-                }
+fn import_config(
+    profile: Option<&str>,
+    path: &Path,
+    mode: ImportMode,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    // The saved settings, not the ones changed by flags for this run.
+    let current = AppConfig::load_profile(profile)?;
+    let imported = config_share::import(&current, path, mode)
+        .and_then(|imported| imported.validate().map(|()| imported));
+    let imported = match imported {
+        Ok(imported) => imported,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("Invalid setting: {}", error);
             }
+            bail!("{} was not imported", path.display());
+        }
+    };
 
-            Ok(())
+    let changed = config_share::changed_settings(&current, &imported);
+    let managed = config_share::managed_settings()?.unwrap_or_default();
+    if changed.is_empty() {
+        println!("No settings would change");
+    } else {
+        println!("Changed settings:");
+    }
+    for name in &changed {
+        if managed.contains_key(name) {
+            println!("  {} (set by the managed config, no effect)", name);
+        } else {
+            println!("  {}", name);
         }
-        // Run transcription
-        None => {
-            // figure out ptt key
-            let ptt_key = match opt.ptt_key {
-                Some(ptt_key) => ptt_key.into(),
-                None => match opt.special_ptt_key {
-                    Some(special_ptt_key) => rdev::Key::Unknown(special_ptt_key),
-                    None => {
-                        println!("No push to talk key specified. Please pass a key using the --ptt-key argument or the --special-ptt-key argument.");
-                        return Ok(());
-                    }
-                },
-            };
+    }
+    if dry_run {
+        println!("Dry run, nothing saved");
+        return Ok(());
+    }
+    imported.save_profile(profile)?;
+    match profile {
+        Some(name) => println!("Imported {} into profile {}", path.display(), name),
+        None => println!("Imported {}", path.display()),
+    }
+    Ok(())
+}
 
-            if !opt.local {
-                if let Some(api_key) = opt.api_key {
-                    env::set_var("OPENAI_API_KEY", api_key);
-                }
+fn transcribe_files(
+    config: &AppConfig,
+    files: &[PathBuf],
+    format: OutputFormat,
+    output_dir: Option<PathBuf>,
+    chunk_secs: u64,
+) -> anyhow::Result<()> {
+    let budget = spending::exceeded(config);
+    if let Some(budget) = budget {
+        eprintln!("Warning: {}", spending::describe(config, budget));
+    }
+    let config = &spending::limit(config.clone(), budget);
+    let model = if config.use_local {
+        let model = config
+            .local_model
+            .ok_or_else(|| anyhow!("No local model selected. Pass --model."))?
+            .model_type();
+        Some(trans::load_or_download_model(&model).map_err(|err| anyhow!("{}", err))?)
+    } else {
+        if config.api_key.is_none() {
+            bail!("No OpenAI API key configured. Set one in the app, pass --api-key or set OPENAI_API_KEY.");
+        }
+        None
+    };
+    if let Some(dir) = &output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
 
-                if env::var("OPENAI_API_KEY").is_err() {
-                    println!("OPENAI_API_KEY not set. Please pass your API key as an argument or assign is to the 'OPENAI_API_KEY' env var using terminal or .env file.");
-                    return Ok(());
-                }
-            } else if opt.model.is_none() {
-                println!("--model must be specified when using --local");
-                return Ok(());
+    let client = Client::with_config(config.openai_config()?);
+    let runtime = tokio::runtime::Runtime::new()?;
+    let inputs = file_transcribe::collect_inputs(files)?;
+    let outputs = match &output_dir {
+        Some(dir) => Some(file_transcribe::output_paths(
+            &inputs,
+            dir,
+            format.extension(),
+        )?),
+        None => None,
+    };
+    let mut failed = 0;
+    for (i, input) in inputs.iter().enumerate() {
+        let transcript = file_transcribe::transcribe_file(input, chunk_secs, |chunk, secs| {
+            let result = match &model {
+                Some(model) => trans::transcribe_local_with(model, chunk),
+                None => runtime.block_on(trans::transcribe_racing(
+                    &client,
+                    chunk,
+                    config.parallel,
+                    secs,
+                )),
+            };
+            result.map_err(|err| anyhow!("{}", err))
+        });
+        let transcript = match transcript {
+            Ok(transcript) => transcript,
+            Err(err) => {
+                eprintln!("Error: Failed to transcribe {}: {:?}", input.display(), err);
+                failed += 1;
+                continue;
             }
-
-            let (tx, rx): (flume::Sender<Event>, flume::Receiver<Event>) = flume::unbounded();
-
-            // create key handler thread
-            thread::spawn(move || {
-                let mut recorder = rec::Recorder::new();
-                let client = Client::new();
-                let runtime = tokio::runtime::Runtime::new()
-                    .context("Failed to create tokio runtime")
-                    .unwrap();
-                let mut enigo = Enigo::new();
-
-                // Track rolling average of WPM
-                let mut wpm_history: VecDeque<f64> = VecDeque::new();
-                let mut wpm_sum: f64 = 0.0;
-                const WPM_ROLLING_MAX: usize = 1000;
-                let mut total_words_transcribed: usize = 0;
-                let mut total_recording_duration: Duration = Duration::from_secs(0);
-
-                let tmp_dir = tempdir().unwrap();
-                // println!("{:?}", tmp_dir.path());
-                let voice_tmp_path = tmp_dir.path().join("voice_tmp.wav");
-
-                let mut recording_start = std::time::SystemTime::now();
-                let mut key_pressed = false;
-                let key_to_check = ptt_key;
-
-                for event in rx.iter() {
-                    // println!("Received: {:?}", event);
-                    match event.event_type {
-                        rdev::EventType::KeyPress(key) => {
-                            if key == key_to_check && !key_pressed {
-                                key_pressed = true;
-                                // handle key press
-                                recording_start = std::time::SystemTime::now();
-                                match recorder.start_recording(&voice_tmp_path, Some(&opt.device)) {
-                                    Ok(_) => (),
-                                    Err(err) => {
-                                        println!("Error: Failed to start recording: {:?}", err)
-                                    }
-                                }
-                            }
-                        }
-                        rdev::EventType::KeyRelease(key) => {
-                            if key == key_to_check && key_pressed {
-                                key_pressed = false;
-                                // handle key release
-
-                                // get elapsed time since recording started
-                                let elapsed = match recording_start.elapsed() {
-                                    Ok(elapsed) => elapsed,
-                                    Err(err) => {
-                                        println!("Error: Failed to get elapsed recording time. Skipping transcription: \n\n{}",err);
-                                        continue;
-                                    }
-                                };
-                                match recorder.stop_recording() {
-                                    Ok(_) => (),
-                                    Err(err) => {
-                                        println!("Error: Failed to stop recording: {:?}", err);
-                                        continue;
-                                    }
-                                }
-
-                                // future::timeout(
-                                //     Duration::from_secs(10),
-                                //     trans::transcribe(&client, &voice_tmp_path),
-                                // )
-                                // .await;
-
-                                // Whisper API can't handle less than 0.1 seconds of audio.
-                                // So we'll only transcribe if the recording is longer than 0.2 seconds.
-                                if elapsed.as_secs_f32() > 0.2 {
-                                    let (tick_tx, tick_rx) = mpsc::channel();
-                                    let tick_handle = thread::spawn(move || tick_loop(tick_rx));
-
-                                    let transcription_result = if opt.local {
-                                        let model = opt
-                                            .model
-                                            .expect("--model required when --local is used");
-                                        trans::transcribe_local(&voice_tmp_path, model.into())
-                                    } else {
-                                        runtime.block_on(trans::transcribe_racing(
-                                            &client,
-                                            &voice_tmp_path,
-                                            opt.parallel,
-                                            elapsed.as_secs_f64(),
-                                        ))
-                                    };
-
-                                    let mut transcription = match transcription_result {
-                                        Ok(transcription) => transcription,
-                                        Err(err) => {
-                                            // Stop ticking before playing error sound
-                                            let _ = tick_tx.send(());
-                                            let _ = tick_handle.join();
-                                            println!(
-                                                "Error: Failed to transcribe audio: {:?}",
-                                                err
-                                            );
-                                            play_failure_sound();
-                                            continue;
-                                        }
-                                    };
-
-                                    // Transcription post processing
-                                    // Note: tick sound continues during punctuation API call
-                                    {
-                                        // Remove ellipses first (Whisper sometimes adds these)
-                                        transcription = transcription.replace("...", "");
-
-                                        // Fix punctuation if enabled and text is missing it
-                                        if opt.punctuation && needs_punctuation_fix(&transcription)
-                                        {
-                                            println!(
-                                                "Transcription missing punctuation, fixing..."
-                                            );
-                                            match runtime.block_on(
-                                                trans::fix_punctuation_with_openai(
-                                                    &client,
-                                                    &transcription,
-                                                ),
-                                            ) {
-                                                Ok(fixed) => {
-                                                    println!("Punctuation added.");
-                                                    transcription = fixed;
-                                                }
-                                                Err(err) => {
-                                                    // Punctuation fix failed - continue with original text
-                                                    println!(
-                                                        "Warning: Failed to fix punctuation: {:?}. Using original transcription.",
-                                                        err
-                                                    );
-                                                }
-                                            }
-                                        }
-
-                                        // Add period at end if enabled and text doesn't end with punctuation
-                                        if opt.period {
-                                            let trimmed = transcription.trim_end();
-                                            if let Some(last_char) = trimmed.chars().last() {
-                                                if !matches!(last_char, '.' | '!' | '?') {
-                                                    // Remove trailing whitespace, add period
-                                                    transcription = format!("{}.", trimmed);
-                                                }
-                                            }
-                                        }
-
-                                        if opt.cap_first {
-                                            capitalize_first_letter(&mut transcription);
-                                        }
-
-                                        if opt.space {
-                                            if let Some(last_char) = transcription.chars().last() {
-                                                if last_char != ' ' {
-                                                    transcription.push(' ');
-                                                }
-                                            }
-                                        }
-
-                                        // Remove ellipses again (LLM might add them)
-                                        transcription = transcription.replace("...", "");
-                                    }
-
-                                    // Stop ticking now that all API calls are complete
-                                    let _ = tick_tx.send(());
-                                    let _ = tick_handle.join();
-
-                                    if transcription.is_empty() {
-                                        println!("No transcription");
-                                        play_failure_sound();
-                                        continue;
-                                    }
-
-                                    let word_count = transcription.split_whitespace().count();
-                                    let duration_secs = elapsed.as_secs_f64();
-                                    if opt.type_chars {
-                                        enigo.key_sequence(&transcription);
-                                    } else {
-                                        // paste from clipboard
-
-                                        // get the clipboard contents so we can restore it later
-                                        let clip_tmp_result = clipboard.get_contents();
-
-                                        // Set and paste Clipboard Contents
-                                        match clipboard.set_contents(transcription) {
-                                            Ok(_) => {
-                                                enigo.key_sequence_parse("{+CTRL}");
-                                                sleep(Duration::from_millis(100));
-                                                enigo.key_sequence_parse("v");
-                                                sleep(Duration::from_millis(100));
-                                                enigo.key_sequence_parse("{-CTRL}");
-                                                sleep(Duration::from_millis(100));
-
-                                                // restore the clipboard contents
-                                                if let Ok(clip_tmp) = clip_tmp_result {
-                                                    if let Err(err) =
-                                                        clipboard.set_contents(clip_tmp)
-                                                    {
-                                                        println!(
-                                                        "Error restoring clipboard contents: {}",
-                                                        err
-                                                    );
-                                                    }
-                                                }
-                                            }
-                                            Err(err) => {
-                                                println!(
-                                                    "Error: Failed to set clipboard contents: {:?}",
-                                                    err
-                                                );
-                                                continue;
-                                            }
-                                        }
-                                    }
-                                    if duration_secs > 0.0 {
-                                        let wpm = (word_count as f64) * 60.0 / duration_secs;
-                                        // Update rolling average
-                                        wpm_history.push_back(wpm);
-                                        wpm_sum += wpm;
-                                        if wpm_history.len() > WPM_ROLLING_MAX {
-                                            if let Some(removed) = wpm_history.pop_front() {
-                                                wpm_sum -= removed;
-                                            }
-                                        }
-                                        let avg_wpm = if !wpm_history.is_empty() {
-                                            wpm_sum / (wpm_history.len() as f64)
-                                        } else {
-                                            0.0
-                                        };
-                                        // Update cumulative totals and print summary
-                                        total_words_transcribed += word_count;
-                                        total_recording_duration += elapsed;
-                                        println!(
-                                            "WPM: {:.1} ({} words over {}) | Avg: {:.1} | Total: {} words, {}",
-                                            wpm,
-                                            word_count,
-                                            humantime::format_duration(truncate_to_secs(elapsed)),
-                                            avg_wpm,
-                                            total_words_transcribed,
-                                            humantime::format_duration(truncate_to_secs(total_recording_duration))
-                                        );
-                                    }
-                                } else {
-                                    println!("Recording too short");
-                                }
-                            }
-                        }
-                        _ => (),
-                    }
-                }
-            });
-
-            // Have this main thread recieve events and send them to the key handler thread
-            {
-                let callback = move |event: Event| {
-                    tx.send(event).unwrap();
-                };
-
-                // This will block.
-                if let Err(error) = listen(callback) {
-                    println!("Error: {:?}", error)
+        };
+        let rendered = transcript.render(format, input);
+        match &outputs {
+            Some(outputs) => {
+                let path = &outputs[i];
+                std::fs::write(path, rendered)?;
+                eprintln!("Wrote {}", path.display());
+            }
+            None => {
+                // Tell files apart like `head` does; JSON is one object per
+                // line and names its file.
+                if inputs.len() > 1 && format != OutputFormat::Json {
+                    println!("==> {} <==", input.display());
                 }
+                print!("{}", rendered);
             }
-
-            Ok(())
         }
     }
-}
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn capitalize_first_letter_works() {
-        let mut s = String::from("hello");
-        capitalize_first_letter(&mut s);
-        assert_eq!(s, "Hello");
+    if failed > 0 {
+        bail!("{} of {} files failed", failed, inputs.len());
     }
+    Ok(())
 }
@@ -0,0 +1,316 @@
+//! Where transcribed text goes.
+//!
+//! Both the batch path (transcribe on release) and the live sessions (realtime
+//! and local streaming) write through an [`OutputSink`], so the way text is
+//! injected is chosen once from [`AppConfig::output`] instead of being decided
+//! inline at every call site.
+
+use crate::config::{AppConfig, OutputMode};
+use crate::inject::{Clipboard, Keyboard, PasteChord};
+use anyhow::{anyhow, Context, Result};
+use parking_lot::Mutex;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Destination for transcribed text.
+///
+/// One utterance is a sequence of [`append`](OutputSink::append) calls (live
+/// text while the key is held) and/or [`insert`](OutputSink::insert) calls
+/// (finished text), closed by [`finish`](OutputSink::finish).
+pub trait OutputSink: Send {
+    /// Outputs a finished piece of text, e.g. a batch transcription.
+    fn insert(&mut self, text: &str) -> Result<()>;
+
+    /// Outputs live text as it is transcribed. Sinks where inserting is slow
+    /// or disruptive (pasting) override this with something incremental.
+    fn append(&mut self, text: &str) -> Result<()> {
+        self.insert(text)
+    }
+
+    /// Removes the last `chars` characters output during this utterance.
+    /// Returns `false` (and changes nothing) if the sink can't take text back.
+    fn erase(&mut self, _chars: usize) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Marks the end of an utterance.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink shared between the key handler and the live session threads.
+pub type SharedSink = Arc<Mutex<Box<dyn OutputSink>>>;
+
+/// Builds the sink selected by the config.
+pub fn from_config(config: &AppConfig) -> Result<Box<dyn OutputSink>> {
+    Ok(match config.output_mode() {
+        OutputMode::Type => Box::new(TypeSink::new()?),
+        OutputMode::Clipboard => Box::new(ClipboardSink::new()?),
+        OutputMode::Stdout => Box::new(StdoutSink),
+        OutputMode::File => {
+            let path = config
+                .output_file
+                .as_deref()
+                .filter(|p| !p.trim().is_empty())
+                .ok_or_else(|| anyhow!("Output mode \"file\" needs an output file path"))?;
+            Box::new(FileSink::new(PathBuf::from(path)))
+        }
+        OutputMode::Paste => Box::new(PasteSink::new(config)?),
+    })
+}
+
+pub fn shared(sink: Box<dyn OutputSink>) -> SharedSink {
+    Arc::new(Mutex::new(sink))
+}
+
+/// Pastes finished text through the clipboard, then restores what was on it.
+/// Live text is typed, since pasting every delta would thrash the clipboard.
+pub struct PasteSink {
+    keyboard: Keyboard,
+    clipboard: Clipboard,
+    /// `paste_shortcut`, after the app rules for the target window.
+    shortcut: PasteChord,
+    transient: bool,
+    restore_delay: Duration,
+}
+
+/// How long to wait after the paste chord before restoring the clipboard:
+/// the configured minimum plus 1 ms per 100 bytes, capped at one extra second.
+fn restore_wait(min: Duration, text: &str) -> Duration {
+    min + Duration::from_millis((text.len() as u64 / 100).min(1000))
+}
+
+impl PasteSink {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        Ok(Self {
+            keyboard: Keyboard::detect()?,
+            clipboard: Clipboard::detect()?,
+            shortcut: config.paste_shortcut().parse()?,
+            transient: config.clipboard_transient,
+            restore_delay: Duration::from_millis(config.paste_restore_delay_ms),
+        })
+    }
+}
+
+impl OutputSink for PasteSink {
+    fn insert(&mut self, text: &str) -> Result<()> {
+        let saved = self.clipboard.snapshot()?;
+        if self.transient {
+            self.clipboard.set_transient_text(text)?;
+        } else {
+            self.clipboard.set_text(text)?;
+        }
+        self.keyboard.paste(&self.shortcut)?;
+
+        // The target application reads the clipboard whenever it gets to the
+        // keystroke, so give it time, more for long texts. If the clipboard
+        // changed meanwhile (the user copied something), leave it alone.
+        thread::sleep(restore_wait(self.restore_delay, text));
+        let untouched = self
+            .clipboard
+            .get_text()
+            .map_or(true, |current| current == text);
+        if untouched {
+            if let Err(err) = self.clipboard.restore(&saved) {
+                eprintln!("Failed to restore clipboard: {err}");
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, text: &str) -> Result<()> {
+        self.keyboard.type_text(text)
+    }
+
+    fn erase(&mut self, chars: usize) -> Result<bool> {
+        self.keyboard.backspace(chars)?;
+        Ok(true)
+    }
+}
+
+/// Types text with synthetic key presses.
+pub struct TypeSink {
+    keyboard: Keyboard,
+}
+
+impl TypeSink {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            keyboard: Keyboard::detect()?,
+        })
+    }
+}
+
+impl OutputSink for TypeSink {
+    fn insert(&mut self, text: &str) -> Result<()> {
+        self.keyboard.type_text(text)
+    }
+
+    fn erase(&mut self, chars: usize) -> Result<bool> {
+        self.keyboard.backspace(chars)?;
+        Ok(true)
+    }
+}
+
+/// Only puts the text on the clipboard; the user pastes it themselves. Text
+/// of one utterance accumulates so the clipboard always holds all of it.
+pub struct ClipboardSink {
+    clipboard: Clipboard,
+    utterance: String,
+}
+
+impl ClipboardSink {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            clipboard: Clipboard::detect()?,
+            utterance: String::new(),
+        })
+    }
+}
+
+impl OutputSink for ClipboardSink {
+    fn insert(&mut self, text: &str) -> Result<()> {
+        self.utterance.push_str(text);
+        self.clipboard.set_text(&self.utterance)
+    }
+
+    fn erase(&mut self, chars: usize) -> Result<bool> {
+        let keep = self.utterance.chars().count().saturating_sub(chars);
+        self.utterance = self.utterance.chars().take(keep).collect();
+        self.clipboard.set_text(&self.utterance)?;
+        Ok(true)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.utterance.clear();
+        Ok(())
+    }
+}
+
+/// Prints text to standard output, one utterance per line.
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn insert(&mut self, text: &str) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        println!();
+        Ok(())
+    }
+}
+
+/// Appends text to a file, one utterance per line.
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn write(&self, text: &str) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open output file {}", self.path.display()))?;
+        file.write_all(text.as_bytes())
+            .with_context(|| format!("Failed to write output file {}", self.path.display()))
+    }
+}
+
+impl OutputSink for FileSink {
+    fn insert(&mut self, text: &str) -> Result<()> {
+        self.write(text)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.write("\n")
+    }
+}
+
+/// One call made on a [`RecordingSink`].
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkOp {
+    Insert(String),
+    Append(String),
+    Erase(usize),
+    Finish,
+}
+
+/// Test double that records every call and keeps the resulting text, so
+/// tests can check output without touching the keyboard or clipboard.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct RecordingSink {
+    pub ops: Arc<Mutex<Vec<SinkOp>>>,
+    pub text: Arc<Mutex<String>>,
+}
+
+#[cfg(test)]
+impl OutputSink for RecordingSink {
+    fn insert(&mut self, text: &str) -> Result<()> {
+        self.ops.lock().push(SinkOp::Insert(text.to_string()));
+        self.text.lock().push_str(text);
+        Ok(())
+    }
+
+    fn append(&mut self, text: &str) -> Result<()> {
+        self.ops.lock().push(SinkOp::Append(text.to_string()));
+        self.text.lock().push_str(text);
+        Ok(())
+    }
+
+    fn erase(&mut self, chars: usize) -> Result<bool> {
+        self.ops.lock().push(SinkOp::Erase(chars));
+        let mut text = self.text.lock();
+        let keep = text.chars().count().saturating_sub(chars);
+        *text = text.chars().take(keep).collect();
+        Ok(true)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.ops.lock().push(SinkOp::Finish);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_sink_writes_one_line_per_utterance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let mut sink = FileSink::new(path.clone());
+        sink.append("Hello").unwrap();
+        sink.append(" world").unwrap();
+        sink.insert(".").unwrap();
+        sink.finish().unwrap();
+        sink.insert("Second").unwrap();
+        sink.finish().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "Hello world.\nSecond\n"
+        );
+    }
+
+    #[test]
+    fn file_sink_cannot_erase() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = FileSink::new(dir.path().join("out.txt"));
+        assert!(!sink.erase(3).unwrap());
+    }
+}
@@ -0,0 +1,98 @@
+//! Realtime preview overlay.
+//!
+//! In the "preview" realtime output mode, live transcript text is not typed
+//! into the focused application (risky in terminals and half-filled forms).
+//! Instead it is shown in a small always-on-top overlay window, and the final
+//! text is inserted once on key release through the normal paste/type path.
+//!
+//! The engine only talks to a [`PreviewEvent`] channel stored in
+//! [`crate::app_state::AppState`]; the Tauri side owns the window and turns
+//! those events into webview events, so the engine keeps no Tauri handles.
+//! Without the `gui` feature nothing listens and preview text only shows up
+//! on release.
+
+#[derive(Debug, Clone)]
+pub enum PreviewEvent {
+    /// A live session started: clear and show the overlay.
+    Show,
+    /// Everything transcribed so far in the current session.
+    Text(String),
+    /// The session ended: hide the overlay.
+    Hide,
+}
+
+#[cfg(feature = "gui")]
+pub use overlay::{spawn_overlay, OVERLAY_LABEL};
+
+#[cfg(feature = "gui")]
+mod overlay {
+    use super::PreviewEvent;
+    use flume::Receiver;
+    use std::thread;
+    use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+
+    /// Label of the overlay webview window.
+    pub const OVERLAY_LABEL: &str = "overlay";
+
+    /// Webview event carrying the full preview text so far.
+    const PREVIEW_TEXT_EVENT: &str = "realtime-preview";
+
+    const OVERLAY_WIDTH: f64 = 640.0;
+    const OVERLAY_HEIGHT: f64 = 120.0;
+    /// Gap between the overlay and the bottom of the screen (logical pixels).
+    const OVERLAY_BOTTOM_MARGIN: f64 = 96.0;
+
+    /// Creates the (hidden) overlay window and forwards preview events to it until
+    /// the sending side is dropped.
+    pub fn spawn_overlay<R: Runtime>(app: &AppHandle<R>, events: Receiver<PreviewEvent>) {
+        let url = WebviewUrl::App("overlay.html".into());
+        let builder = WebviewWindowBuilder::new(app, OVERLAY_LABEL, url)
+            .title("DeskTalk Preview")
+            .inner_size(OVERLAY_WIDTH, OVERLAY_HEIGHT)
+            .resizable(false)
+            .decorations(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .focused(false)
+            .focusable(false)
+            .visible(false);
+        let window = match builder.build() {
+            Ok(window) => window,
+            Err(err) => {
+                eprintln!("Failed to create preview overlay: {:?}", err);
+                return;
+            }
+        };
+
+        // Bottom center of the primary monitor.
+        if let Ok(Some(monitor)) = window.primary_monitor() {
+            let scale = monitor.scale_factor();
+            let size = monitor.size().to_logical::<f64>(scale);
+            let origin = monitor.position().to_logical::<f64>(scale);
+            let x = origin.x + (size.width - OVERLAY_WIDTH) / 2.0;
+            let y = origin.y + size.height - OVERLAY_HEIGHT - OVERLAY_BOTTOM_MARGIN;
+            let _ = window.set_position(tauri::LogicalPosition::new(x, y));
+        }
+
+        let app = app.clone();
+        thread::spawn(move || {
+            for event in events.iter() {
+                let Some(window) = app.get_webview_window(OVERLAY_LABEL) else {
+                    continue;
+                };
+                match event {
+                    PreviewEvent::Show => {
+                        let _ = app.emit_to(OVERLAY_LABEL, PREVIEW_TEXT_EVENT, "");
+                        let _ = window.show();
+                    }
+                    PreviewEvent::Text(text) => {
+                        let _ = app.emit_to(OVERLAY_LABEL, PREVIEW_TEXT_EVENT, text);
+                    }
+                    PreviewEvent::Hide => {
+                        let _ = window.hide();
+                    }
+                }
+            }
+        });
+    }
+}
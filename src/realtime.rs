@@ -6,20 +6,34 @@
 //! deltas as they are produced. Those deltas are typed into the focused window
 //! live, so text appears while you are still speaking.
 //!
+//! If the connection drops mid-utterance the session reconnects and resends the
+//! audio of the unfinished segment, skipping text that was already typed. When
+//! reconnecting fails, the buffered audio is transcribed with the regular batch
+//! API instead so nothing spoken is lost.
+//!
 //! This is intentionally a separate code path so the original, non-realtime
 //! behavior is preserved untouched.
 
+use crate::config::{InputDevice, RealtimeDelay};
+use crate::output::SharedSink;
+use crate::preview::PreviewEvent;
+use crate::transcribe::trans;
 use anyhow::{anyhow, bail, Context, Result};
+use async_openai::config::{Config, OpenAIConfig};
+use async_openai::Client;
 use base64::Engine as _;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::traits::{DeviceTrait, StreamTrait};
 use cpal::{FromSample, Sample, SizedSample};
-use enigo::{Enigo, KeyboardControllable};
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
+use tokio::net::TcpStream;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
 /// Target sample rate required by the OpenAI Realtime API (pcm16, mono).
 const TARGET_SAMPLE_RATE: u32 = 24_000;
@@ -74,18 +88,19 @@ pub struct RealtimeSession {
 }
 
 impl RealtimeSession {
-    /// Starts capturing the microphone and streaming it to OpenAI. Returns
+    /// Starts capturing the microphone and streaming it to the realtime API
+    /// at `openai`'s endpoint. Returns
     /// quickly; all work happens on a background thread. Transcript deltas are
     /// typed into the focused window as they arrive.
-    #[allow(clippy::too_many_arguments)]
     pub fn start(
-        api_key: String,
-        device: String,
+        openai: OpenAIConfig,
+        device: InputDevice,
         model: String,
         language: Option<String>,
-        cap_first: bool,
-        delay: String,
+        output: LiveTyperOptions,
+        delay: RealtimeDelay,
     ) -> Result<Self> {
+        let delay = delay.to_string();
         let (stop_tx, stop_rx) = flume::bounded::<()>(1);
         let (result_tx, result_rx) = flume::bounded::<Result<String>>(1);
 
@@ -104,7 +119,7 @@ impl RealtimeSession {
                 };
 
                 let result = rt.block_on(run_session(
-                    api_key, device, model, language, cap_first, delay, stop_rx,
+                    openai, device, model, language, output, delay, None, stop_rx,
                 ));
                 let _ = result_tx.send(result);
             })
@@ -133,17 +148,244 @@ impl RealtimeSession {
     }
 }
 
+/// How often an idle pre-warmed connection is pinged so proxies and the
+/// server don't drop it.
+const PREWARM_KEEPALIVE: Duration = Duration::from_secs(20);
+
+/// Idle pre-warmed connections are replaced after this long, well before the
+/// server-side session lifetime runs out.
+const PREWARM_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Wait before trying again when pre-warming a connection fails.
+const PREWARM_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+enum PoolCommand {
+    Start {
+        device: InputDevice,
+        output: LiveTyperOptions,
+        stop_rx: flume::Receiver<()>,
+        result_tx: flume::Sender<Result<String>>,
+    },
+}
+
+/// Keeps one configured Realtime API connection open so the next push-to-talk
+/// press can start streaming immediately instead of waiting on the TLS and
+/// WebSocket handshake.
+///
+/// Sockets are bound to the runtime that opened them, so sessions started
+/// through the pool run on the pool's own thread. Dropping the pool closes the
+/// idle connection and ends that thread.
+pub struct RealtimePool {
+    cmd_tx: flume::Sender<PoolCommand>,
+}
+
+impl RealtimePool {
+    /// Starts the pool thread, which connects right away and keeps the
+    /// connection alive until it is handed to a session.
+    pub fn start(
+        openai: OpenAIConfig,
+        model: String,
+        language: Option<String>,
+        delay: RealtimeDelay,
+    ) -> Result<Self> {
+        let delay = delay.to_string();
+        let (cmd_tx, cmd_rx) = flume::unbounded::<PoolCommand>();
+
+        thread::Builder::new()
+            .name("realtime-pool".into())
+            .spawn(move || {
+                let rt = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        log_line(&format!("Failed to build pre-warm runtime: {e}"));
+                        return;
+                    }
+                };
+                rt.block_on(run_pool(openai, model, language, delay, cmd_rx));
+            })
+            .context("Failed to spawn realtime pool thread")?;
+
+        Ok(Self { cmd_tx })
+    }
+
+    /// Starts a session on the pre-warmed connection (or a fresh one if none
+    /// is ready yet). Behaves exactly like [`RealtimeSession::start`].
+    pub fn session(
+        &self,
+        device: InputDevice,
+        output: LiveTyperOptions,
+    ) -> Result<RealtimeSession> {
+        let (stop_tx, stop_rx) = flume::bounded::<()>(1);
+        let (result_tx, result_rx) = flume::bounded::<Result<String>>(1);
+        self.cmd_tx
+            .send(PoolCommand::Start {
+                device,
+                output,
+                stop_rx,
+                result_tx,
+            })
+            .map_err(|_| anyhow!("Realtime pool is not running"))?;
+        Ok(RealtimeSession {
+            stop_tx,
+            result_rx,
+            handle: None,
+        })
+    }
+}
+
+/// What woke the pool up while a connection was idle.
+enum PoolWake {
+    Command(Option<PoolCommand>),
+    Message(Option<std::result::Result<Message, tokio_tungstenite::tungstenite::Error>>),
+    Keepalive,
+    Expired,
+}
+
+async fn run_pool(
+    openai: OpenAIConfig,
+    model: String,
+    language: Option<String>,
+    delay: String,
+    cmd_rx: flume::Receiver<PoolCommand>,
+) {
+    let mut warm: Option<Link> = None;
+
+    loop {
+        let Some(link) = warm.as_mut() else {
+            // Not connected: connect, but never make a key press wait on it.
+            // A press that arrives first gets a cold session instead.
+            let connecting = connect(&openai, &model, &language, &delay);
+            tokio::pin!(connecting);
+            let cmd = tokio::select! {
+                biased;
+                cmd = cmd_rx.recv_async() => cmd.ok(),
+                result = &mut connecting => match result {
+                    Ok(link) => {
+                        log_line("Pre-warmed realtime connection ready");
+                        warm = Some(link);
+                        continue;
+                    }
+                    Err(_) => {
+                        tokio::select! {
+                            cmd = cmd_rx.recv_async() => cmd.ok(),
+                            _ = tokio::time::sleep(PREWARM_RETRY_DELAY) => continue,
+                        }
+                    }
+                },
+            };
+            match cmd {
+                Some(cmd) => run_pool_command(&openai, &model, &language, &delay, cmd, None).await,
+                None => return,
+            }
+            continue;
+        };
+
+        let expires = tokio::time::sleep(PREWARM_MAX_AGE);
+        tokio::pin!(expires);
+        let wake = loop {
+            let wake = tokio::select! {
+                cmd = cmd_rx.recv_async() => PoolWake::Command(cmd.ok()),
+                msg = link.read.next() => PoolWake::Message(msg),
+                _ = tokio::time::sleep(PREWARM_KEEPALIVE) => PoolWake::Keepalive,
+                _ = &mut expires => PoolWake::Expired,
+            };
+            match wake {
+                PoolWake::Keepalive => {
+                    if link.write.send(Message::Ping(Vec::new())).await.is_err() {
+                        log_line("Pre-warmed connection keepalive failed");
+                        break PoolWake::Expired;
+                    }
+                }
+                PoolWake::Message(Some(Ok(Message::Text(text)))) => {
+                    // Only lifecycle events are expected while idle; an error
+                    // means the session is unusable.
+                    let event: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                    let event_type = event.get("type").and_then(|v| v.as_str()).unwrap_or("");
+                    if event_type == "error" {
+                        log_line(&format!("Pre-warmed session error: {text}"));
+                        break PoolWake::Expired;
+                    }
+                    log_line(&format!("Pre-warm server: {event_type}"));
+                }
+                PoolWake::Message(Some(Ok(Message::Close(_)))) | PoolWake::Message(None) => {
+                    log_line("Pre-warmed connection closed by server");
+                    break PoolWake::Expired;
+                }
+                PoolWake::Message(Some(Err(e))) => {
+                    log_line(&format!("Pre-warmed connection error: {e}"));
+                    break PoolWake::Expired;
+                }
+                PoolWake::Message(Some(Ok(_))) => {}
+                other => break other,
+            }
+        };
+
+        match wake {
+            PoolWake::Command(Some(cmd)) => {
+                let link = warm.take();
+                run_pool_command(&openai, &model, &language, &delay, cmd, link).await;
+            }
+            PoolWake::Command(None) => {
+                if let Some(mut link) = warm.take() {
+                    let _ = link.write.send(Message::Close(None)).await;
+                }
+                return;
+            }
+            _ => {
+                // Expired or broken: drop it and connect a fresh one.
+                if let Some(mut link) = warm.take() {
+                    let _ = link.write.send(Message::Close(None)).await;
+                }
+            }
+        }
+    }
+}
+
+async fn run_pool_command(
+    openai: &OpenAIConfig,
+    model: &str,
+    language: &Option<String>,
+    delay: &str,
+    cmd: PoolCommand,
+    link: Option<Link>,
+) {
+    match cmd {
+        PoolCommand::Start {
+            device,
+            output,
+            stop_rx,
+            result_tx,
+        } => {
+            let result = run_session(
+                openai.clone(),
+                device,
+                model.to_string(),
+                language.clone(),
+                output,
+                delay.to_string(),
+                link,
+                stop_rx,
+            )
+            .await;
+            let _ = result_tx.send(result);
+        }
+    }
+}
+
 /// Stateful linear resampler that converts an arbitrary input rate to the
 /// target rate. Carries fractional position and the last sample across calls so
 /// successive audio buffers stitch together seamlessly.
-struct Resampler {
+pub(crate) struct Resampler {
     step: f64,
     pos: f64,
     prev: f32,
 }
 
 impl Resampler {
-    fn new(in_rate: f64, out_rate: f64) -> Self {
+    pub(crate) fn new(in_rate: f64, out_rate: f64) -> Self {
         Self {
             step: in_rate / out_rate,
             pos: 0.0,
@@ -151,7 +393,7 @@ impl Resampler {
         }
     }
 
-    fn process(&mut self, input: &[f32], out: &mut Vec<i16>) {
+    pub(crate) fn process(&mut self, input: &[f32], out: &mut Vec<i16>) {
         if input.is_empty() {
             return;
         }
@@ -179,24 +421,15 @@ impl Resampler {
     }
 }
 
-/// Builds a cpal input stream that downmixes to mono, resamples to the target
-/// rate, converts to PCM16, and forwards batches of samples through `sender`.
-/// Returns the live stream (kept alive by the caller).
-fn build_input_stream(
-    device_name: &str,
+/// Builds a cpal input stream that downmixes to mono, resamples to
+/// `target_rate`, converts to PCM16, and forwards batches of samples through
+/// `sender`. Returns the live stream (kept alive by the caller).
+pub(crate) fn build_input_stream(
+    device: &InputDevice,
+    target_rate: u32,
     sender: flume::Sender<Vec<i16>>,
 ) -> Result<cpal::Stream> {
-    let host = cpal::default_host();
-    let device = match if device_name == "default" {
-        host.default_input_device()
-    } else {
-        host.input_devices()
-            .context("Failed to get list of input devices")?
-            .find(|d| d.name().map(|n| n == device_name).unwrap_or(false))
-    } {
-        Some(d) => d,
-        None => bail!("Failed to find input device '{device_name}'"),
-    };
+    let device = device.find()?;
 
     let config = device
         .default_input_config()
@@ -208,16 +441,48 @@ fn build_input_stream(
 
     let stream = match config.sample_format() {
         cpal::SampleFormat::F32 => {
-            build_typed_stream::<f32>(&device, &config.into(), channels, in_rate, sender, err_fn)?
+            build_typed_stream::<f32>(
+                &device,
+                &config.into(),
+                channels,
+                in_rate,
+                target_rate,
+                sender,
+                err_fn,
+            )?
         }
         cpal::SampleFormat::I16 => {
-            build_typed_stream::<i16>(&device, &config.into(), channels, in_rate, sender, err_fn)?
+            build_typed_stream::<i16>(
+                &device,
+                &config.into(),
+                channels,
+                in_rate,
+                target_rate,
+                sender,
+                err_fn,
+            )?
         }
         cpal::SampleFormat::I32 => {
-            build_typed_stream::<i32>(&device, &config.into(), channels, in_rate, sender, err_fn)?
+            build_typed_stream::<i32>(
+                &device,
+                &config.into(),
+                channels,
+                in_rate,
+                target_rate,
+                sender,
+                err_fn,
+            )?
         }
         cpal::SampleFormat::I8 => {
-            build_typed_stream::<i8>(&device, &config.into(), channels, in_rate, sender, err_fn)?
+            build_typed_stream::<i8>(
+                &device,
+                &config.into(),
+                channels,
+                in_rate,
+                target_rate,
+                sender,
+                err_fn,
+            )?
         }
         other => bail!("Unsupported sample format '{other}'"),
     };
@@ -231,6 +496,7 @@ fn build_typed_stream<T>(
     config: &cpal::StreamConfig,
     channels: usize,
     in_rate: f64,
+    target_rate: u32,
     sender: flume::Sender<Vec<i16>>,
     err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
 ) -> Result<cpal::Stream>
@@ -238,7 +504,7 @@ where
     T: Sample + SizedSample,
     f32: FromSample<T>,
 {
-    let mut resampler = Resampler::new(in_rate, TARGET_SAMPLE_RATE as f64);
+    let mut resampler = Resampler::new(in_rate, target_rate as f64);
     let stream = device
         .build_input_stream(
             config,
@@ -283,29 +549,49 @@ fn extract_text(event: &Value, field: &str) -> Option<String> {
         .map(|s| s.to_string())
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn run_session(
-    api_key: String,
-    device: String,
-    model: String,
-    language: Option<String>,
-    cap_first: bool,
-    delay: String,
-    stop_rx: flume::Receiver<()>,
-) -> Result<String> {
-    // --- Connect ---------------------------------------------------------
-    let url = "wss://api.openai.com/v1/realtime?intent=transcription";
-    log_line(&format!(
-        "Starting realtime session (model: {model}, device: {device})"
-    ));
-    let mut request = url
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Both halves of a configured Realtime API connection.
+struct Link {
+    write: SplitSink<WsStream, Message>,
+    read: SplitStream<WsStream>,
+}
+
+/// Delays between reconnection attempts after a transient failure. Once these
+/// are exhausted the session falls back to the batch transcription API.
+const RECONNECT_BACKOFF: [Duration; 3] = [
+    Duration::from_millis(250),
+    Duration::from_secs(1),
+    Duration::from_secs(3),
+];
+
+/// The transcription WebSocket of the API at `api_base`, e.g.
+/// `https://api.openai.com/v1` becomes `wss://api.openai.com/v1/realtime`.
+fn realtime_url(api_base: &str) -> String {
+    let api_base = api_base.trim_end_matches('/');
+    let api_base = match api_base.split_once("://") {
+        Some(("http", rest)) => format!("ws://{rest}"),
+        Some(("https", rest)) => format!("wss://{rest}"),
+        _ => api_base.to_string(),
+    };
+    format!("{api_base}/realtime?intent=transcription")
+}
+
+/// Opens the WebSocket and sends the transcription `session.update`.
+async fn connect(
+    openai: &OpenAIConfig,
+    model: &str,
+    language: &Option<String>,
+    delay: &str,
+) -> Result<Link> {
+    let mut request = realtime_url(openai.api_base())
         .into_client_request()
         .context("Failed to build websocket request")?;
     {
         let headers = request.headers_mut();
         headers.insert(
             "Authorization",
-            format!("Bearer {api_key}")
+            format!("Bearer {}", openai.api_key())
                 .parse()
                 .context("Invalid Authorization header")?,
         );
@@ -321,7 +607,7 @@ async fn run_session(
             return Err(anyhow!("Failed to connect to OpenAI Realtime API: {e}"));
         }
     };
-    let (mut write, mut read) = ws_stream.split();
+    let (mut write, read) = ws_stream.split();
 
     // --- Configure the transcription session (GA schema) -----------------
     // gpt-realtime-whisper is a natively-streaming model: it emits partial
@@ -332,14 +618,13 @@ async fn run_session(
 
     let mut transcription = json!({ "model": model });
     if let Some(lang) = language {
-        transcription["language"] = Value::String(lang);
+        transcription["language"] = Value::String(lang.clone());
     }
     if is_streaming_whisper {
         // Latency/accuracy tradeoff: minimal | low | medium | high | xhigh.
         // Higher = more audio context before emitting text = better accuracy.
-        let delay = crate::config::sanitize_realtime_delay(&delay);
         log_line(&format!("Realtime delay: {delay}"));
-        transcription["delay"] = Value::String(delay);
+        transcription["delay"] = Value::String(delay.to_string());
     }
 
     let turn_detection = if is_streaming_whisper {
@@ -366,156 +651,693 @@ async fn run_session(
             }
         }
     });
-    if let Err(e) = write
-        .send(Message::Text(session_update.to_string()))
-        .await
-    {
+    if let Err(e) = write.send(Message::Text(session_update.to_string())).await {
         log_line(&format!("Failed to send session config: {e}"));
         return Err(anyhow!("Failed to send session config: {e}"));
     }
     log_line("Sent session.update (transcription config)");
 
+    Ok(Link { write, read })
+}
+
+/// Reconnects with backoff and replays the audio of the unfinished segment so
+/// the new session picks up where the old one stopped. Returns `None` once all
+/// attempts have failed.
+async fn reconnect(
+    openai: &OpenAIConfig,
+    model: &str,
+    language: &Option<String>,
+    delay: &str,
+    recovery: &mut Recovery,
+) -> Option<Link> {
+    for (attempt, backoff) in RECONNECT_BACKOFF.iter().enumerate() {
+        tokio::time::sleep(*backoff).await;
+        log_line(&format!(
+            "Reconnecting (attempt {}/{})",
+            attempt + 1,
+            RECONNECT_BACKOFF.len()
+        ));
+        let mut link = match connect(openai, model, language, delay).await {
+            Ok(link) => link,
+            Err(_) => continue,
+        };
+        if resend_audio(&mut link, &recovery.audio).await.is_err() {
+            log_line("Failed to resend buffered audio after reconnecting");
+            continue;
+        }
+        log_line(&format!(
+            "Reconnected; resent {:.1}s of buffered audio",
+            recovery.audio.len() as f64 / TARGET_SAMPLE_RATE as f64
+        ));
+        recovery.begin_replay();
+        return Some(link);
+    }
+    None
+}
+
+async fn resend_audio(link: &mut Link, audio: &[i16]) -> Result<()> {
+    // Larger chunks than live streaming: this is a catch-up burst.
+    for chunk in audio.chunks(TARGET_SAMPLE_RATE as usize) {
+        send_append(link, chunk).await?;
+    }
+    Ok(())
+}
+
+async fn send_append(link: &mut Link, samples: &[i16]) -> Result<()> {
+    let b64 = pcm16_to_base64(samples);
+    let msg = json!({ "type": "input_audio_buffer.append", "audio": b64 });
+    link.write
+        .send(Message::Text(msg.to_string()))
+        .await
+        .map_err(|e| anyhow!("Failed to send audio: {e}"))
+}
+
+/// Audio and text of the current (not yet completed) segment. Everything
+/// captured is kept here until the server reports the segment transcribed, so
+/// a dropped connection can be resumed (or transcribed via the batch API)
+/// without losing speech or typing anything twice.
+#[derive(Default)]
+struct Recovery {
+    /// All audio captured since the last completed segment.
+    audio: Vec<i16>,
+    /// Position of `audio[0]` in the audio sent over the current connection,
+    /// in samples.
+    offset: usize,
+    /// Where each VAD segment ended in the connection's audio (samples), by
+    /// item id, from `speech_stopped` events.
+    segment_ends: HashMap<String, usize>,
+    /// Text typed since the last completed segment.
+    typed: String,
+    /// Lowercased, whitespace-free characters of `typed` still expected from
+    /// a replayed segment. Matching deltas were already typed and are dropped.
+    skip: VecDeque<char>,
+}
+
+impl Recovery {
+    /// Notes where server VAD ended the segment `item_id`.
+    fn speech_stopped(&mut self, item_id: &str, audio_end_ms: u64) {
+        let end = audio_end_ms as usize * TARGET_SAMPLE_RATE as usize / 1000;
+        self.segment_ends.insert(item_id.to_string(), end);
+    }
+
+    /// Drops the audio of the completed segment. With server VAD, audio
+    /// captured after the segment ended belongs to the next one and is kept;
+    /// a manually committed turn covers everything sent.
+    fn segment_completed(&mut self, item_id: Option<&str>) {
+        let end = item_id.and_then(|id| self.segment_ends.remove(id));
+        let done = match end {
+            Some(end) => end.saturating_sub(self.offset).min(self.audio.len()),
+            None => self.audio.len(),
+        };
+        self.audio.drain(..done);
+        self.offset += done;
+        self.typed.clear();
+        self.skip.clear();
+    }
+
+    /// Called after the buffered audio was resent to a new session: the
+    /// transcript will start over from the beginning of the segment.
+    fn begin_replay(&mut self) {
+        self.offset = 0;
+        self.segment_ends.clear();
+        self.skip = self
+            .typed
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect();
+    }
+
+    /// Returns the part of `delta` that has not been typed yet. Comparison
+    /// ignores case and whitespace because the first delta of a session is
+    /// trimmed and capitalized before typing.
+    fn filter_delta(&mut self, delta: &str) -> String {
+        if self.skip.is_empty() {
+            return delta.to_string();
+        }
+        let mut rest_start = delta.len();
+        for (i, c) in delta.char_indices() {
+            if c.is_whitespace() {
+                continue;
+            }
+            let lower: Vec<char> = c.to_lowercase().collect();
+            let matches = self.skip.len() >= lower.len()
+                && self.skip.iter().zip(lower.iter()).all(|(a, b)| a == b);
+            if !matches {
+                // The new session heard something different; stop skipping and
+                // type from here on.
+                self.skip.clear();
+                rest_start = i;
+                break;
+            }
+            self.skip.drain(..lower.len());
+            if self.skip.is_empty() {
+                rest_start = i + c.len_utf8();
+                break;
+            }
+        }
+        delta[rest_start..].to_string()
+    }
+}
+
+/// Outcome of streaming over one connection.
+enum LinkOutcome {
+    /// Finished normally (key released / turn completed).
+    Done,
+    /// The connection dropped; worth reconnecting.
+    Lost(anyhow::Error),
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_session(
+    openai: OpenAIConfig,
+    device: InputDevice,
+    model: String,
+    language: Option<String>,
+    output: LiveTyperOptions,
+    delay: String,
+    prewarmed: Option<Link>,
+    stop_rx: flume::Receiver<()>,
+) -> Result<String> {
+    log_line(&format!(
+        "Starting realtime session (model: {model}, device: {device})"
+    ));
+
     // --- Start capturing audio -------------------------------------------
+    // Capture starts before connecting: everything is buffered in `recovery`
+    // and sent once the socket is up, so speech during the handshake (or a
+    // reconnect) is never dropped.
     let (audio_tx, audio_rx) = flume::unbounded::<Vec<i16>>();
-    let _stream = build_input_stream(&device, audio_tx)
+    let stream = build_input_stream(&device, TARGET_SAMPLE_RATE, audio_tx)
         .context("Failed to start microphone capture")?;
 
-    let mut enigo = Enigo::new();
-    let mut accumulated = String::new();
-    let mut typed_first = false;
+    let mut typer = LiveTyper::new(output);
     // When a speech segment completes, the next segment's text won't include a
     // leading space, so we insert one ourselves before the next delta.
     let mut pending_space = false;
-    let mut batch: Vec<i16> = Vec::with_capacity(APPEND_BATCH_SAMPLES * 2);
+    let mut recovery = Recovery::default();
 
-    // Types a delta into the focused window and records it.
-    let mut handle_delta =
-        |text: &str, enigo: &mut Enigo, accumulated: &mut String, typed_first: &mut bool| {
-            if text.is_empty() {
-                return;
+    let mut link = match prewarmed {
+        Some(link) => {
+            log_line("Using pre-warmed connection");
+            Some(link)
+        }
+        None => match connect(&openai, &model, &language, &delay).await {
+            Ok(link) => Some(link),
+            Err(_) => reconnect(&openai, &model, &language, &delay, &mut recovery).await,
+        },
+    };
+
+    // --- Phase 1: stream audio until the key is released -----------------
+    let mut stopped = false;
+    while let Some(current) = link.as_mut() {
+        match stream_until_stop(
+            current,
+            &audio_rx,
+            &stop_rx,
+            &mut typer,
+            &mut pending_space,
+            &mut recovery,
+        )
+        .await?
+        {
+            LinkOutcome::Done => {
+                stopped = true;
+                break;
             }
-            let mut to_type = text.to_string();
-            if !*typed_first {
-                // The realtime model prefixes the first delta of a session with
-                // a leading space; strip it so dictation doesn't begin with a
-                // stray space (especially annoying at the start of a new line).
-                to_type = to_type.trim_start().to_string();
-                if to_type.is_empty() {
-                    return; // delta was only whitespace; wait for real text
-                }
-                if cap_first {
-                    capitalize_first_letter(&mut to_type);
-                }
+            LinkOutcome::Lost(e) => {
+                log_line(&format!("Connection lost while streaming: {e}"));
+                link = reconnect(&openai, &model, &language, &delay, &mut recovery).await;
             }
-            *typed_first = true;
-            accumulated.push_str(&to_type);
-            enigo.key_sequence(&to_type);
-        };
+        }
+    }
+
+    // --- Phase 2: commit and drain the remaining transcript --------------
+    // A connection lost here gets one more reconnect (with its own backoff):
+    // the resent audio is committed again and the drain starts over.
+    let mut drain_attempts = 0;
+    while let Some(current) = link.as_mut() {
+        drain_attempts += 1;
+        // Drain anything captured but not yet sent.
+        let mut tail: Vec<i16> = Vec::new();
+        while let Ok(samples) = audio_rx.try_recv() {
+            tail.extend_from_slice(&samples);
+        }
+        match commit_and_drain(
+            current,
+            &tail,
+            &mut typer,
+            &mut pending_space,
+            &mut recovery,
+        )
+        .await?
+        {
+            LinkOutcome::Done => {
+                let _ = current.write.send(Message::Close(None)).await;
+                let accumulated = typer.into_text();
+                log_line(&format!("Session finished. Transcript: {accumulated:?}"));
+                return Ok(accumulated);
+            }
+            LinkOutcome::Lost(e) => {
+                log_line(&format!("Connection lost while draining: {e}"));
+                link = if drain_attempts < 2 {
+                    reconnect(&openai, &model, &language, &delay, &mut recovery).await
+                } else {
+                    None
+                };
+            }
+        }
+    }
+
+    // --- Fallback: transcribe the buffered audio via the batch API -------
+    if !stopped {
+        // Keep recording until the key is released so the rest of the
+        // utterance is captured too.
+        let _ = stop_rx.recv_async().await;
+    }
+    while let Ok(samples) = audio_rx.try_recv() {
+        recovery.audio.extend_from_slice(&samples);
+    }
+    drop(stream);
+    log_line(&format!(
+        "Realtime connection unavailable; transcribing {:.1}s of buffered audio via the batch API",
+        recovery.audio.len() as f64 / TARGET_SAMPLE_RATE as f64
+    ));
+    let transcript = transcribe_fallback(&openai, &recovery).await?;
+    typer.finish_segment_with(&transcript);
+    let accumulated = typer.into_text();
+    log_line(&format!(
+        "Session finished via batch fallback. Transcript: {accumulated:?}"
+    ));
+    Ok(accumulated)
+}
+
+/// Streams captured audio and types incoming deltas until the key is
+/// released. Transport errors are returned as [`LinkOutcome::Lost`]; API
+/// errors are permanent and returned as `Err`.
+async fn stream_until_stop(
+    link: &mut Link,
+    audio_rx: &flume::Receiver<Vec<i16>>,
+    stop_rx: &flume::Receiver<()>,
+    typer: &mut LiveTyper,
+    pending_space: &mut bool,
+    recovery: &mut Recovery,
+) -> Result<LinkOutcome> {
+    // Audio captured while connecting is still queued in `audio_rx`; audio of
+    // the current segment from before a reconnect was resent by `reconnect`.
+    let mut batch: Vec<i16> = Vec::with_capacity(APPEND_BATCH_SAMPLES * 2);
 
-    // --- Phase 1: stream audio until the key is released -----------------
     loop {
         tokio::select! {
             biased;
 
             // Key released -> finish capturing.
             _ = stop_rx.recv_async() => {
-                break;
+                if !batch.is_empty() {
+                    if let Err(e) = send_append(link, &batch).await {
+                        return Ok(LinkOutcome::Lost(e));
+                    }
+                }
+                return Ok(LinkOutcome::Done);
             }
 
             // New audio captured -> batch & forward.
             audio = audio_rx.recv_async() => {
                 if let Ok(samples) = audio {
+                    recovery.audio.extend_from_slice(&samples);
                     batch.extend_from_slice(&samples);
                     if batch.len() >= APPEND_BATCH_SAMPLES {
-                        let b64 = pcm16_to_base64(&batch);
+                        let result = send_append(link, &batch).await;
                         batch.clear();
-                        let msg = json!({ "type": "input_audio_buffer.append", "audio": b64 });
-                        if write.send(Message::Text(msg.to_string())).await.is_err() {
-                            break;
+                        if let Err(e) = result {
+                            return Ok(LinkOutcome::Lost(e));
                         }
                     }
                 }
             }
 
             // Incoming transcript events.
-            msg = read.next() => {
+            msg = link.read.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
                         // Ignore turn-completion during phase 1 (VAD models emit
                         // a completion per segment while you keep talking).
-                        let _ = process_event(&text, &mut enigo, &mut accumulated, &mut typed_first, &mut pending_space, &mut handle_delta)?;
+                        let _ = process_event(&text, typer, pending_space, recovery)?;
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Ok(LinkOutcome::Lost(anyhow!("Server closed the connection")));
                     }
-                    Some(Ok(Message::Close(_))) | None => break,
                     Some(Ok(_)) => {}
-                    Some(Err(e)) => bail!("Realtime websocket error: {e}"),
+                    Some(Err(e)) => {
+                        return Ok(LinkOutcome::Lost(anyhow!("Realtime websocket error: {e}")));
+                    }
                 }
             }
         }
     }
+}
 
-    // --- Flush any remaining audio and commit ----------------------------
-    // Drain anything captured but not yet batched.
-    while let Ok(samples) = audio_rx.try_recv() {
-        batch.extend_from_slice(&samples);
-    }
-    if !batch.is_empty() {
-        let b64 = pcm16_to_base64(&batch);
-        batch.clear();
-        let msg = json!({ "type": "input_audio_buffer.append", "audio": b64 });
-        let _ = write.send(Message::Text(msg.to_string())).await;
+/// Sends the last captured audio, commits the buffer and waits for the
+/// committed turn to finish transcribing.
+async fn commit_and_drain(
+    link: &mut Link,
+    tail: &[i16],
+    typer: &mut LiveTyper,
+    pending_space: &mut bool,
+    recovery: &mut Recovery,
+) -> Result<LinkOutcome> {
+    if !tail.is_empty() {
+        recovery.audio.extend_from_slice(tail);
+        if let Err(e) = send_append(link, tail).await {
+            return Ok(LinkOutcome::Lost(e));
+        }
     }
-    let _ = write
+    if let Err(e) = link
+        .write
         .send(Message::Text(
             json!({ "type": "input_audio_buffer.commit" }).to_string(),
         ))
-        .await;
+        .await
+    {
+        return Ok(LinkOutcome::Lost(anyhow!("Failed to commit audio: {e}")));
+    }
     log_line("Committed audio buffer, draining final transcript");
 
-    // Stop the microphone now that we've committed.
-    drop(_stream);
-
-    // --- Phase 2: drain remaining transcript deltas ----------------------
     // Stop as soon as the server reports the committed turn is complete; the
     // idle timeout is only a safety net for the unlikely case it never does.
     loop {
-        match tokio::time::timeout(DRAIN_IDLE_TIMEOUT, read.next()).await {
+        match tokio::time::timeout(DRAIN_IDLE_TIMEOUT, link.read.next()).await {
             Ok(Some(Ok(Message::Text(text)))) => {
-                let completed = process_event(
-                    &text,
-                    &mut enigo,
-                    &mut accumulated,
-                    &mut typed_first,
-                    &mut pending_space,
-                    &mut handle_delta,
-                )?;
+                let completed = process_event(&text, typer, pending_space, recovery)?;
                 if completed {
-                    break;
+                    return Ok(LinkOutcome::Done);
                 }
             }
-            Ok(Some(Ok(Message::Close(_)))) | Ok(None) => break,
+            Ok(Some(Ok(Message::Close(_)))) | Ok(None) => {
+                return Ok(LinkOutcome::Lost(anyhow!(
+                    "Server closed the connection before the transcript completed"
+                )));
+            }
             Ok(Some(Ok(_))) => {}
-            Ok(Some(Err(_))) => break,
+            Ok(Some(Err(e))) => {
+                return Ok(LinkOutcome::Lost(anyhow!("Realtime websocket error: {e}")));
+            }
             // No new events for DRAIN_IDLE_TIMEOUT -> assume we're done.
-            Err(_) => break,
+            Err(_) => return Ok(LinkOutcome::Done),
         }
     }
+}
 
-    let _ = write.send(Message::Close(None)).await;
-    log_line(&format!("Session finished. Transcript: {accumulated:?}"));
-    Ok(accumulated)
+/// Transcribes the unfinished segment with the regular transcription API.
+async fn transcribe_fallback(openai: &OpenAIConfig, recovery: &Recovery) -> Result<String> {
+    let secs = recovery.audio.len() as f64 / TARGET_SAMPLE_RATE as f64;
+    // Whisper API can't handle less than 0.1 seconds of audio.
+    if secs < 0.2 {
+        return Ok(String::new());
+    }
+
+    let tmp_dir = tempfile::tempdir().context("Failed to create temp dir")?;
+    let wav_path = tmp_dir.path().join("realtime_fallback.wav");
+    {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: TARGET_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer =
+            hound::WavWriter::create(&wav_path, spec).context("Failed to create fallback WAV")?;
+        for &s in &recovery.audio {
+            writer
+                .write_sample(s)
+                .context("Failed to write fallback WAV")?;
+        }
+        writer
+            .finalize()
+            .context("Failed to finalize fallback WAV")?;
+    }
+
+    // Each attempt is counted toward spending as a whisper-1 request there.
+    let client = Client::with_config(openai.clone());
+    trans::transcribe_with_retry(&client, &wav_path, 2, secs)
+        .await
+        .map_err(|e| anyhow!("Batch fallback transcription failed: {e}"))
+}
+
+/// How a live session outputs text while the key is held.
+#[derive(Clone)]
+pub struct LiveTyperOptions {
+    /// Where live text is written (unless previewing).
+    pub sink: SharedSink,
+    /// Capitalize the first letter of the utterance.
+    pub cap_first: bool,
+    /// Correct each segment's typed text once its final transcript arrives.
+    pub reconcile: bool,
+    /// Show text in the preview overlay instead of typing it. The caller
+    /// inserts the final text itself once the session stops.
+    pub preview: Option<flume::Sender<PreviewEvent>>,
+}
+
+/// Writes live transcript text to the output sink (or the preview overlay)
+/// and records what was written. Shared by the OpenAI realtime session
+/// and the local streaming session so both clean up the start of an utterance
+/// the same way.
+pub(crate) struct LiveTyper {
+    sink: SharedSink,
+    accumulated: String,
+    typed_first: bool,
+    cap_first: bool,
+    reconcile: bool,
+    preview: Option<flume::Sender<PreviewEvent>>,
+    /// Byte offset in `accumulated` where the current segment begins.
+    segment_start: usize,
+    /// Conversation item the current segment's deltas belong to, and whether
+    /// deltas of more than one item were mixed into it.
+    segment_item: Option<String>,
+    segment_mixed: bool,
+}
+
+impl LiveTyper {
+    pub(crate) fn new(options: LiveTyperOptions) -> Self {
+        Self {
+            sink: options.sink,
+            accumulated: String::new(),
+            typed_first: false,
+            cap_first: options.cap_first,
+            reconcile: options.reconcile,
+            preview: options.preview,
+            segment_start: 0,
+            segment_item: None,
+            segment_mixed: false,
+        }
+    }
+
+    /// Writes a delta to the sink and records it.
+    pub(crate) fn type_delta(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let mut to_type = text.to_string();
+        if !self.typed_first {
+            // The realtime model prefixes the first delta of a session with a
+            // leading space; strip it so dictation doesn't begin with a stray
+            // space (especially annoying at the start of a new line).
+            to_type = to_type.trim_start().to_string();
+            if to_type.is_empty() {
+                return; // delta was only whitespace; wait for real text
+            }
+            if self.cap_first {
+                capitalize_first_letter(&mut to_type);
+            }
+        }
+        self.typed_first = true;
+        self.accumulated.push_str(&to_type);
+        match &self.preview {
+            Some(preview) => {
+                let _ = preview.send(PreviewEvent::Text(self.accumulated.clone()));
+            }
+            None => {
+                if let Err(e) = self.sink.lock().append(&to_type) {
+                    log_line(&format!("Failed to output live text: {e:?}"));
+                }
+            }
+        }
+    }
+
+    /// True once any non-whitespace text has been typed.
+    pub(crate) fn has_typed(&self) -> bool {
+        self.typed_first
+    }
+
+    /// Everything typed so far.
+    pub(crate) fn text(&self) -> &str {
+        &self.accumulated
+    }
+
+    pub(crate) fn into_text(self) -> String {
+        self.accumulated
+    }
+
+    /// Records which conversation item a delta belongs to. Reconciliation is
+    /// skipped for a segment whose typed text spans several items, since the
+    /// tail being corrected would not be this item's text alone.
+    fn note_item(&mut self, item_id: Option<&str>) {
+        match (&self.segment_item, item_id) {
+            (None, Some(id)) => self.segment_item = Some(id.to_string()),
+            (Some(current), Some(id)) if current != id => self.segment_mixed = true,
+            _ => {}
+        }
+    }
+
+    /// Finishes the current segment. With reconciliation on, the text typed
+    /// for it is compared with the final `transcript` and the differing tail
+    /// is erased with Backspace and retyped.
+    fn complete_segment(&mut self, item_id: Option<&str>, transcript: Option<&str>) {
+        let same_item = match (&self.segment_item, item_id) {
+            (Some(current), Some(id)) => current == id,
+            _ => true,
+        };
+        if self.reconcile && same_item && !self.segment_mixed {
+            if let Some(transcript) = transcript {
+                self.reconcile_segment(transcript);
+            }
+        }
+        self.segment_start = self.accumulated.len();
+        self.segment_item = None;
+        self.segment_mixed = false;
+    }
+
+    fn reconcile_segment(&mut self, transcript: &str) {
+        let typed = &self.accumulated[self.segment_start..];
+        let body = transcript.trim();
+        // An empty final transcript would erase the whole segment; more likely
+        // the event is incomplete, so leave the text alone.
+        if body.is_empty() || typed.trim().is_empty() {
+            return;
+        }
+        let mut body = body.to_string();
+        if self.segment_start == 0 && self.cap_first {
+            capitalize_first_letter(&mut body);
+        }
+        // Keep the surrounding whitespace exactly as typed (the segment-boundary
+        // space in particular).
+        let lead = &typed[..typed.len() - typed.trim_start().len()];
+        let trail = &typed[typed.trim_end().len()..];
+        let expected = format!("{lead}{body}{trail}");
+
+        let (erase, retype) = tail_edit(typed, &expected);
+        if erase == 0 && retype.is_empty() {
+            return;
+        }
+        let typed = typed.to_string();
+        if self.edit_tail(erase, retype) {
+            log_line(&format!(
+                "Reconciled segment: {typed:?} -> {expected:?} ({erase} backspaces)"
+            ));
+        } else {
+            log_line("Output can't take text back; skipping reconciliation");
+        }
+    }
+
+    /// Ends the session with `transcript`, the batch transcript of the whole
+    /// unfinished segment. Text typed live for the segment stays as far as it
+    /// agrees with the transcript (ignoring case), even if it stops mid-word;
+    /// from there on the transcript replaces it.
+    pub(crate) fn finish_segment_with(&mut self, transcript: &str) {
+        let mut body = transcript.trim().to_string();
+        if body.is_empty() {
+            return;
+        }
+        let segment = &self.accumulated[self.segment_start..];
+        let shown = segment.trim_start();
+        if shown.is_empty() {
+            self.type_delta(&format!(" {body}"));
+            return;
+        }
+        if self.segment_start == 0 && self.cap_first {
+            capitalize_first_letter(&mut body);
+        }
+        let shown_chars = shown.chars().count();
+        let (erase, retype) = fallback_edit(shown, &body);
+        if !self.edit_tail(erase, retype) {
+            // Keep what was typed and add whatever the transcript has beyond it.
+            let rest: String = body.chars().skip(shown_chars).collect();
+            self.type_delta(&rest);
+        }
+    }
+
+    /// Erases the last `erase` characters written and writes `retype`.
+    /// Returns false, changing nothing, if the output can't take text back.
+    fn edit_tail(&mut self, erase: usize, retype: &str) -> bool {
+        if self.preview.is_none() {
+            let mut sink = self.sink.lock();
+            if erase > 0 {
+                match sink.erase(erase) {
+                    Ok(true) => {}
+                    Ok(false) => return false,
+                    Err(e) => {
+                        log_line(&format!("Failed to erase live text: {e:?}"));
+                        return false;
+                    }
+                }
+            }
+            if !retype.is_empty() {
+                if let Err(e) = sink.append(retype) {
+                    log_line(&format!("Failed to output live text: {e:?}"));
+                }
+            }
+        }
+        let keep = self.accumulated.len()
+            - self
+                .accumulated
+                .chars()
+                .rev()
+                .take(erase)
+                .map(char::len_utf8)
+                .sum::<usize>();
+        self.accumulated.truncate(keep);
+        self.accumulated.push_str(retype);
+        if let Some(preview) = &self.preview {
+            let _ = preview.send(PreviewEvent::Text(self.accumulated.clone()));
+        }
+        true
+    }
+}
+
+/// The cursor sits at the end of the typed text, so it can only be corrected
+/// from the end: returns how many characters to erase with Backspace and what
+/// to type after that to turn `typed` into `expected`.
+fn tail_edit<'a>(typed: &str, expected: &'a str) -> (usize, &'a str) {
+    let common: usize = typed
+        .chars()
+        .zip(expected.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    (typed[common..].chars().count(), &expected[common..])
+}
+
+/// Like [`tail_edit`], but case-insensitive: the batch fallback's transcript
+/// is compared with what was typed live, whose case may differ.
+fn fallback_edit<'a>(typed: &str, transcript: &'a str) -> (usize, &'a str) {
+    let mut common = (0, 0);
+    for ((i, a), (j, b)) in typed.char_indices().zip(transcript.char_indices()) {
+        if !a.to_lowercase().eq(b.to_lowercase()) {
+            break;
+        }
+        common = (i + a.len_utf8(), j + b.len_utf8());
+    }
+    (typed[common.0..].chars().count(), &transcript[common.1..])
 }
 
 /// Processes one server event. Returns `Ok(true)` when the event signals that
 /// the committed turn has finished transcribing (a `...completed` event), so
 /// the caller can stop draining immediately instead of waiting out a timeout.
-#[allow(clippy::too_many_arguments)]
 fn process_event(
     text: &str,
-    enigo: &mut Enigo,
-    accumulated: &mut String,
-    typed_first: &mut bool,
+    typer: &mut LiveTyper,
     pending_space: &mut bool,
-    handle_delta: &mut impl FnMut(&str, &mut Enigo, &mut String, &mut bool),
+    recovery: &mut Recovery,
 ) -> Result<bool> {
     let event: Value = match serde_json::from_str(text) {
         Ok(v) => v,
@@ -527,16 +1349,24 @@ fn process_event(
         // Incremental transcript text.
         "conversation.item.input_audio_transcription.delta" => {
             if let Some(delta) = extract_text(&event, "delta") {
+                // After a reconnect the segment is transcribed again from the
+                // start; drop whatever was already typed before the drop.
+                let delta = recovery.filter_delta(&delta);
+                if delta.is_empty() {
+                    return Ok(false);
+                }
+                recovery.typed.push_str(&delta);
+                typer.note_item(event.get("item_id").and_then(|v| v.as_str()));
                 // Insert a space at a segment boundary if neither side has one.
                 let needs_space = *pending_space
-                    && !accumulated.is_empty()
-                    && !accumulated.ends_with(char::is_whitespace)
+                    && !typer.text().is_empty()
+                    && !typer.text().ends_with(char::is_whitespace)
                     && !delta.starts_with(char::is_whitespace);
                 *pending_space = false;
                 if needs_space {
-                    handle_delta(&format!(" {delta}"), enigo, accumulated, typed_first);
+                    typer.type_delta(&format!(" {delta}"));
                 } else {
-                    handle_delta(&delta, enigo, accumulated, typed_first);
+                    typer.type_delta(&delta);
                 }
             }
             return Ok(false);
@@ -545,14 +1375,30 @@ fn process_event(
         // Mark that the next segment should start with a space, and signal the
         // turn is complete so the caller can stop waiting immediately.
         "conversation.item.input_audio_transcription.completed" => {
-            if let Some(t) = extract_text(&event, "transcript") {
+            let transcript = extract_text(&event, "transcript");
+            if let Some(t) = &transcript {
                 log_line(&format!("Segment completed: {t:?}"));
             }
-            if *typed_first {
+            typer.complete_segment(
+                event.get("item_id").and_then(|v| v.as_str()),
+                transcript.as_deref(),
+            );
+            if typer.has_typed() {
                 *pending_space = true;
             }
+            recovery.segment_completed(event.get("item_id").and_then(|v| v.as_str()));
             return Ok(true);
         }
+        // Server VAD ended a segment; its audio can be dropped once the
+        // segment's transcript completes.
+        "input_audio_buffer.speech_stopped" => {
+            let item_id = event.get("item_id").and_then(|v| v.as_str());
+            let audio_end_ms = event.get("audio_end_ms").and_then(|v| v.as_u64());
+            if let (Some(item_id), Some(audio_end_ms)) = (item_id, audio_end_ms) {
+                recovery.speech_stopped(item_id, audio_end_ms);
+            }
+            log_line(&format!("Server: {event_type}"));
+        }
         // Transcription failed for a segment.
         "conversation.item.input_audio_transcription.failed" => {
             log_line(&format!("Transcription failed event: {text}"));
@@ -588,3 +1434,122 @@ fn capitalize_first_letter(s: &mut String) {
         s.replace_range(0..first_char_len, &uppercase);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{self, RecordingSink, SinkOp};
+
+    fn typer(sink: &RecordingSink, reconcile: bool) -> LiveTyper {
+        LiveTyper::new(LiveTyperOptions {
+            sink: output::shared(Box::new(sink.clone())),
+            cap_first: true,
+            reconcile,
+            preview: None,
+        })
+    }
+
+    #[test]
+    fn first_delta_is_trimmed_and_capitalized() {
+        let sink = RecordingSink::default();
+        let mut typer = typer(&sink, false);
+        typer.type_delta(" ");
+        typer.type_delta(" hello");
+        typer.type_delta(" there");
+        assert_eq!(*sink.text.lock(), "Hello there");
+        assert_eq!(typer.text(), "Hello there");
+    }
+
+    #[test]
+    fn reconcile_retypes_only_the_differing_tail() {
+        let sink = RecordingSink::default();
+        let mut typer = typer(&sink, true);
+        typer.note_item(Some("item_1"));
+        typer.type_delta(" hello wurld");
+        typer.complete_segment(Some("item_1"), Some("hello world"));
+        assert_eq!(*sink.text.lock(), "Hello world");
+        assert_eq!(typer.text(), "Hello world");
+        let ops = sink.ops.lock();
+        assert_eq!(
+            ops[ops.len() - 2..],
+            [SinkOp::Erase(4), SinkOp::Append("orld".to_string())]
+        );
+    }
+
+    #[test]
+    fn fallback_continues_a_word_cut_off_by_the_drop() {
+        let sink = RecordingSink::default();
+        let mut typer = typer(&sink, false);
+        typer.type_delta(" hello wor");
+        typer.finish_segment_with("hello world, how are you?");
+        assert_eq!(*sink.text.lock(), "Hello world, how are you?");
+        assert_eq!(
+            sink.ops.lock().last(),
+            Some(&SinkOp::Append("ld, how are you?".to_string()))
+        );
+
+        // Live text the transcript disagrees with is erased and retyped.
+        typer.complete_segment(None, None);
+        typer.type_delta(" Fine thx");
+        typer.finish_segment_with("fine, thanks.");
+        assert_eq!(typer.text(), "Hello world, how are you? Fine, thanks.");
+        let ops = sink.ops.lock();
+        assert_eq!(
+            ops[ops.len() - 2..],
+            [SinkOp::Erase(4), SinkOp::Append(", thanks.".to_string())]
+        );
+    }
+
+    #[test]
+    fn reconcile_skips_segments_spanning_several_items() {
+        let sink = RecordingSink::default();
+        let mut typer = typer(&sink, true);
+        typer.note_item(Some("item_1"));
+        typer.type_delta("one");
+        typer.note_item(Some("item_2"));
+        typer.type_delta(" two");
+        typer.complete_segment(Some("item_1"), Some("won"));
+        assert_eq!(*sink.text.lock(), "One two");
+    }
+
+    #[test]
+    fn replayed_deltas_are_not_typed_twice() {
+        let mut recovery = Recovery {
+            typed: "Hello wor".to_string(),
+            ..Default::default()
+        };
+        recovery.begin_replay();
+        assert_eq!(recovery.filter_delta(" hello"), "");
+        assert_eq!(recovery.filter_delta(" world"), "ld");
+        assert_eq!(recovery.filter_delta(" again"), " again");
+    }
+
+    #[test]
+    fn completed_vad_segments_keep_later_audio() {
+        let mut recovery = Recovery {
+            audio: vec![0; 24_000 * 2],
+            ..Default::default()
+        };
+        recovery.speech_stopped("item_1", 1_500);
+        recovery.segment_completed(Some("item_1"));
+        assert_eq!(recovery.audio.len(), 12_000);
+        recovery.audio.extend_from_slice(&[0; 12_000]);
+        recovery.speech_stopped("item_2", 2_000);
+        recovery.segment_completed(Some("item_2"));
+        assert_eq!(recovery.audio.len(), 12_000);
+        recovery.segment_completed(None);
+        assert!(recovery.audio.is_empty());
+    }
+
+    #[test]
+    fn realtime_url_follows_the_api_base() {
+        assert_eq!(
+            realtime_url("https://api.openai.com/v1"),
+            "wss://api.openai.com/v1/realtime?intent=transcription"
+        );
+        assert_eq!(
+            realtime_url("http://localhost:8080/v1/"),
+            "ws://localhost:8080/v1/realtime?intent=transcription"
+        );
+    }
+}
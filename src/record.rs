@@ -2,7 +2,8 @@ pub mod rec {
 
     use anyhow::{bail, Context};
     // use clap::Parser;
-    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use crate::config::InputDevice;
+    use cpal::traits::{DeviceTrait, StreamTrait};
     use cpal::{FromSample, Sample};
     use hound::WavWriter;
     // use no_panic::no_panic;
@@ -37,6 +38,12 @@ pub mod rec {
         utils: Option<(Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>, cpal::Stream)>,
     }
 
+    impl Default for Recorder {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl Recorder {
         // #[no_panic]
         pub fn new() -> Self {
@@ -56,14 +63,12 @@ pub mod rec {
         pub fn start_recording(
             &mut self,
             save_location: &Path,
-            device: Option<&str>,
+            device: &InputDevice,
         ) -> Result<(), anyhow::Error> {
             if self.utils.is_some() {
                 bail!("Attempted to start recording when already recording!")
             }
 
-            let device = device.unwrap_or("default");
-
             // ========================
             // let opt = Opt::parse();
 
@@ -99,21 +104,9 @@ pub mod rec {
             //     )),
             //     not(feature = "jack")
             // ))]
-            let host = cpal::default_host();
 
             // Set up the input device and stream with the default input config.
-            let device = match if device == "default" {
-                host.default_input_device()
-            } else {
-                host.input_devices()
-                    .context("Failed to get list of input devices")?
-                    .find(|x| x.name().map(|y| y == device).unwrap_or(false))
-            } {
-                Some(x) => x,
-                None => {
-                    bail!(format!("Failed to find input device '{}'", device))
-                }
-            };
+            let device = device.find()?;
 
             match device.name() {
                 Ok(name) => println!("Input device: {}", name),
@@ -0,0 +1,291 @@
+//! Estimated spend on the OpenAI API, and the daily and monthly budgets.
+//!
+//! Requests are counted as they are sent, since a request that fails or
+//! loses a race is usually billed too: transcriptions by audio length (once
+//! per racing lane and per retry, including the batch fallback of a realtime
+//! stream), realtime streams by how long the key was held, and punctuation
+//! calls by the token usage in the response. Costs use the list prices
+//! below, so they're an estimate; OpenAI's usage page has the actual bill.
+//!
+//! Totals per day are kept in `spending.json` next to `lifetime_stats.json`
+//! and shared by every DeskTalk process (the app, the CLI, file
+//! transcription). Each update locks the file while it reads, adds and
+//! writes back, so concurrent processes don't lose each other's requests.
+//! While a budget is used up, [`limit`] applies the configured
+//! `budget_action` to the settings the engine runs with.
+
+use crate::app_state::{data_file, lock_data_file, write_data_file};
+use crate::config::{AppConfig, BudgetAction, EndPunctuation};
+use anyhow::Context;
+use async_openai::types::Usage;
+use chrono::{Local, NaiveDate};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::{fmt, fs};
+
+/// US dollars per minute of audio.
+const AUDIO_RATES: &[(&str, f64)] = &[("whisper-1", 0.006), ("gpt-realtime-whisper", 0.006)];
+/// US dollars per million prompt and completion tokens.
+const TOKEN_RATES: &[(&str, f64, f64)] = &[("gpt-4o-mini", 0.15, 0.60)];
+
+/// What was spent on one day.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailySpend {
+    pub cost_usd: f64,
+    /// Requests sent, every racing lane and retry counted.
+    pub requests: usize,
+    /// Audio sent, counted once per request it was in.
+    pub audio_secs: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// The contents of `spending.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spending {
+    /// "YYYY-MM-DD" -> spend that day
+    #[serde(default)]
+    pub daily: BTreeMap<String, DailySpend>,
+}
+
+/// A budget that is used up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Daily,
+    Monthly,
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Budget::Daily => "daily",
+            Budget::Monthly => "monthly",
+        })
+    }
+}
+
+impl Spending {
+    fn load() -> Self {
+        let Some(path) = data_file("spending.json") else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                eprintln!("Warning: Ignoring unreadable {:?}: {}", path, err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let path = data_file("spending.json").context("Failed to determine spending path")?;
+        let contents =
+            serde_json::to_string_pretty(self).context("Failed to serialize spending")?;
+        write_data_file(&path, &contents).context("Failed to write spending")?;
+        Ok(())
+    }
+
+    /// Spent on `date`.
+    pub fn day(&self, date: NaiveDate) -> f64 {
+        self.daily
+            .get(&date.format("%Y-%m-%d").to_string())
+            .map_or(0.0, |day| day.cost_usd)
+    }
+
+    /// Spent in the month of `date`.
+    pub fn month(&self, date: NaiveDate) -> f64 {
+        let prefix = date.format("%Y-%m-").to_string();
+        self.daily
+            .range(prefix.clone()..)
+            .take_while(|(day, _)| day.starts_with(&prefix))
+            .map(|(_, day)| day.cost_usd)
+            .sum()
+    }
+
+    /// The budget of `config` that is used up on `date`, if any.
+    pub fn exceeded(&self, config: &AppConfig, date: NaiveDate) -> Option<Budget> {
+        let used_up = |budget: Option<f64>, spent: f64| budget.is_some_and(|b| spent >= b);
+        if used_up(config.monthly_budget, self.month(date)) {
+            Some(Budget::Monthly)
+        } else if used_up(config.daily_budget, self.day(date)) {
+            Some(Budget::Daily)
+        } else {
+            None
+        }
+    }
+}
+
+/// The ledger as this process last read or wrote it.
+static LEDGER: Lazy<Mutex<Spending>> = Lazy::new(|| Mutex::new(Spending::load()));
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Adds to today's spend and saves, on top of what other processes saved.
+fn add(update: impl FnOnce(&mut DailySpend)) {
+    let mut ledger = LEDGER.lock();
+    let _lock = lock_data_file("spending.json");
+    *ledger = Spending::load();
+    let date = today().format("%Y-%m-%d").to_string();
+    update(ledger.daily.entry(date).or_default());
+    if let Err(err) = ledger.save() {
+        eprintln!("Warning: Failed to save spending: {:#}", err);
+    }
+}
+
+/// Counts `requests` requests with `secs` of audio each to `model`.
+pub fn record_audio(model: &str, secs: f64, requests: usize) {
+    let rate = AUDIO_RATES
+        .iter()
+        .find(|(name, _)| *name == model)
+        .map_or(0.0, |(_, rate)| *rate);
+    let secs = secs * requests as f64;
+    add(|day| {
+        day.cost_usd += secs / 60.0 * rate;
+        day.requests += requests;
+        day.audio_secs += secs;
+    });
+}
+
+/// Counts a chat request to `model` with the usage from its response.
+pub fn record_tokens(model: &str, usage: Option<&Usage>) {
+    let (prompt, completion) = usage.map_or((0, 0), |usage| {
+        (usage.prompt_tokens as u64, usage.completion_tokens as u64)
+    });
+    let (prompt_rate, completion_rate) = TOKEN_RATES
+        .iter()
+        .find(|(name, _, _)| *name == model)
+        .map_or((0.0, 0.0), |(_, prompt, completion)| (*prompt, *completion));
+    add(|day| {
+        day.cost_usd +=
+            (prompt as f64 * prompt_rate + completion as f64 * completion_rate) / 1_000_000.0;
+        day.requests += 1;
+        day.prompt_tokens += prompt;
+        day.completion_tokens += completion;
+    });
+}
+
+/// The ledger as saved, for the statistics. Also picks up what other
+/// processes spent.
+pub fn snapshot() -> Spending {
+    let mut ledger = LEDGER.lock();
+    *ledger = Spending::load();
+    ledger.clone()
+}
+
+/// The budget of `config` that is used up today, if any.
+pub fn exceeded(config: &AppConfig) -> Option<Budget> {
+    LEDGER.lock().exceeded(config, today())
+}
+
+/// Spent today and this month.
+pub fn totals() -> (f64, f64) {
+    let ledger = LEDGER.lock();
+    let date = today();
+    (ledger.day(date), ledger.month(date))
+}
+
+/// The settings to run with while `budget` is used up.
+pub fn limit(mut config: AppConfig, budget: Option<Budget>) -> AppConfig {
+    if budget.is_none() {
+        return config;
+    }
+    match config.budget_action {
+        BudgetAction::Warn => {}
+        BudgetAction::Reduce => {
+            config.parallel = 1;
+            config.punctuation = false;
+            let rules = config
+                .app_rules
+                .iter_mut()
+                .map(|rule| &mut rule.end_punctuation);
+            for mode in rules.flatten().chain([&mut config.end_punctuation]) {
+                if *mode == EndPunctuation::Smart {
+                    *mode = EndPunctuation::Period;
+                }
+            }
+        }
+        BudgetAction::Local => config.use_local = true,
+    }
+    config
+}
+
+/// Describes the used-up budget for logs and notifications.
+pub fn describe(config: &AppConfig, budget: Budget) -> String {
+    let (day, month) = totals();
+    let (limit, spent) = match budget {
+        Budget::Daily => (config.daily_budget, day),
+        Budget::Monthly => (config.monthly_budget, month),
+    };
+    let action = match config.budget_action {
+        BudgetAction::Warn => "",
+        BudgetAction::Reduce => "; using cheaper settings",
+        BudgetAction::Local => "; using the local model",
+    };
+    format!(
+        "API {} budget of ${:.2} used up (${:.2} spent){}",
+        budget,
+        limit.unwrap_or_default(),
+        spent,
+        action
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spent(days: &[(&str, f64)]) -> Spending {
+        Spending {
+            daily: days
+                .iter()
+                .map(|(date, cost_usd)| {
+                    let day = DailySpend {
+                        cost_usd: *cost_usd,
+                        ..DailySpend::default()
+                    };
+                    (date.to_string(), day)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn budgets_count_the_day_and_the_month() {
+        let spending = spent(&[
+            ("2026-02-28", 4.0),
+            ("2026-03-01", 0.5),
+            ("2026-03-02", 1.0),
+        ]);
+        let date = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        assert_eq!(spending.month(date), 1.5);
+
+        let mut config = AppConfig {
+            daily_budget: Some(2.0),
+            monthly_budget: Some(5.0),
+            ..AppConfig::default()
+        };
+        assert_eq!(spending.exceeded(&config, date), None);
+        config.daily_budget = Some(1.0);
+        assert_eq!(spending.exceeded(&config, date), Some(Budget::Daily));
+        config.monthly_budget = Some(1.5);
+        assert_eq!(spending.exceeded(&config, date), Some(Budget::Monthly));
+    }
+
+    #[test]
+    fn reducing_drops_the_extra_requests() {
+        let config = AppConfig {
+            parallel: 4,
+            budget_action: BudgetAction::Reduce,
+            ..AppConfig::default()
+        };
+        let limited = limit(config.clone(), Some(Budget::Daily));
+        assert_eq!(limited.parallel, 1);
+        assert_eq!(limited.end_punctuation, EndPunctuation::Period);
+        assert_eq!(limit(config, None).parallel, 4);
+    }
+}
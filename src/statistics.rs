@@ -0,0 +1,188 @@
+//! Usage statistics as shown in the settings window, shared by the Tauri
+//! commands and the D-Bus service.
+
+use crate::app_state::AppState;
+use crate::latency::LatencySummary;
+use crate::spending;
+
+/// Daily data point for the chart
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DailyDataPoint {
+    pub date: String,         // "YYYY-MM-DD"
+    pub display_date: String, // "Jan 15" for display
+    pub words: usize,
+    pub recording_time_secs: f64,
+    pub time_saved_secs: f64,
+    pub transcription_count: usize,
+    pub cost_usd: f64,
+}
+
+/// Combined statistics response for the UI
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CombinedStatistics {
+    // Session stats
+    pub total_words: usize,
+    pub total_recording_time_secs: f64,
+    pub average_wpm: f64,
+    pub session_count: usize,
+    pub time_saved_secs: f64,
+    // Lifetime stats
+    pub lifetime_total_words: usize,
+    pub lifetime_total_recording_time_secs: f64,
+    pub lifetime_average_wpm: f64,
+    pub lifetime_session_count: usize,
+    pub lifetime_time_saved_secs: f64,
+    // Daily averages
+    pub days_tracked: f64,
+    pub avg_time_saved_per_day_secs: f64,
+    pub avg_words_per_day: f64,
+    // Chart data (last 30 days)
+    pub daily_chart: Vec<DailyDataPoint>,
+    // User's typing speed for reference
+    pub typing_wpm: u32,
+    // Parallel racing stats (lifetime, kept in transcription_stats.json)
+    pub racing_total_requests: usize,
+    pub racing_succeeded_requests: usize,
+    pub racing_failed_requests: usize,
+    pub racing_total_races: usize,
+    pub racing_succeeded_races: usize,
+    pub racing_failed_races: usize,
+    pub racing_failures_avoided: usize,
+    pub racing_avg_winning_time_ms: f64,
+    pub racing_avg_all_success_time_ms: f64,
+    pub parallel: usize,
+    // Estimated API spend (see spending.rs)
+    pub spend_today_usd: f64,
+    pub spend_month_usd: f64,
+    pub lifetime_spend_usd: f64,
+    pub daily_budget: Option<f64>,
+    pub monthly_budget: Option<f64>,
+    /// "daily" or "monthly" while that budget is used up
+    pub budget_exceeded: Option<String>,
+    // Transcription latency per backend (last 90 days)
+    pub latency_daily: Vec<LatencySummary>,
+    pub latency_overall: Vec<LatencySummary>,
+}
+
+/// Session, lifetime and racing statistics as shown in the settings window.
+pub fn combined_statistics(state: &AppState) -> CombinedStatistics {
+    use chrono::{Days, Local};
+
+    // Read first, so what other processes spent counts towards the budgets.
+    let spent = spending::snapshot();
+    let session = state.get_statistics();
+    let lifetime = state.get_lifetime_statistics();
+    let config = state.config.read();
+    let typing_wpm = config.typing_wpm;
+    let budget_exceeded = spending::exceeded(&config).map(|budget| budget.to_string());
+    let (daily_budget, monthly_budget) = (config.daily_budget, config.monthly_budget);
+    drop(config);
+
+    // Calculate time saved: time_to_type - actual_recording_time
+    // time_to_type = words / (typing_wpm / 60) = words * 60 / typing_wpm
+    let calc_time_saved = |words: usize, recording_secs: f64, wpm: u32| -> f64 {
+        if wpm == 0 {
+            return 0.0;
+        }
+        let time_to_type_secs = (words as f64) * 60.0 / (wpm as f64);
+        (time_to_type_secs - recording_secs).max(0.0)
+    };
+
+    let time_saved = calc_time_saved(
+        session.total_words,
+        session.total_recording_time_secs,
+        typing_wpm,
+    );
+    let lifetime_time_saved = calc_time_saved(
+        lifetime.total_words,
+        lifetime.total_recording_time_secs,
+        typing_wpm,
+    );
+
+    // Calculate daily average
+    let days_tracked = lifetime.days_since_start();
+    let avg_time_saved_per_day = lifetime_time_saved / days_tracked;
+    let avg_words_per_day = (lifetime.total_words as f64) / days_tracked;
+
+    // Build chart data for last 30 days
+    let today = Local::now().date_naive();
+    let mut daily_chart = Vec::new();
+
+    for i in (0..30).rev() {
+        let date = today.checked_sub_days(Days::new(i)).unwrap_or(today);
+        let date_str = date.format("%Y-%m-%d").to_string();
+        let display_date = date.format("%b %d").to_string();
+
+        let (words, recording_time_secs, transcription_count) =
+            if let Some(stats) = lifetime.daily_stats.get(&date_str) {
+                (
+                    stats.words,
+                    stats.recording_time_secs,
+                    stats.transcription_count,
+                )
+            } else {
+                (0, 0.0, 0)
+            };
+
+        let day_time_saved = calc_time_saved(words, recording_time_secs, typing_wpm);
+        let cost_usd = spent.day(date);
+
+        daily_chart.push(DailyDataPoint {
+            date: date_str,
+            display_date,
+            words,
+            recording_time_secs,
+            time_saved_secs: day_time_saved,
+            transcription_count,
+            cost_usd,
+        });
+    }
+
+    let rs = crate::transcribe::trans::get_racing_stats();
+    let (latency_daily, latency_overall) = crate::transcribe::trans::get_latency_stats();
+    let parallel = state.config.read().parallel;
+
+    CombinedStatistics {
+        // Session stats
+        total_words: session.total_words,
+        total_recording_time_secs: session.total_recording_time_secs,
+        average_wpm: session.average_wpm,
+        session_count: session.session_count,
+        time_saved_secs: time_saved,
+        // Lifetime stats
+        lifetime_total_words: lifetime.total_words,
+        lifetime_total_recording_time_secs: lifetime.total_recording_time_secs,
+        lifetime_average_wpm: lifetime.average_wpm(),
+        lifetime_session_count: lifetime.session_count,
+        lifetime_time_saved_secs: lifetime_time_saved,
+        // Daily averages
+        days_tracked,
+        avg_time_saved_per_day_secs: avg_time_saved_per_day,
+        avg_words_per_day,
+        // Chart data
+        daily_chart,
+        // Config
+        typing_wpm,
+        // Racing stats
+        racing_total_requests: rs.total_requests,
+        racing_succeeded_requests: rs.succeeded_requests,
+        racing_failed_requests: rs.failed_requests,
+        racing_total_races: rs.total_races,
+        racing_succeeded_races: rs.succeeded_races,
+        racing_failed_races: rs.failed_races,
+        racing_failures_avoided: rs.failures_avoided,
+        racing_avg_winning_time_ms: rs.avg_winning_time_ms,
+        racing_avg_all_success_time_ms: rs.avg_all_success_time_ms,
+        parallel,
+        // Spending
+        spend_today_usd: spent.day(today),
+        spend_month_usd: spent.month(today),
+        lifetime_spend_usd: spent.daily.values().map(|day| day.cost_usd).sum(),
+        daily_budget,
+        monthly_budget,
+        budget_exceeded,
+        // Latency
+        latency_daily,
+        latency_overall,
+    }
+}
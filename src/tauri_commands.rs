@@ -1,55 +1,14 @@
-use crate::app_state::AppState;
-use crate::config::AppConfig;
+use crate::app_state::{AppState, TranscriptionRecord};
+use crate::config::{
+    self, AppConfig, BudgetAction, Choice, ConfigError, EndPunctuation, LocalModel, OutputMode,
+    RealtimeDelay, RealtimeOutput,
+};
+use crate::config_share::{self, ImportMode};
+use crate::credentials::{self, Credential};
 use crate::easy_rdev_key::PTTKey;
+use crate::statistics::{combined_statistics, CombinedStatistics};
 use cpal::traits::{DeviceTrait, HostTrait};
 
-/// Daily data point for the chart
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct DailyDataPoint {
-    pub date: String,           // "YYYY-MM-DD"
-    pub display_date: String,   // "Jan 15" for display
-    pub words: usize,
-    pub recording_time_secs: f64,
-    pub time_saved_secs: f64,
-    pub transcription_count: usize,
-}
-
-/// Combined statistics response for the UI
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct CombinedStatistics {
-    // Session stats
-    pub total_words: usize,
-    pub total_recording_time_secs: f64,
-    pub average_wpm: f64,
-    pub session_count: usize,
-    pub time_saved_secs: f64,
-    // Lifetime stats
-    pub lifetime_total_words: usize,
-    pub lifetime_total_recording_time_secs: f64,
-    pub lifetime_average_wpm: f64,
-    pub lifetime_session_count: usize,
-    pub lifetime_time_saved_secs: f64,
-    // Daily averages
-    pub days_tracked: f64,
-    pub avg_time_saved_per_day_secs: f64,
-    pub avg_words_per_day: f64,
-    // Chart data (last 30 days)
-    pub daily_chart: Vec<DailyDataPoint>,
-    // User's typing speed for reference
-    pub typing_wpm: u32,
-    // Parallel racing stats (session only)
-    pub racing_total_requests: usize,
-    pub racing_succeeded_requests: usize,
-    pub racing_failed_requests: usize,
-    pub racing_total_races: usize,
-    pub racing_succeeded_races: usize,
-    pub racing_failed_races: usize,
-    pub racing_failures_avoided: usize,
-    pub racing_avg_winning_time_ms: f64,
-    pub racing_avg_all_success_time_ms: f64,
-    pub parallel: usize,
-}
-
 #[tauri::command]
 pub fn get_config(state: tauri::State<AppState>) -> Result<AppConfig, String> {
     let config = state.config.read().clone();
@@ -60,41 +19,81 @@ pub fn get_config(state: tauri::State<AppState>) -> Result<AppConfig, String> {
     Ok(config)
 }
 
+/// Allowed values of the settings that have a fixed set, so the settings
+/// window's dropdowns come from the same enums the config uses.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConfigOptions {
+    pub output: Vec<Choice>,
+    pub realtime_output: Vec<Choice>,
+    pub end_punctuation: Vec<Choice>,
+    pub realtime_delay: Vec<Choice>,
+    pub local_model: Vec<Choice>,
+    pub budget_action: Vec<Choice>,
+}
+
 #[tauri::command]
-pub fn save_config(state: tauri::State<AppState>, incoming: AppConfig) -> Result<(), String> {
+pub fn get_config_options() -> ConfigOptions {
+    ConfigOptions {
+        output: config::choices::<OutputMode>(),
+        realtime_output: config::choices::<RealtimeOutput>(),
+        end_punctuation: config::choices::<EndPunctuation>(),
+        realtime_delay: config::choices::<RealtimeDelay>(),
+        local_model: config::choices::<LocalModel>(),
+        budget_action: config::choices::<BudgetAction>(),
+    }
+}
+
+/// Rejects an invalid config with one [`ConfigError`] per problem, so the
+/// settings window can point at the fields.
+#[tauri::command]
+pub fn save_config(
+    state: tauri::State<AppState>,
+    incoming: serde_json::Value,
+) -> Result<(), Vec<ConfigError>> {
     println!("=== SAVE CONFIG CALLED ===");
+    let incoming = AppConfig::from_input(incoming)?;
     println!("PTT Key: {:?}", incoming.ptt_key);
     println!("Device: {}", incoming.device);
     println!("API Key present: {}", incoming.api_key.is_some());
 
-    {
-        let mut current = state.config.write();
-
-        // If incoming has an API key, save it to keyring/env
-        if let Some(ref api_key) = incoming.api_key {
-            if !api_key.is_empty() {
-                println!("Saving new API key to keyring/env...");
-                AppConfig::save_api_key(api_key).map_err(|e| {
-                    println!("ERROR saving API key: {}", e);
-                    e.to_string()
-                })?;
-                println!("API key saved successfully");
-            }
+    if let Err(errors) = incoming.validate() {
+        for error in &errors {
+            println!("Invalid setting: {}", error);
         }
+        return Err(errors);
+    }
 
-        *current = incoming;
-        current.save().map_err(|e| {
+    // If incoming has an API key, save it to keyring/env
+    if let Some(ref api_key) = incoming.api_key {
+        if !api_key.is_empty() {
+            println!("Saving new API key to keyring/env...");
+            incoming.save_api_key(api_key).map_err(|e| {
+                println!("ERROR saving API key: {}", e);
+                vec![ConfigError::new("api_key", e)]
+            })?;
+            println!("API key saved successfully");
+        }
+    }
+
+    let mut config = incoming;
+    config
+        .save_profile(state.profile().as_deref())
+        .map_err(|e| {
             println!("ERROR saving config: {}", e);
-            e.to_string()
+            vec![ConfigError::new("", format!("{:#}", e))]
         })?;
 
-        // Reload API key from keyring/env back into memory
-        current.api_key = AppConfig::load_api_key().ok();
-        println!(
-            "API key reloaded from keyring/env: {}",
-            current.api_key.is_some()
-        );
+    // Reload API key from keyring/env back into memory
+    config.api_key = config.load_api_key().ok();
+    println!(
+        "API key reloaded from keyring/env: {}",
+        config.api_key.is_some()
+    );
+    if let Err(e) = config_share::apply_managed(&mut config) {
+        println!("ERROR applying managed config: {:#}", e);
     }
+    // A running engine picks the new settings up before the next utterance.
+    state.set_config(config);
 
     println!("Config saved to disk (without API key)");
     println!("=== CONFIG SAVE COMPLETE ===");
@@ -103,105 +102,75 @@ pub fn save_config(state: tauri::State<AppState>, incoming: AppConfig) -> Result
 }
 
 #[tauri::command]
-pub fn get_statistics(state: tauri::State<AppState>) -> Result<CombinedStatistics, String> {
-    use chrono::{Days, Local};
-    
-    let session = state.get_statistics();
-    let lifetime = state.get_lifetime_statistics();
-    let config = state.config.read();
-    let typing_wpm = config.typing_wpm;
-    drop(config);
-
-    // Calculate time saved: time_to_type - actual_recording_time
-    // time_to_type = words / (typing_wpm / 60) = words * 60 / typing_wpm
-    let calc_time_saved = |words: usize, recording_secs: f64, wpm: u32| -> f64 {
-        if wpm == 0 {
-            return 0.0;
-        }
-        let time_to_type_secs = (words as f64) * 60.0 / (wpm as f64);
-        (time_to_type_secs - recording_secs).max(0.0)
-    };
-
-    let time_saved = calc_time_saved(
-        session.total_words,
-        session.total_recording_time_secs,
-        typing_wpm,
-    );
-    let lifetime_time_saved = calc_time_saved(
-        lifetime.total_words,
-        lifetime.total_recording_time_secs,
-        typing_wpm,
-    );
+pub fn list_credentials() -> Result<Vec<Credential>, String> {
+    credentials::list().map_err(|e| format!("{:#}", e))
+}
 
-    // Calculate daily average
-    let days_tracked = lifetime.days_since_start();
-    let avg_time_saved_per_day = lifetime_time_saved / days_tracked;
-    let avg_words_per_day = (lifetime.total_words as f64) / days_tracked;
-    
-    // Build chart data for last 30 days
-    let today = Local::now().date_naive();
-    let mut daily_chart = Vec::new();
-    
-    for i in (0..30).rev() {
-        let date = today.checked_sub_days(Days::new(i)).unwrap_or(today);
-        let date_str = date.format("%Y-%m-%d").to_string();
-        let display_date = date.format("%b %d").to_string();
-        
-        let (words, recording_time_secs, transcription_count) = 
-            if let Some(stats) = lifetime.daily_stats.get(&date_str) {
-                (stats.words, stats.recording_time_secs, stats.transcription_count)
-            } else {
-                (0, 0.0, 0)
-            };
-        
-        let day_time_saved = calc_time_saved(words, recording_time_secs, typing_wpm);
-        
-        daily_chart.push(DailyDataPoint {
-            date: date_str,
-            display_date,
-            words,
-            recording_time_secs,
-            time_saved_secs: day_time_saved,
-            transcription_count,
-        });
+/// The key stored as `name`, or `None` for a name not used yet.
+#[tauri::command]
+pub fn load_credential_key(name: String) -> Result<Option<String>, String> {
+    match credentials::get(&name).map_err(|e| format!("{:#}", e))? {
+        Some(_) => credentials::load_key(&name)
+            .map(Some)
+            .map_err(|e| format!("{:#}", e)),
+        None => Ok(None),
     }
-    
-    let rs = crate::transcribe::trans::get_racing_stats();
-    let parallel = state.config.read().parallel;
-
-    Ok(CombinedStatistics {
-        // Session stats
-        total_words: session.total_words,
-        total_recording_time_secs: session.total_recording_time_secs,
-        average_wpm: session.average_wpm,
-        session_count: session.session_count,
-        time_saved_secs: time_saved,
-        // Lifetime stats
-        lifetime_total_words: lifetime.total_words,
-        lifetime_total_recording_time_secs: lifetime.total_recording_time_secs,
-        lifetime_average_wpm: lifetime.average_wpm(),
-        lifetime_session_count: lifetime.session_count,
-        lifetime_time_saved_secs: lifetime_time_saved,
-        // Daily averages
-        days_tracked,
-        avg_time_saved_per_day_secs: avg_time_saved_per_day,
-        avg_words_per_day,
-        // Chart data
-        daily_chart,
-        // Config
-        typing_wpm,
-        // Racing stats
-        racing_total_requests: rs.total_requests,
-        racing_succeeded_requests: rs.succeeded_requests,
-        racing_failed_requests: rs.failed_requests,
-        racing_total_races: rs.total_races,
-        racing_succeeded_races: rs.succeeded_races,
-        racing_failed_races: rs.failed_races,
-        racing_failures_avoided: rs.failures_avoided,
-        racing_avg_winning_time_ms: rs.avg_winning_time_ms,
-        racing_avg_all_success_time_ms: rs.avg_all_success_time_ms,
-        parallel,
-    })
+}
+
+#[tauri::command]
+pub fn remove_credential(name: String) -> Result<(), String> {
+    credentials::remove(&name).map_err(|e| format!("{:#}", e))
+}
+
+/// Writes the settings, without the API key, to `path` for others to import.
+#[tauri::command]
+pub fn export_config(state: tauri::State<AppState>, path: String) -> Result<(), String> {
+    let config = state.config.read().clone();
+    config_share::export(&config, path.as_ref()).map_err(|e| format!("{:#}", e))
+}
+
+/// Imports the config file at `path` into the current profile and returns
+/// the names of the settings that changed. Nothing is saved if the result
+/// doesn't validate.
+#[tauri::command]
+pub fn import_config(
+    state: tauri::State<AppState>,
+    path: String,
+    mode: ImportMode,
+) -> Result<Vec<String>, Vec<ConfigError>> {
+    let current = state.config.read().clone();
+    let mut config = config_share::import(&current, path.as_ref(), mode)?;
+    config.validate()?;
+    let changed = config_share::changed_settings(&current, &config);
+    config
+        .save_profile(state.profile().as_deref())
+        .map_err(|e| vec![ConfigError::new("", format!("{:#}", e))])?;
+    config_share::apply_managed(&mut config)
+        .map_err(|e| vec![ConfigError::new("", format!("{:#}", e))])?;
+    println!("Imported {} ({} settings changed)", path, changed.len());
+    state.set_config(config);
+    Ok(changed)
+}
+
+/// Names of the settings the managed config enforces.
+#[tauri::command]
+pub fn get_managed_settings() -> Result<Vec<String>, String> {
+    let settings = config_share::managed_settings().map_err(|e| format!("{:#}", e))?;
+    Ok(settings
+        .map(|settings| settings.keys().cloned().collect())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_statistics(state: tauri::State<AppState>) -> Result<CombinedStatistics, String> {
+    Ok(combined_statistics(&state))
+}
+
+#[tauri::command]
+pub fn get_last_transcription(
+    state: tauri::State<AppState>,
+) -> Result<Option<TranscriptionRecord>, String> {
+    Ok(state.last_transcription())
 }
 
 #[tauri::command]
@@ -1,5 +1,9 @@
 pub mod trans {
 
+    use crate::app_state::{data_file, lock_data_file, write_data_file};
+    use crate::credentials::child_command;
+    use crate::latency::{LatencyLog, LatencySummary};
+    use crate::spending;
     use anyhow::{anyhow, bail, Context};
     use async_openai::{
         config::OpenAIConfig,
@@ -12,18 +16,21 @@ pub mod trans {
     use async_std::future;
     use directories::ProjectDirs;
     use mutter::{Model, ModelType};
+    use once_cell::sync::Lazy;
+    use serde::{Deserialize, Serialize};
     use std::fs;
     use std::time::Duration;
     use std::{
         error::Error,
         path::{Path, PathBuf},
-        process::Command,
     };
 
     use std::sync::atomic::{AtomicUsize, Ordering};
     use tempfile::tempdir;
     use ureq;
 
+    #[derive(Default, Serialize, Deserialize)]
+    #[serde(default)]
     struct RacingStats {
         total_requests: AtomicUsize,
         succeeded_requests: AtomicUsize,
@@ -41,31 +48,169 @@ pub mod trans {
         all_success_count: AtomicUsize,
     }
 
-    static RACING_STATS: RacingStats = RacingStats {
-        total_requests: AtomicUsize::new(0),
-        succeeded_requests: AtomicUsize::new(0),
-        failed_requests: AtomicUsize::new(0),
-        total_races: AtomicUsize::new(0),
-        succeeded_races: AtomicUsize::new(0),
-        failed_races: AtomicUsize::new(0),
-        failures_avoided: AtomicUsize::new(0),
-        winning_time_ms_sum: AtomicUsize::new(0),
-        all_success_time_ms_sum: AtomicUsize::new(0),
-        all_success_count: AtomicUsize::new(0),
-    };
+    impl RacingStats {
+        fn counters(&self) -> [&AtomicUsize; 10] {
+            [
+                &self.total_requests,
+                &self.succeeded_requests,
+                &self.failed_requests,
+                &self.total_races,
+                &self.succeeded_races,
+                &self.failed_races,
+                &self.failures_avoided,
+                &self.winning_time_ms_sum,
+                &self.all_success_time_ms_sum,
+                &self.all_success_count,
+            ]
+        }
+    }
+
+    const TRANSCRIPTION_MODEL: &str = "whisper-1";
+    const PUNCTUATION_MODEL: &str = "gpt-4o-mini";
 
     const BASE_TIMEOUT_SECS: f64 = 20.0;
     const DEFAULT_RATIO: f64 = 1.0;
     const SAFETY_MULTIPLIER: f64 = 1.5;
     const ADAPTIVE_MAX_SAMPLES: usize = 50;
 
+    #[derive(Default, Serialize, Deserialize)]
+    #[serde(default)]
     struct AdaptiveTimeout {
         ratios: std::sync::Mutex<Vec<f64>>,
+        /// How many of the last `ratios` this process added since it saved.
+        #[serde(skip)]
+        unsaved: AtomicUsize,
     }
 
-    static ADAPTIVE_TIMEOUT: AdaptiveTimeout = AdaptiveTimeout {
-        ratios: std::sync::Mutex::new(Vec::new()),
-    };
+    /// Racing counters, adaptive timeout samples and latency histograms,
+    /// kept across runs in `transcription_stats.json` next to
+    /// `lifetime_stats.json` so the timeout doesn't relearn every launch.
+    /// The app and the CLI share the file; each save adds what this process
+    /// recorded since its last one to what is saved.
+    #[derive(Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Stats {
+        racing: RacingStats,
+        timeout: AdaptiveTimeout,
+        latency: std::sync::Mutex<LatencyLog>,
+        /// The racing counters as last loaded or saved.
+        #[serde(skip)]
+        racing_saved: std::sync::Mutex<[usize; 10]>,
+        /// Latency samples recorded since the last save.
+        #[serde(skip)]
+        unsaved_latency: std::sync::Mutex<LatencyLog>,
+        #[serde(skip)]
+        saving: std::sync::Mutex<()>,
+    }
+
+    static STATS: Lazy<Stats> = Lazy::new(Stats::load);
+
+    impl Stats {
+        fn load() -> Self {
+            let Some(path) = data_file("transcription_stats.json") else {
+                return Self::default();
+            };
+            let stats: Self = match fs::read_to_string(&path) {
+                Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                    eprintln!("Warning: Ignoring unreadable {:?}: {}", path, err);
+                    Self::default()
+                }),
+                Err(_) => Self::default(),
+            };
+            *stats.racing_saved.lock().unwrap() = stats
+                .racing
+                .counters()
+                .map(|counter| counter.load(Ordering::Relaxed));
+            stats
+        }
+
+        fn record_latency(&self, backend: &str, elapsed: Duration) {
+            let mut latency = self.latency.lock().unwrap();
+            latency.record(backend, elapsed);
+            self.unsaved_latency
+                .lock()
+                .unwrap()
+                .record(backend, elapsed);
+        }
+
+        /// Adds what this process recorded since it last saved to `saved`,
+        /// the statistics other processes saved, and takes the result as
+        /// its own.
+        fn merge_unsaved(&self, saved: &mut Stats) {
+            let mut racing_saved = self.racing_saved.lock().unwrap();
+            for ((own, theirs), last) in self
+                .racing
+                .counters()
+                .into_iter()
+                .zip(saved.racing.counters())
+                .zip(racing_saved.iter_mut())
+            {
+                let their_count = theirs.load(Ordering::Relaxed);
+                let total = their_count + own.load(Ordering::Relaxed).saturating_sub(*last);
+                theirs.store(total, Ordering::Relaxed);
+                // Adjusted rather than set, so increments made meanwhile stay.
+                if their_count >= *last {
+                    own.fetch_add(their_count - *last, Ordering::Relaxed);
+                } else {
+                    own.fetch_sub(*last - their_count, Ordering::Relaxed);
+                }
+                *last = total;
+            }
+
+            let mut ratios = self.timeout.ratios.lock().unwrap();
+            let unsaved = self
+                .timeout
+                .unsaved
+                .swap(0, Ordering::Relaxed)
+                .min(ratios.len());
+            let merged = saved.timeout.ratios.get_mut().unwrap();
+            merged.extend_from_slice(&ratios[ratios.len() - unsaved..]);
+            let excess = merged.len().saturating_sub(ADAPTIVE_MAX_SAMPLES);
+            merged.drain(..excess);
+            ratios.clone_from(merged);
+
+            let mut latency = self.latency.lock().unwrap();
+            let unsaved = std::mem::take(&mut *self.unsaved_latency.lock().unwrap());
+            let merged = saved.latency.get_mut().unwrap();
+            merged.merge(&unsaved);
+            latency.clone_from(merged);
+        }
+
+        /// Saves the statistics, on top of what other processes saved; must
+        /// not be called while holding one of their locks.
+        fn save(&self) {
+            let _saving = self.saving.lock().unwrap();
+            let _lock = lock_data_file("transcription_stats.json");
+            let mut stats = Self::load();
+            self.merge_unsaved(&mut stats);
+            let saved = data_file("transcription_stats.json")
+                .context("Failed to determine stats path")
+                .and_then(|path| {
+                    let contents = serde_json::to_string_pretty(&stats)
+                        .context("Failed to serialize transcription stats")?;
+                    write_data_file(&path, &contents).context("Failed to write transcription stats")
+                });
+            if let Err(err) = saved {
+                eprintln!(
+                    "Warning: Failed to save transcription statistics: {:#}",
+                    err
+                );
+            }
+        }
+    }
+
+    /// Adds a transcription latency sample for `backend` ("openai",
+    /// "local" or "realtime") and saves.
+    pub fn record_latency(backend: &str, elapsed: Duration) {
+        STATS.record_latency(backend, elapsed);
+        STATS.save();
+    }
+
+    /// Latency percentiles per backend for each day, and over all days.
+    pub fn get_latency_stats() -> (Vec<LatencySummary>, Vec<LatencySummary>) {
+        let latency = STATS.latency.lock().unwrap();
+        (latency.daily(), latency.overall())
+    }
 
     impl AdaptiveTimeout {
         fn record(&self, recording_duration_secs: f64, api_secs: f64) {
@@ -78,6 +223,7 @@ pub mod trans {
                 ratios.remove(0);
             }
             ratios.push(ratio);
+            self.unsaved.fetch_add(1, Ordering::Relaxed);
             eprintln!(
                 "Adaptive timeout: recorded ratio {:.2} ({:.1}s API / {:.1}s audio), p90 now {:.2} ({} samples)",
                 ratio, api_secs, recording_duration_secs,
@@ -114,7 +260,7 @@ pub mod trans {
         output.set_extension("mp3");
 
         // `ffmpeg -i input.mp4 -q:a 0 -map a output.mp3`
-        let mut cmd = Command::new("ffmpeg");
+        let mut cmd = child_command("ffmpeg");
 
         #[cfg(windows)]
         {
@@ -172,7 +318,7 @@ pub mod trans {
 
         let request = CreateTranscriptionRequestArgs::default()
             .file(input_mp3)
-            .model("whisper-1")
+            .model(TRANSCRIPTION_MODEL)
             .prompt("And now, a transcription from random language(s) that concludes with perfect punctuation: ")
             .build()
             .context("Failed to build transcription request.")?;
@@ -197,7 +343,7 @@ pub mod trans {
         recording_duration_secs: f64,
     ) -> Result<String, Box<dyn Error>> {
         let mut last_err: Option<Box<dyn Error>> = None;
-        let timeout_dur = ADAPTIVE_TIMEOUT.compute_timeout(recording_duration_secs);
+        let timeout_dur = STATS.timeout.compute_timeout(recording_duration_secs);
         eprintln!(
             "Transcription timeout: {:.1}s for {:.1}s of audio ({} attempts)",
             timeout_dur.as_secs_f64(), recording_duration_secs, attempts
@@ -205,11 +351,15 @@ pub mod trans {
 
         for attempt in 0..attempts {
             let attempt_start = std::time::Instant::now();
+            spending::record_audio(TRANSCRIPTION_MODEL, recording_duration_secs, 1);
             match future::timeout(timeout_dur, transcribe(client, input)).await {
                 Ok(res) => match res {
                     Ok(text) => {
-                        let api_secs = attempt_start.elapsed().as_secs_f64();
-                        ADAPTIVE_TIMEOUT.record(recording_duration_secs, api_secs);
+                        let elapsed = attempt_start.elapsed();
+                        STATS
+                            .timeout
+                            .record(recording_duration_secs, elapsed.as_secs_f64());
+                        record_latency("openai", elapsed);
                         return Ok(text);
                     }
                     Err(e) => {
@@ -254,7 +404,7 @@ pub mod trans {
             return transcribe_with_retry(client, input, 3, recording_duration_secs).await;
         }
 
-        let timeout_dur = ADAPTIVE_TIMEOUT.compute_timeout(recording_duration_secs);
+        let timeout_dur = STATS.timeout.compute_timeout(recording_duration_secs);
         eprintln!(
             "Racing {} parallel transcription requests (timeout: {:.1}s for {:.1}s of audio)",
             parallel, timeout_dur.as_secs_f64(), recording_duration_secs
@@ -277,9 +427,11 @@ pub mod trans {
         let first_success_time: std::sync::Arc<std::sync::Mutex<Option<std::time::Instant>>> =
             std::sync::Arc::new(std::sync::Mutex::new(None));
 
-        RACING_STATS
+        STATS
+            .racing
             .total_requests
             .fetch_add(parallel, Ordering::Relaxed);
+        spending::record_audio(TRANSCRIPTION_MODEL, recording_duration_secs, parallel);
 
         for i in 0..parallel {
             let client = client.clone();
@@ -299,8 +451,11 @@ pub mod trans {
                         Ok(Ok(text)) => {
                             let elapsed = race_start.elapsed();
                             let elapsed_ms = elapsed.as_millis();
-                            ADAPTIVE_TIMEOUT.record(recording_duration_secs, elapsed.as_secs_f64());
-                            RACING_STATS
+                            STATS
+                                .timeout
+                                .record(recording_duration_secs, elapsed.as_secs_f64());
+                            STATS
+                                .racing
                                 .succeeded_requests
                                 .fetch_add(1, Ordering::Relaxed);
                             let mut first = first_success_time.lock().unwrap();
@@ -326,9 +481,7 @@ pub mod trans {
                         Ok(Err(e)) => {
                             let elapsed = race_start.elapsed();
                             let elapsed_ms = elapsed.as_millis();
-                            RACING_STATS
-                                .failed_requests
-                                .fetch_add(1, Ordering::Relaxed);
+                            STATS.racing.failed_requests.fetch_add(1, Ordering::Relaxed);
                             let extra = first_success_time
                                 .lock()
                                 .unwrap()
@@ -354,9 +507,7 @@ pub mod trans {
                         Err(_) => {
                             let elapsed = race_start.elapsed();
                             let elapsed_ms = elapsed.as_millis();
-                            RACING_STATS
-                                .failed_requests
-                                .fetch_add(1, Ordering::Relaxed);
+                            STATS.racing.failed_requests.fetch_add(1, Ordering::Relaxed);
                             let extra = first_success_time
                                 .lock()
                                 .unwrap()
@@ -405,39 +556,47 @@ pub mod trans {
             }
         }
 
-        RACING_STATS.total_races.fetch_add(1, Ordering::Relaxed);
+        STATS.racing.total_races.fetch_add(1, Ordering::Relaxed);
 
         let race_succeeded = first_text.is_some();
         if race_succeeded {
-            RACING_STATS.succeeded_races.fetch_add(1, Ordering::Relaxed);
+            STATS.racing.succeeded_races.fetch_add(1, Ordering::Relaxed);
 
             if had_failure {
-                RACING_STATS
+                STATS
+                    .racing
                     .failures_avoided
                     .fetch_add(1, Ordering::Relaxed);
             }
 
             if let Some(&fastest) = success_times_ms.iter().min() {
-                RACING_STATS
+                let fastest_dur = Duration::from_millis(fastest as u64);
+                STATS.record_latency("openai", fastest_dur);
+                STATS
+                    .racing
                     .winning_time_ms_sum
                     .fetch_add(fastest as usize, Ordering::Relaxed);
             }
             let total_success_ms: u128 = success_times_ms.iter().sum();
-            RACING_STATS
+            STATS
+                .racing
                 .all_success_time_ms_sum
                 .fetch_add(total_success_ms as usize, Ordering::Relaxed);
-            RACING_STATS
+            STATS
+                .racing
                 .all_success_count
                 .fetch_add(success_times_ms.len(), Ordering::Relaxed);
         } else {
-            RACING_STATS.failed_races.fetch_add(1, Ordering::Relaxed);
+            STATS.racing.failed_races.fetch_add(1, Ordering::Relaxed);
         }
 
-        let total_req = RACING_STATS.total_requests.load(Ordering::Relaxed);
-        let ok_req = RACING_STATS.succeeded_requests.load(Ordering::Relaxed);
-        let total_race = RACING_STATS.total_races.load(Ordering::Relaxed);
-        let ok_race = RACING_STATS.succeeded_races.load(Ordering::Relaxed);
-        let avoided = RACING_STATS.failures_avoided.load(Ordering::Relaxed);
+        STATS.save();
+
+        let total_req = STATS.racing.total_requests.load(Ordering::Relaxed);
+        let ok_req = STATS.racing.succeeded_requests.load(Ordering::Relaxed);
+        let total_race = STATS.racing.total_races.load(Ordering::Relaxed);
+        let ok_race = STATS.racing.succeeded_races.load(Ordering::Relaxed);
+        let avoided = STATS.racing.failures_avoided.load(Ordering::Relaxed);
 
         let req_pct = if total_req > 0 {
             (ok_req as f64 / total_req as f64) * 100.0
@@ -476,7 +635,7 @@ pub mod trans {
         Ok(cache_dir.join(filename))
     }
 
-    fn load_or_download_model(model: &ModelType) -> Result<Model, Box<dyn Error>> {
+    pub fn load_or_download_model(model: &ModelType) -> Result<Model, Box<dyn Error>> {
         use std::io::Read;
 
         let path = get_model_path(model)?;
@@ -497,10 +656,18 @@ pub mod trans {
 
     pub fn transcribe_local(input: &Path, model_type: ModelType) -> Result<String, Box<dyn Error>> {
         let model = load_or_download_model(&model_type)?;
+        transcribe_local_with(&model, input)
+    }
+
+    /// Like [`transcribe_local`], with a model that is already loaded (for
+    /// transcribing many files without reloading it each time).
+    pub fn transcribe_local_with(model: &Model, input: &Path) -> Result<String, Box<dyn Error>> {
         let bytes = fs::read(input)?;
+        let start = std::time::Instant::now();
         let res = model
             .transcribe_audio(bytes, false, false, None)
             .map_err(|e| anyhow!("{:?}", e))?;
+        record_latency("local", start.elapsed());
 
         let mut res = res.as_text();
         res = res.replace("\n", " "); // Remove double spaces
@@ -531,7 +698,7 @@ pub mod trans {
         };
 
         let request = CreateChatCompletionRequestArgs::default()
-            .model("gpt-4o-mini")
+            .model(PUNCTUATION_MODEL)
             .messages(vec![system_message, user_message])
             .temperature(0.2)
             .build()
@@ -542,6 +709,7 @@ pub mod trans {
             .create(request)
             .await
             .context("Failed to get punctuation response")?;
+        spending::record_tokens(PUNCTUATION_MODEL, response.usage.as_ref());
 
         response
             .choices
@@ -603,7 +771,7 @@ pub mod trans {
         };
 
         let request = CreateChatCompletionRequestArgs::default()
-            .model("gpt-4o-mini")
+            .model(PUNCTUATION_MODEL)
             .messages(vec![system_message, user_message])
             .temperature(0.0)
             .max_tokens(4u16)
@@ -615,6 +783,7 @@ pub mod trans {
                 Ok(result) => result.context("Punctuation decision request failed")?,
                 Err(_) => return Err(anyhow!("Punctuation decision timed out").into()),
             };
+        spending::record_tokens(PUNCTUATION_MODEL, response.usage.as_ref());
 
         let raw = response
             .choices
@@ -657,16 +826,16 @@ pub mod trans {
     }
 
     pub fn get_racing_stats() -> RacingStatsSnapshot {
-        let total_requests = RACING_STATS.total_requests.load(Ordering::Relaxed);
-        let succeeded_requests = RACING_STATS.succeeded_requests.load(Ordering::Relaxed);
-        let failed_requests = RACING_STATS.failed_requests.load(Ordering::Relaxed);
-        let total_races = RACING_STATS.total_races.load(Ordering::Relaxed);
-        let succeeded_races = RACING_STATS.succeeded_races.load(Ordering::Relaxed);
-        let failed_races = RACING_STATS.failed_races.load(Ordering::Relaxed);
-        let failures_avoided = RACING_STATS.failures_avoided.load(Ordering::Relaxed);
-        let winning_sum = RACING_STATS.winning_time_ms_sum.load(Ordering::Relaxed);
-        let all_sum = RACING_STATS.all_success_time_ms_sum.load(Ordering::Relaxed);
-        let all_count = RACING_STATS.all_success_count.load(Ordering::Relaxed);
+        let total_requests = STATS.racing.total_requests.load(Ordering::Relaxed);
+        let succeeded_requests = STATS.racing.succeeded_requests.load(Ordering::Relaxed);
+        let failed_requests = STATS.racing.failed_requests.load(Ordering::Relaxed);
+        let total_races = STATS.racing.total_races.load(Ordering::Relaxed);
+        let succeeded_races = STATS.racing.succeeded_races.load(Ordering::Relaxed);
+        let failed_races = STATS.racing.failed_races.load(Ordering::Relaxed);
+        let failures_avoided = STATS.racing.failures_avoided.load(Ordering::Relaxed);
+        let winning_sum = STATS.racing.winning_time_ms_sum.load(Ordering::Relaxed);
+        let all_sum = STATS.racing.all_success_time_ms_sum.load(Ordering::Relaxed);
+        let all_count = STATS.racing.all_success_count.load(Ordering::Relaxed);
 
         let avg_winning_time_ms = if succeeded_races > 0 {
             winning_sum as f64 / succeeded_races as f64
@@ -691,4 +860,37 @@ pub mod trans {
             avg_all_success_time_ms,
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn saving_adds_to_what_other_processes_saved() {
+            let ours = Stats::default();
+            ours.racing.total_races.store(3, Ordering::Relaxed);
+            ours.timeout.record(10.0, 5.0);
+            ours.record_latency("openai", Duration::from_millis(700));
+
+            let mut theirs = Stats::default();
+            theirs.racing.total_races.store(5, Ordering::Relaxed);
+            theirs.timeout.record(10.0, 2.0);
+            theirs.record_latency("local", Duration::from_millis(300));
+
+            ours.merge_unsaved(&mut theirs);
+            assert_eq!(theirs.racing.total_races.load(Ordering::Relaxed), 8);
+            assert_eq!(*theirs.timeout.ratios.lock().unwrap(), [0.2, 0.5]);
+            assert_eq!(theirs.latency.lock().unwrap().overall().len(), 2);
+            assert_eq!(ours.racing.total_races.load(Ordering::Relaxed), 8);
+            assert_eq!(ours.latency.lock().unwrap().overall().len(), 2);
+
+            // Saved already; only new samples are added the next time.
+            let mut again = Stats::default();
+            again.racing.total_races.store(8, Ordering::Relaxed);
+            ours.racing.total_races.fetch_add(1, Ordering::Relaxed);
+            ours.merge_unsaved(&mut again);
+            assert_eq!(again.racing.total_races.load(Ordering::Relaxed), 9);
+            assert!(again.timeout.ratios.lock().unwrap().is_empty());
+        }
+    }
 }
@@ -1,19 +1,25 @@
-use crate::app_state::AppState;
-use crate::config::AppConfig;
+use crate::active_window::{self, ActiveWindow};
+use crate::app_state::{AppState, TranscriptionRecord};
+use crate::config::{AppConfig, EndPunctuation, OutputMode, RealtimeOutput};
+use crate::events::EngineEvent;
+use crate::local_stream::LocalStreamingSession;
+use crate::output::{self, SharedSink};
+use crate::preview::PreviewEvent;
+use crate::realtime::{LiveTyperOptions, RealtimePool, RealtimeSession};
 use crate::record::rec;
+use crate::spending::{self, Budget};
 use crate::transcribe::trans;
-use async_openai::Client;
-use clipboard::{ClipboardContext, ClipboardProvider};
+use anyhow::Context;
+use async_openai::{config::OpenAIConfig, Client};
 use default_device_sink::DefaultDeviceSink;
-use enigo::{Enigo, KeyboardControllable};
 use parking_lot::Mutex;
-use rdev::Event;
+use rdev::{Event, Key};
 use rodio::{source::SineWave, Decoder, Source};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::io::{BufReader, Cursor};
 use std::sync::mpsc;
 use std::sync::Arc;
-use std::thread::{self, sleep};
+use std::thread;
 use std::time::Duration;
 use tempfile::tempdir;
 
@@ -95,6 +101,229 @@ fn needs_punctuation_fix(text: &str) -> bool {
     !text.chars().any(|c| matches!(c, '.' | '!' | '?'))
 }
 
+/// Captures the window about to receive an utterance and works out the
+/// settings for it (app rules applied).
+fn utterance_settings(opt: &AppConfig) -> (Option<ActiveWindow>, AppConfig) {
+    let window = active_window::probe();
+    if let Some(window) = &window {
+        println!(
+            "Target window: {} ({}) \"{}\"",
+            window.class, window.process, window.title
+        );
+    }
+    let settings = opt.for_window(window.as_ref());
+    (window, settings)
+}
+
+/// What tells sinks apart: the output mode, and the chord for pasting.
+fn sink_key(settings: &AppConfig) -> String {
+    match settings.output_mode() {
+        OutputMode::Paste => format!("paste {}", settings.paste_shortcut()),
+        mode => mode.to_string(),
+    }
+}
+
+/// The sink for the utterance's output mode, built on first use. Falls back
+/// to `fallback` if that mode can't be set up.
+fn sink_for(
+    sinks: &mut HashMap<String, SharedSink>,
+    settings: &AppConfig,
+    fallback: &SharedSink,
+) -> SharedSink {
+    let key = sink_key(settings);
+    if let Some(sink) = sinks.get(&key) {
+        return sink.clone();
+    }
+    match output::from_config(settings) {
+        Ok(sink) => sinks.entry(key).or_insert(output::shared(sink)).clone(),
+        Err(err) => {
+            eprintln!("Failed to set up \"{}\" output: {:?}", key, err);
+            fallback.clone()
+        }
+    }
+}
+
+fn unix_time(time: std::time::SystemTime) -> i64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// A session that types text live while the PTT key is held: either the OpenAI
+/// realtime stream or the local whisper stream.
+enum LiveSession {
+    Cloud(RealtimeSession),
+    Local(LocalStreamingSession),
+}
+
+impl LiveSession {
+    fn stop(self) -> anyhow::Result<String> {
+        match self {
+            LiveSession::Cloud(session) => session.stop(),
+            LiveSession::Local(session) => session.stop(),
+        }
+    }
+}
+
+/// Checks that `config` can run the engine and returns its PTT key.
+fn check_config(config: &AppConfig) -> anyhow::Result<Key> {
+    let ptt_key = config
+        .get_ptt_key()
+        .ok_or_else(|| anyhow::anyhow!("No push-to-talk key configured"))?;
+    println!("Transcription engine using PTT key: {:?}", ptt_key);
+
+    if !config.use_local && config.api_key.is_none() {
+        return Err(anyhow::anyhow!("No OpenAI API key configured"));
+    }
+
+    if config.use_local && config.local_model.is_none() {
+        return Err(anyhow::anyhow!("No local model selected"));
+    }
+    Ok(ptt_key)
+}
+
+/// What wakes the key handler up.
+enum Wake {
+    Key(Event),
+    ConfigChanged,
+    /// Another engine event, nothing to do.
+    Other,
+    /// The engine was stopped.
+    Closed,
+}
+
+/// Everything the key handler derives from the settings. Built again, as a
+/// whole, when the settings change.
+struct Setup {
+    /// The settings as saved, and `opt`, what runs while a budget is used up.
+    requested: AppConfig,
+    budget: Option<Budget>,
+    opt: AppConfig,
+    key: Key,
+    /// The API key and endpoint, for the batch client and realtime sessions.
+    openai: OpenAIConfig,
+    client: Client<OpenAIConfig>,
+    default_sink: SharedSink,
+    /// Sinks of the output modes used so far, by mode.
+    sinks: HashMap<String, SharedSink>,
+    /// Realtime streaming mode: when enabled (and not using a local model)
+    /// we stream audio to OpenAI and type transcript deltas live, instead
+    /// of recording a WAV file and transcribing it at the end.
+    realtime_enabled: bool,
+    /// Local streaming is the offline equivalent: the in-process whisper
+    /// model transcribes a sliding window while the key is held.
+    local_streaming_enabled: bool,
+    /// Optionally keeps a connection open so the next press doesn't wait on
+    /// the handshake.
+    realtime_pool: Option<RealtimePool>,
+    /// Preview mode shows live text in the overlay instead of typing it, and
+    /// inserts the final text once on release. Without an overlay (no GUI)
+    /// the text is simply not shown until then.
+    preview_enabled: bool,
+    live_output: LiveTyperOptions,
+}
+
+impl Setup {
+    fn new(requested: AppConfig, app_state: &AppState) -> anyhow::Result<Self> {
+        let budget = spending::exceeded(&requested);
+        if let Some(budget) = budget {
+            let message = spending::describe(&requested, budget);
+            eprintln!("Warning: {}", message);
+            app_state.report_error(message);
+        }
+        let opt = spending::limit(requested.clone(), budget);
+        let key = check_config(&opt)?;
+        let default_sink =
+            output::shared(output::from_config(&opt).context("Failed to set up text output")?);
+        let sinks = HashMap::from([(sink_key(&opt), default_sink.clone())]);
+
+        let openai = opt.openai_config()?;
+        let realtime_enabled = opt.realtime && !opt.use_local;
+        let local_streaming_enabled = opt.local_streaming && opt.use_local;
+        if realtime_enabled {
+            println!(
+                "Realtime streaming transcription enabled (model: gpt-realtime-whisper, delay: {})",
+                opt.realtime_delay
+            );
+            println!(
+                "Realtime debug log: {}",
+                crate::realtime::log_path().display()
+            );
+        }
+        let realtime_pool = if realtime_enabled && opt.realtime_prewarm {
+            match opt.api_key.as_ref().map(|_| {
+                RealtimePool::start(
+                    openai.clone(),
+                    "gpt-realtime-whisper".to_string(),
+                    None,
+                    opt.realtime_delay,
+                )
+            }) {
+                Some(Ok(pool)) => {
+                    println!("Realtime connection pre-warming enabled");
+                    Some(pool)
+                }
+                Some(Err(err)) => {
+                    eprintln!("Failed to start realtime pre-warming: {:?}", err);
+                    None
+                }
+                None => None,
+            }
+        } else {
+            None
+        };
+        let preview_enabled = (realtime_enabled || local_streaming_enabled)
+            && opt.realtime_output == RealtimeOutput::Preview;
+        let live_output = LiveTyperOptions {
+            sink: default_sink.clone(),
+            cap_first: opt.cap_first,
+            reconcile: opt.realtime_reconcile,
+            preview: if preview_enabled {
+                Some(
+                    app_state
+                        .preview_sender()
+                        .unwrap_or_else(|| flume::unbounded().0),
+                )
+            } else {
+                None
+            },
+        };
+        if preview_enabled {
+            println!("Live text is shown in the preview overlay");
+        }
+        if local_streaming_enabled {
+            println!(
+                "Local streaming transcription enabled (model: {})",
+                opt.local_model
+                    .map_or("none".to_string(), |model| model.to_string())
+            );
+        }
+
+        let client = Client::with_config(openai.clone());
+        Ok(Self {
+            requested,
+            budget,
+            opt,
+            key,
+            openai,
+            client,
+            default_sink,
+            sinks,
+            realtime_enabled,
+            local_streaming_enabled,
+            realtime_pool,
+            preview_enabled,
+            live_output,
+        })
+    }
+
+    /// Whether this setup was built from settings equal to `config`.
+    fn uses(&self, config: &AppConfig) -> bool {
+        spending::exceeded(config) == self.budget
+            && serde_json::to_value(&self.requested).ok() == serde_json::to_value(config).ok()
+    }
+}
+
 pub struct TranscriptionEngine {
     app_state: AppState,
     stop_signal: Arc<Mutex<bool>>,
@@ -109,36 +338,16 @@ impl TranscriptionEngine {
     }
 
     pub fn start(&self) -> anyhow::Result<()> {
-        use std::env;
-
         let config = self.app_state.config.read().clone();
-
-        // Validate configuration
-        let ptt_key = config
-            .get_ptt_key()
-            .ok_or_else(|| anyhow::anyhow!("No push-to-talk key configured"))?;
-
-        println!("Transcription engine using PTT key: {:?}", ptt_key);
-
-        if !config.use_local {
-            if let Some(ref api_key) = config.api_key {
-                // Set environment variable for OpenAI client
-                env::set_var("OPENAI_API_KEY", api_key);
-                println!("OpenAI API key set from config");
-            } else {
-                return Err(anyhow::anyhow!("No OpenAI API key configured"));
-            }
-        }
-
-        if config.use_local && config.local_model.is_none() {
-            return Err(anyhow::anyhow!("No local model selected"));
-        }
-
+        check_config(&config)?;
         println!("Configuration validated successfully");
 
         let (tx, rx): (flume::Sender<Event>, flume::Receiver<Event>) = flume::unbounded();
         let app_state_for_handler = self.app_state.clone();
         let stop_signal_for_key_thread = self.stop_signal.clone();
+        // Subscribed before the thread starts so a change made meanwhile
+        // isn't missed.
+        let config_changes = self.app_state.events.subscribe();
 
         // Register sender so the global listener can forward events
         self.app_state.set_event_sender(tx);
@@ -147,6 +356,7 @@ impl TranscriptionEngine {
         thread::spawn(move || {
             Self::key_handler_thread(
                 rx,
+                config_changes,
                 app_state_for_handler,
                 config,
                 stop_signal_for_key_thread,
@@ -166,21 +376,28 @@ impl TranscriptionEngine {
 
     fn key_handler_thread(
         rx: flume::Receiver<Event>,
+        config_changes: flume::Receiver<EngineEvent>,
         app_state: AppState,
         opt: AppConfig,
         stop_signal: Arc<Mutex<bool>>,
     ) {
         let mut recorder = rec::Recorder::new();
-        let client = Client::new();
         let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-        let mut enigo = Enigo::new();
-        let mut clipboard: ClipboardContext = match ClipboardProvider::new() {
-            Ok(provider) => provider,
+        let mut setup = match Setup::new(opt, &app_state) {
+            Ok(setup) => setup,
             Err(err) => {
-                eprintln!("Failed to access clipboard: {:?}", err);
+                eprintln!("Failed to set up the engine: {:?}", err);
                 return;
             }
         };
+        // Per-utterance state, set when the key goes down: the target window,
+        // the settings after app rules, and the sink they select.
+        let mut target_window: Option<ActiveWindow> = None;
+        let mut settings = setup.opt.clone();
+        let mut sink = setup.default_sink.clone();
+        // Changed settings wait until no utterance is in progress, so one
+        // utterance never mixes old and new ones.
+        let mut config_changed = false;
 
         let mut wpm_history: VecDeque<f64> = VecDeque::new();
         let mut wpm_sum: f64 = 0.0;
@@ -194,70 +411,150 @@ impl TranscriptionEngine {
         let mut key_pressed = false;
         let mut last_transcription_failed = false;
         let mut last_recording_duration_secs: f64 = 0.0;
-        let key_to_check = opt.get_ptt_key().unwrap();
-
-        // Realtime streaming mode: when enabled (and not using a local model)
-        // we stream audio to OpenAI and type transcript deltas live, instead of
-        // recording a WAV file and transcribing it at the end.
-        let realtime_enabled = opt.realtime && !opt.use_local;
-        let mut realtime_session: Option<crate::realtime::RealtimeSession> = None;
-
-        if realtime_enabled {
-            println!(
-                "Realtime streaming transcription enabled (model: gpt-realtime-whisper, delay: {})",
-                opt.realtime_delay
-            );
-            println!(
-                "Realtime debug log: {}",
-                crate::realtime::log_path().display()
-            );
-        }
+        let mut live_session: Option<LiveSession> = None;
 
         println!(
             "Key handler thread started, waiting for PTT key: {:?}",
-            key_to_check
+            setup.key
         );
 
-        for event in rx.iter() {
+        loop {
+            // A budget running out, or a new day or month starting, changes
+            // the settings to run with too.
+            if spending::exceeded(&setup.requested) != setup.budget {
+                config_changed = true;
+            }
+            if config_changed && !key_pressed && live_session.is_none() {
+                config_changed = false;
+                let config = app_state.config.read().clone();
+                if !setup.uses(&config) {
+                    match Setup::new(config, &app_state) {
+                        Ok(new_setup) => {
+                            setup = new_setup;
+                            settings = setup.opt.clone();
+                            sink = setup.default_sink.clone();
+                            println!("Settings changed - applied (PTT key: {:?})", setup.key);
+                        }
+                        Err(err) => {
+                            eprintln!("Error: New settings not applied: {:?}", err);
+                            app_state.report_error(format!("New settings not applied: {}", err));
+                        }
+                    }
+                }
+            }
+
+            let event = match flume::Selector::new()
+                .recv(&rx, |event| event.map(Wake::Key).unwrap_or(Wake::Closed))
+                .recv(&config_changes, |event| match event {
+                    Ok(EngineEvent::ConfigChanged) => Wake::ConfigChanged,
+                    _ => Wake::Other,
+                })
+                .wait()
+            {
+                Wake::Key(event) => event,
+                Wake::ConfigChanged => {
+                    config_changed = true;
+                    continue;
+                }
+                Wake::Other => continue,
+                Wake::Closed => break,
+            };
             if *stop_signal.lock() {
                 println!("Stop signal received - shutting down key handler");
                 break;
             }
             match event.event_type {
                 rdev::EventType::KeyPress(key) => {
-                    if key == key_to_check && !key_pressed {
+                    if key == setup.key && !key_pressed {
                         key_pressed = true;
+                        app_state.set_recording(true);
                         play_ptt_press_sound(); // Play low beep
                         recording_start = std::time::SystemTime::now();
 
-                        if realtime_enabled {
+                        if let Some(preview) = &setup.live_output.preview {
+                            let _ = preview.send(PreviewEvent::Show);
+                        }
+
+                        if setup.realtime_enabled || setup.local_streaming_enabled {
+                            // Live text needs the settings before it starts.
+                            (target_window, settings) = utterance_settings(&setup.opt);
+                            sink = sink_for(&mut setup.sinks, &settings, &setup.default_sink);
+                            setup.live_output.sink = sink.clone();
+                            setup.live_output.cap_first = settings.cap_first;
+                        }
+
+                        if setup.realtime_enabled {
                             println!("PTT key pressed - starting realtime stream");
-                            let api_key = opt
-                                .api_key
-                                .clone()
-                                .or_else(|| std::env::var("OPENAI_API_KEY").ok());
-                            match api_key {
-                                Some(api_key) => {
-                                    match crate::realtime::RealtimeSession::start(
-                                        api_key,
-                                        opt.device.clone(),
-                                        "gpt-realtime-whisper".to_string(),
-                                        None,
-                                        opt.cap_first,
-                                        opt.realtime_delay.clone(),
-                                    ) {
-                                        Ok(session) => realtime_session = Some(session),
+                            match setup.opt.api_key {
+                                Some(_) => {
+                                    let pooled = setup.realtime_pool.as_ref().and_then(|pool| {
+                                        pool.session(
+                                            setup.opt.device.clone(),
+                                            setup.live_output.clone(),
+                                        )
+                                        .ok()
+                                    });
+                                    let started = match pooled {
+                                        Some(session) => Ok(session),
+                                        None => RealtimeSession::start(
+                                            setup.openai.clone(),
+                                            setup.opt.device.clone(),
+                                            "gpt-realtime-whisper".to_string(),
+                                            None,
+                                            setup.live_output.clone(),
+                                            setup.opt.realtime_delay,
+                                        ),
+                                    };
+                                    match started {
+                                        Ok(session) => {
+                                            live_session = Some(LiveSession::Cloud(session))
+                                        }
                                         Err(err) => {
                                             eprintln!(
                                                 "Error: Failed to start realtime session: {:?}",
                                                 err
                                             );
+                                            app_state.report_error(format!(
+                                                "Failed to start realtime session: {}",
+                                                err
+                                            ));
                                             play_failure_sound();
                                         }
                                     }
                                 }
                                 None => {
                                     eprintln!("Error: No OpenAI API key for realtime mode");
+                                    app_state.report_error(
+                                        "No OpenAI API key for realtime mode".to_string(),
+                                    );
+                                    play_failure_sound();
+                                }
+                            }
+                            continue;
+                        }
+
+                        if setup.local_streaming_enabled {
+                            println!("PTT key pressed - starting local stream");
+                            let model = setup
+                                .opt
+                                .local_model
+                                .expect("Valid model required")
+                                .model_type();
+                            match LocalStreamingSession::start(
+                                setup.opt.device.clone(),
+                                model,
+                                setup.live_output.clone(),
+                            ) {
+                                Ok(session) => live_session = Some(LiveSession::Local(session)),
+                                Err(err) => {
+                                    eprintln!(
+                                        "Error: Failed to start local streaming session: {:?}",
+                                        err
+                                    );
+                                    app_state.report_error(format!(
+                                        "Failed to start local streaming session: {}",
+                                        err
+                                    ));
                                     play_failure_sound();
                                 }
                             }
@@ -265,31 +562,64 @@ impl TranscriptionEngine {
                         }
 
                         println!("PTT key pressed - starting recording");
-                        match recorder.start_recording(&voice_tmp_path, Some(&opt.device)) {
+                        match recorder.start_recording(&voice_tmp_path, &setup.opt.device) {
                             Ok(_) => println!("Recording started successfully"),
                             Err(err) => {
                                 eprintln!("Error: Failed to start recording: {:?}", err);
+                                app_state
+                                    .report_error(format!("Failed to start recording: {}", err));
                                 continue;
                             }
                         }
+                        // Probed after recording starts so the first words
+                        // aren't lost to a slow probe.
+                        (target_window, settings) = utterance_settings(&setup.opt);
+                        sink = sink_for(&mut setup.sinks, &settings, &setup.default_sink);
                     }
                 }
                 rdev::EventType::KeyRelease(key) => {
-                    if key == key_to_check && key_pressed {
+                    if key == setup.key && key_pressed {
                         key_pressed = false;
+                        app_state.set_recording(false);
                         play_ptt_release_sound(); // Play high beep
 
-                        if realtime_enabled {
-                            println!("PTT key released - finishing realtime stream");
+                        if setup.realtime_enabled || setup.local_streaming_enabled {
+                            println!("PTT key released - finishing live stream");
                             let elapsed = recording_start.elapsed().unwrap_or_default();
-                            let session = match realtime_session.take() {
+                            let session = match live_session.take() {
                                 Some(s) => s,
                                 None => continue,
                             };
-                            let transcription = match session.stop() {
+                            let stop_start = std::time::Instant::now();
+                            let result = session.stop();
+                            if result.is_ok() {
+                                // Time from release to the final text.
+                                let backend = if setup.realtime_enabled {
+                                    "realtime"
+                                } else {
+                                    "local"
+                                };
+                                trans::record_latency(backend, stop_start.elapsed());
+                            }
+                            if setup.realtime_enabled {
+                                spending::record_audio(
+                                    "gpt-realtime-whisper",
+                                    elapsed.as_secs_f64(),
+                                    1,
+                                );
+                            }
+                            if let Some(preview) = &setup.live_output.preview {
+                                let _ = preview.send(PreviewEvent::Hide);
+                            }
+                            let transcription = match result {
                                 Ok(text) => text,
                                 Err(err) => {
-                                    eprintln!("Error: Realtime transcription failed: {:?}", err);
+                                    let _ = sink.lock().finish();
+                                    eprintln!("Error: Live transcription failed: {:?}", err);
+                                    app_state.report_error(format!(
+                                        "Live transcription failed: {}",
+                                        err
+                                    ));
                                     play_failure_sound();
                                     continue;
                                 }
@@ -302,24 +632,28 @@ impl TranscriptionEngine {
                                 continue;
                             }
 
-                            // Text was already typed live during the stream, so
-                            // any final ending-punctuation post-processing is
-                            // applied now via enigo. The mode is a single setting
-                            // ("none" | "period" | "smart"); they're mutually
-                            // exclusive so there's no "LLM undoing a period" case.
+                            // Text was already typed live during the stream (or
+                            // shown in the preview overlay), so any final
+                            // ending-punctuation post-processing is applied now.
+                            // The mode is a single setting ("none" | "period" |
+                            // "smart"); they're mutually exclusive so there's no
+                            // "LLM undoing a period" case. Smart mode needs the
+                            // OpenAI API, so it's skipped for local streaming
+                            // (same as the local batch path).
                             let already_punctuated = trimmed
                                 .chars()
                                 .last()
                                 .map(|c| trans::is_terminal_punct(c))
                                 .unwrap_or(false);
 
-                            match opt.end_punctuation.as_str() {
-                                "period" => {
+                            let mut ending = String::new();
+                            match settings.end_punctuation {
+                                EndPunctuation::Period => {
                                     if !already_punctuated {
-                                        enigo.key_sequence(".");
+                                        ending.push('.');
                                     }
                                 }
-                                "smart" => {
+                                EndPunctuation::Smart if !setup.opt.use_local => {
                                     // If it already ends with a terminal mark,
                                     // trust it and skip the LLM call entirely —
                                     // detecting *presence* needs no intelligence.
@@ -329,18 +663,12 @@ impl TranscriptionEngine {
                                         );
                                     } else {
                                         match runtime.block_on(trans::decide_end_punctuation(
-                                            &client, trimmed,
+                                            &setup.client,
+                                            trimmed,
                                         )) {
-                                            Ok(mark) => {
-                                                if !mark.is_empty() {
-                                                    enigo.key_sequence(&mark);
-                                                }
-                                            }
+                                            Ok(mark) => ending.push_str(&mark),
                                             Err(err) => {
-                                                eprintln!(
-                                                    "Smart punctuation failed: {:?}",
-                                                    err
-                                                );
+                                                eprintln!("Smart punctuation failed: {:?}", err);
                                             }
                                         }
                                     }
@@ -349,10 +677,38 @@ impl TranscriptionEngine {
                             }
 
                             // --space: type a trailing space (after punctuation).
-                            if opt.space {
-                                enigo.key_sequence(" ");
+                            if settings.space {
+                                ending.push(' ');
+                            }
+
+                            let output_result = {
+                                let mut sink = sink.lock();
+                                let result = if setup.preview_enabled {
+                                    // Nothing was output yet: insert the whole text
+                                    // once, the same way batch results are.
+                                    let text = settings.apply_replacements(trimmed);
+                                    sink.insert(&format!("{}{}", text, ending))
+                                } else if !ending.is_empty() {
+                                    sink.append(&ending)
+                                } else {
+                                    Ok(())
+                                };
+                                result.and_then(|_| sink.finish())
+                            };
+                            if let Err(err) = output_result {
+                                eprintln!("Error: Failed to output text: {:?}", err);
+                                app_state.report_error(format!("Failed to output text: {}", err));
+                                play_failure_sound();
+                                continue;
                             }
 
+                            app_state.record_transcription(TranscriptionRecord {
+                                text: format!("{}{}", trimmed, ending),
+                                started_at: unix_time(recording_start),
+                                duration_secs: elapsed.as_secs_f64(),
+                                window: target_window.clone(),
+                            });
+
                             let word_count = trimmed.split_whitespace().count();
                             let duration_secs = elapsed.as_secs_f64();
                             if duration_secs > 0.0 {
@@ -412,18 +768,18 @@ impl TranscriptionEngine {
                             let (tick_tx, tick_rx) = mpsc::channel();
                             let tick_handle = thread::spawn(move || tick_loop(tick_rx));
 
-                            let transcription_result = if opt.use_local {
-                                let model = opt
+                            let transcription_result = if setup.opt.use_local {
+                                let model = setup
+                                    .opt
                                     .local_model
-                                    .as_ref()
-                                    .and_then(|m| Self::parse_model(m))
-                                    .expect("Valid model required");
+                                    .expect("Valid model required")
+                                    .model_type();
                                 trans::transcribe_local(&audio_path, model)
                             } else {
                                 runtime.block_on(trans::transcribe_racing(
-                                    &client,
+                                    &setup.client,
                                     &audio_path,
-                                    opt.parallel,
+                                    setup.opt.parallel,
                                     recording_duration_secs,
                                 ))
                             };
@@ -434,6 +790,10 @@ impl TranscriptionEngine {
                                     let _ = tick_tx.send(());
                                     let _ = tick_handle.join();
                                     eprintln!("Error: Failed to transcribe audio: {:?}", err);
+                                    app_state.report_error(format!(
+                                        "Failed to transcribe audio: {}",
+                                        err
+                                    ));
                                     if !is_retry {
                                         if let Err(e) = std::fs::copy(&voice_tmp_path, &voice_retry_path) {
                                             eprintln!("Warning: Failed to save audio for retry: {:?}", e);
@@ -447,10 +807,10 @@ impl TranscriptionEngine {
 
                             transcription = transcription.replace("...", "");
 
-                            if opt.punctuation && needs_punctuation_fix(&transcription) {
+                            if setup.opt.punctuation && needs_punctuation_fix(&transcription) {
                                 println!("Transcription missing punctuation, fixing...");
                                 match runtime.block_on(trans::fix_punctuation_with_openai(
-                                    &client,
+                                    &setup.client,
                                     &transcription,
                                 )) {
                                     Ok(fixed) => {
@@ -479,12 +839,12 @@ impl TranscriptionEngine {
                                 .map(|c| trans::is_terminal_punct(c))
                                 .unwrap_or(false);
 
-                            if opt.end_punctuation == "smart"
-                                && !opt.use_local
+                            if settings.end_punctuation == EndPunctuation::Smart
+                                && !setup.opt.use_local
                                 && !already_punctuated
                             {
                                 match runtime.block_on(trans::decide_end_punctuation(
-                                    &client,
+                                    &setup.client,
                                     transcription.trim(),
                                 )) {
                                     Ok(mark) => {
@@ -508,16 +868,20 @@ impl TranscriptionEngine {
                             let _ = tick_tx.send(());
                             let _ = tick_handle.join();
 
-                            if opt.end_punctuation == "period" && !already_punctuated {
+                            if settings.end_punctuation == EndPunctuation::Period
+                                && !already_punctuated
+                            {
                                 let trimmed = transcription.trim_end();
                                 transcription = format!("{}.", trimmed);
                             }
 
-                            if opt.cap_first {
+                            transcription = settings.apply_replacements(&transcription);
+
+                            if settings.cap_first {
                                 capitalize_first_letter(&mut transcription);
                             }
 
-                            if opt.space {
+                            if settings.space {
                                 if let Some(last_char) = transcription.chars().last() {
                                     if last_char != ' ' {
                                         transcription.push(' ');
@@ -535,33 +899,25 @@ impl TranscriptionEngine {
 
                             let word_count = transcription.split_whitespace().count();
 
-                            if opt.type_chars {
-                                enigo.key_sequence(&transcription);
-                            } else {
-                                let clip_tmp_result = clipboard.get_contents();
-
-                                match clipboard.set_contents(transcription.clone()) {
-                                    Ok(_) => {
-                                        enigo.key_sequence_parse("{+CTRL}");
-                                        sleep(Duration::from_millis(100));
-                                        enigo.key_sequence_parse("v");
-                                        sleep(Duration::from_millis(100));
-                                        enigo.key_sequence_parse("{-CTRL}");
-                                        sleep(Duration::from_millis(100));
-
-                                        if let Ok(clip_tmp) = clip_tmp_result {
-                                            let _ = clipboard.set_contents(clip_tmp);
-                                        }
-                                    }
-                                    Err(err) => {
-                                        eprintln!("Error: Failed to set clipboard: {:?}", err);
-                                        continue;
-                                    }
-                                }
+                            let output_result = {
+                                let mut sink = sink.lock();
+                                sink.insert(&transcription).and_then(|_| sink.finish())
+                            };
+                            if let Err(err) = output_result {
+                                eprintln!("Error: Failed to output text: {:?}", err);
+                                app_state.report_error(format!("Failed to output text: {}", err));
+                                continue;
                             }
 
                             last_transcription_failed = false;
 
+                            app_state.record_transcription(TranscriptionRecord {
+                                text: transcription.clone(),
+                                started_at: unix_time(recording_start),
+                                duration_secs: recording_duration_secs,
+                                window: target_window.clone(),
+                            });
+
                             if !is_retry && elapsed.as_secs_f64() > 0.0 {
                                 let duration_secs = elapsed.as_secs_f64();
                                 let wpm = (word_count as f64) * 60.0 / duration_secs;
@@ -592,22 +948,16 @@ impl TranscriptionEngine {
             }
         }
     }
+}
 
-    fn parse_model(model: &str) -> Option<mutter::ModelType> {
-        use mutter::ModelType;
-        match model.to_lowercase().as_str() {
-            "tiny-en" => Some(ModelType::TinyEn),
-            "tiny" => Some(ModelType::Tiny),
-            "base-en" => Some(ModelType::BaseEn),
-            "base" => Some(ModelType::Base),
-            "small-en" => Some(ModelType::SmallEn),
-            "small" => Some(ModelType::Small),
-            "medium-en" => Some(ModelType::MediumEn),
-            "medium" => Some(ModelType::Medium),
-            "large-v1" => Some(ModelType::LargeV1),
-            "large-v2" => Some(ModelType::LargeV2),
-            "large-v3" => Some(ModelType::LargeV3),
-            _ => None,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capitalize_first_letter_works() {
+        let mut s = String::from("hello");
+        capitalize_first_letter(&mut s);
+        assert_eq!(s, "Hello");
     }
 }
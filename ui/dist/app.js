@@ -8,6 +8,9 @@ if (!invoke) {
 
 let cachedApiKey = '';
 let currentPttKey = null;
+// Last config received from the backend. Saves start from it so settings that
+// have no control in this window are kept instead of reset to defaults.
+let loadedConfig = {};
 
 function applyPttKeySelection() {
     if (!currentPttKey) {
@@ -134,6 +137,27 @@ function updateModeVisibility() {
     });
 }
 
+// The output file path only matters for the "file" output, the paste
+// shortcut only for "paste".
+function updateOutputFileVisibility() {
+    const outputEl = document.getElementById('output');
+    const row = document.getElementById('outputFileRow');
+    if (outputEl && row) {
+        row.style.display = outputEl.value === 'file' ? 'flex' : 'none';
+    }
+    ['pasteShortcutRow', 'clipboardTransientRow'].forEach(id => {
+        const pasteRow = document.getElementById(id);
+        if (outputEl && pasteRow) {
+            pasteRow.style.display = outputEl.value === 'paste' ? 'flex' : 'none';
+        }
+    });
+}
+
+const outputEl = document.getElementById('output');
+if (outputEl) {
+    outputEl.addEventListener('change', updateOutputFileVisibility);
+}
+
 const transcriptionSpeedEl = document.getElementById('transcriptionSpeed');
 if (transcriptionSpeedEl) {
     transcriptionSpeedEl.addEventListener('change', updateModeVisibility);
@@ -143,6 +167,7 @@ if (transcriptionSpeedEl) {
 async function loadConfig() {
     try {
         const config = await invoke('get_config');
+        loadedConfig = config;
         console.log('Loaded config from backend:', config);
         console.log('Config.api_key:', config.api_key ? `EXISTS (${config.api_key.length} chars)` : 'MISSING');
         
@@ -155,13 +180,23 @@ async function loadConfig() {
         document.getElementById('capFirst').checked = config.cap_first || false;
         document.getElementById('space').checked = config.space || false;
         document.getElementById('punctuation').checked = config.punctuation || false;
-        document.getElementById('typeChars').checked = config.type_chars || false;
+        // type_chars is the legacy spelling of the "type" output.
+        const output = config.output || 'paste';
+        document.getElementById('output').value =
+            output === 'paste' && config.type_chars ? 'type' : output;
+        document.getElementById('outputFile').value = config.output_file || '';
+        document.getElementById('pasteShortcut').value = config.paste_shortcut || '';
+        document.getElementById('clipboardTransient').checked = config.clipboard_transient !== false;
+        updateOutputFileVisibility();
         document.getElementById('endPunctuation').value = config.end_punctuation || 'smart';
         document.getElementById('autoStart').checked = config.auto_start || false;
 
         // Transcription mode (Standard vs Realtime) and realtime delay
         document.getElementById('transcriptionSpeed').value = config.realtime ? 'realtime' : 'standard';
         document.getElementById('realtimeDelay').value = config.realtime_delay || 'xhigh';
+        document.getElementById('realtimePrewarm').checked = config.realtime_prewarm || false;
+        document.getElementById('realtimeReconcile').checked = config.realtime_reconcile || false;
+        document.getElementById('realtimeOutput').value = config.realtime_output || 'type';
         updateModeVisibility();
         document.getElementById('startMinimized').checked = config.start_minimized || false;
         document.getElementById('darkMode').checked = config.dark_mode || false;
@@ -199,6 +234,13 @@ async function loadConfig() {
         document.getElementById('openaiSection').style.display = isLocal ? 'none' : 'block';
         document.getElementById('localSection').style.display = isLocal ? 'block' : 'none';
         
+        document.getElementById('dailyBudget').value = config.daily_budget ?? '';
+        document.getElementById('monthlyBudget').value = config.monthly_budget ?? '';
+        document.getElementById('budgetAction').value = config.budget_action || 'warn';
+        document.getElementById('credential').value = config.credential || '';
+        document.getElementById('writeEnvFile').checked = config.write_env_file || false;
+        await loadCredentials();
+
         const apiKeyField = document.getElementById('apiKey');
         console.log('API key field element:', apiKeyField ? 'FOUND' : 'NOT FOUND');
         if (config.api_key) {
@@ -210,6 +252,7 @@ async function loadConfig() {
             apiKeyField.value = ''; // Clear the field
         }
 
+        document.getElementById('localStreaming').checked = config.local_streaming || false;
         if (config.local_model) {
             document.getElementById('localModel').value = config.local_model;
         }
@@ -224,6 +267,135 @@ async function loadConfig() {
     }
 }
 
+// Fill the dropdowns whose values are fixed by the backend's config types
+async function loadConfigOptions() {
+    try {
+        const options = await invoke('get_config_options');
+        const fill = (id, choices) => {
+            const select = document.getElementById(id);
+            choices.forEach(choice => {
+                const option = document.createElement('option');
+                option.value = choice.value;
+                option.textContent = choice.label;
+                select.appendChild(option);
+            });
+        };
+        fill('output', options.output);
+        fill('realtimeOutput', options.realtime_output);
+        fill('endPunctuation', options.end_punctuation);
+        fill('realtimeDelay', options.realtime_delay);
+        fill('localModel', options.local_model);
+        fill('budgetAction', options.budget_action);
+    } catch (error) {
+        console.error('Error loading config options:', error);
+    }
+}
+
+// An empty budget field means no limit
+function readBudget(id) {
+    const value = parseFloat(document.getElementById(id).value);
+    return Number.isFinite(value) ? value : null;
+}
+
+// save_config rejects with a list of { field, message }
+function formatConfigErrors(error) {
+    if (!Array.isArray(error)) {
+        return String(error);
+    }
+    return error
+        .map(e => (e.field ? `${e.field}: ${e.message}` : e.message))
+        .join('; ');
+}
+
+// Offer the names of the stored API keys
+async function loadCredentials() {
+    try {
+        const credentials = await invoke('list_credentials');
+        const list = document.getElementById('credentialNames');
+        list.innerHTML = '';
+        credentials.forEach(credential => {
+            const option = document.createElement('option');
+            option.value = credential.name;
+            list.appendChild(option);
+        });
+    } catch (error) {
+        console.error('Error loading stored API keys:', error);
+    }
+}
+
+// Show the key stored under the chosen name (empty for a new name)
+async function selectCredential() {
+    const name = document.getElementById('credential').value.trim() || 'default';
+    try {
+        const key = await invoke('load_credential_key', { name });
+        cachedApiKey = key || '';
+        document.getElementById('apiKey').value = cachedApiKey;
+    } catch (error) {
+        showStatus('Could not read key "' + name + '": ' + error, 'error');
+    }
+}
+
+async function removeCredential() {
+    const name = document.getElementById('credential').value.trim() || 'default';
+    try {
+        await invoke('remove_credential', { name });
+        cachedApiKey = '';
+        document.getElementById('apiKey').value = '';
+        await loadCredentials();
+        showStatus('Removed key "' + name + '"', 'success');
+    } catch (error) {
+        showStatus('Could not remove key "' + name + '": ' + error, 'error');
+    }
+}
+
+// Export the settings to the file named in the Share Settings section
+async function exportConfig() {
+    const path = document.getElementById('shareFile').value.trim();
+    if (!path) {
+        showStatus('Enter a file to export to', 'error');
+        return;
+    }
+    try {
+        await invoke('export_config', { path });
+        showStatus('Settings exported to ' + path, 'success');
+    } catch (error) {
+        showStatus('Export failed: ' + error, 'error');
+    }
+}
+
+// Import a settings file, then show what it changed
+async function importConfig() {
+    const path = document.getElementById('shareFile').value.trim();
+    if (!path) {
+        showStatus('Enter a file to import', 'error');
+        return;
+    }
+    const mode = document.getElementById('importMode').value;
+    try {
+        const changed = await invoke('import_config', { path, mode });
+        await loadConfig();
+        showStatus(changed.length
+            ? 'Imported. Changed: ' + changed.join(', ')
+            : 'Imported. No settings changed.', 'success');
+    } catch (error) {
+        showStatus('Import failed: ' + formatConfigErrors(error), 'error');
+    }
+}
+
+// Tell the user which settings an administrator has fixed
+async function loadManagedSettings() {
+    try {
+        const managed = await invoke('get_managed_settings');
+        const note = document.getElementById('managedNote');
+        if (managed.length) {
+            note.textContent = 'Set by your administrator (changes to these are not kept): ' + managed.join(', ');
+            note.style.display = 'block';
+        }
+    } catch (error) {
+        console.error('Error loading managed settings:', error);
+    }
+}
+
 // Load PTT keys
 async function loadPTTKeys() {
     try {
@@ -437,6 +609,52 @@ async function loadStatistics() {
             dailyAverageEl.textContent = '';
         }
         
+        // Spending
+        const formatUsd = (usd) => `$${(usd || 0).toFixed(2)}`;
+        document.getElementById('spendToday').textContent = formatUsd(stats.spend_today_usd);
+        document.getElementById('spendMonth').textContent = formatUsd(stats.spend_month_usd);
+        document.getElementById('spendLifetime').textContent = formatUsd(stats.lifetime_spend_usd);
+        document.getElementById('spendTodayBudget').textContent =
+            stats.daily_budget != null ? `of ${formatUsd(stats.daily_budget)} budget` : 'No daily limit';
+        document.getElementById('spendMonthBudget').textContent =
+            stats.monthly_budget != null ? `of ${formatUsd(stats.monthly_budget)} budget` : 'No monthly limit';
+        document.getElementById('budgetExceededHint').textContent = stats.budget_exceeded
+            ? `Your ${stats.budget_exceeded} budget is used up.`
+            : '';
+
+        // Latency per backend
+        const latencySection = document.getElementById('latencySection');
+        const latencyGrid = document.getElementById('latencyGrid');
+        const latencyOverall = stats.latency_overall || [];
+        if (latencySection && latencyOverall.length > 0) {
+            latencySection.style.display = '';
+            const formatMs = (ms) => ms >= 1000 ? `${(ms / 1000).toFixed(1)}s` : `${ms}ms`;
+            const backendNames = { openai: 'OpenAI', realtime: 'Realtime', local: 'Local' };
+            const today = new Date().toLocaleDateString('en-CA');
+            latencyGrid.innerHTML = '';
+            for (const summary of latencyOverall) {
+                const todays = (stats.latency_daily || []).find(
+                    (day) => day.backend === summary.backend && day.date === today
+                );
+                const card = document.createElement('div');
+                card.className = 'stat-card';
+                const value = document.createElement('div');
+                value.className = 'stat-value';
+                value.textContent = formatMs(summary.p50_ms);
+                const label = document.createElement('div');
+                label.className = 'stat-label';
+                label.textContent = `${backendNames[summary.backend] || summary.backend} median`;
+                const detail = document.createElement('div');
+                detail.className = 'stat-lifetime';
+                detail.textContent = `p90 ${formatMs(summary.p90_ms)} · p99 ${formatMs(summary.p99_ms)} · ${formatNumber(summary.count)} total`
+                    + (todays ? ` · today ${formatMs(todays.p50_ms)}` : '');
+                card.append(value, label, detail);
+                latencyGrid.appendChild(card);
+            }
+        } else if (latencySection) {
+            latencySection.style.display = 'none';
+        }
+
         // Racing stats
         const racingSection = document.getElementById('racingStatsSection');
         if (racingSection && stats.parallel > 1 && stats.racing_total_races > 0) {
@@ -528,6 +746,7 @@ async function saveConfig() {
             
             // Still save the config with empty PTT key
             const config = {
+                ...loadedConfig,
                 ptt_key: null,
                 special_ptt_key: null,
                 device: document.getElementById('audioDevice').value,
@@ -536,16 +755,29 @@ async function saveConfig() {
                 cap_first: document.getElementById('capFirst').checked,
                 space: document.getElementById('space').checked,
                 punctuation: document.getElementById('punctuation').checked,
-                type_chars: document.getElementById('typeChars').checked,
+                type_chars: document.getElementById('output').value === 'type',
+                output: document.getElementById('output').value,
+                output_file: document.getElementById('outputFile').value.trim() || null,
+                paste_shortcut: document.getElementById('pasteShortcut').value.trim(),
+                clipboard_transient: document.getElementById('clipboardTransient').checked,
                 realtime: document.getElementById('transcriptionSpeed').value === 'realtime',
                 realtime_delay: document.getElementById('realtimeDelay').value,
+                realtime_prewarm: document.getElementById('realtimePrewarm').checked,
+                realtime_reconcile: document.getElementById('realtimeReconcile').checked,
+                realtime_output: document.getElementById('realtimeOutput').value,
+                local_streaming: document.getElementById('localStreaming').checked,
                 end_punctuation: document.getElementById('endPunctuation').value,
                 auto_start: document.getElementById('autoStart').checked,
                 start_minimized: document.getElementById('startMinimized').checked,
                 dark_mode: document.getElementById('darkMode').checked,
                 api_key: document.getElementById('apiKey').value || cachedApiKey || null,
+                credential: document.getElementById('credential').value.trim() || null,
+                write_env_file: document.getElementById('writeEnvFile').checked,
                 typing_wpm: parseInt(document.getElementById('typingWPM').value) || 40,
                 parallel: document.getElementById('parallelEnabled').checked ? (parseInt(document.getElementById('parallelCount').value) || 3) : 1,
+                daily_budget: readBudget('dailyBudget'),
+                monthly_budget: readBudget('monthlyBudget'),
+                budget_action: document.getElementById('budgetAction').value,
             };
             
             await invoke('save_config', { incoming: config });
@@ -576,6 +808,7 @@ async function saveConfig() {
         }
         
         const config = {
+            ...loadedConfig,
             ptt_key: pttKeyValue,
             special_ptt_key: null,
             device: document.getElementById('audioDevice').value,
@@ -584,16 +817,29 @@ async function saveConfig() {
             cap_first: document.getElementById('capFirst').checked,
             space: document.getElementById('space').checked,
             punctuation: document.getElementById('punctuation').checked,
-            type_chars: document.getElementById('typeChars').checked,
+            type_chars: document.getElementById('output').value === 'type',
+            output: document.getElementById('output').value,
+            output_file: document.getElementById('outputFile').value.trim() || null,
+            paste_shortcut: document.getElementById('pasteShortcut').value.trim(),
+            clipboard_transient: document.getElementById('clipboardTransient').checked,
             realtime: document.getElementById('transcriptionSpeed').value === 'realtime',
             realtime_delay: document.getElementById('realtimeDelay').value,
+            realtime_prewarm: document.getElementById('realtimePrewarm').checked,
+            realtime_reconcile: document.getElementById('realtimeReconcile').checked,
+            realtime_output: document.getElementById('realtimeOutput').value,
+            local_streaming: document.getElementById('localStreaming').checked,
             end_punctuation: document.getElementById('endPunctuation').value,
             auto_start: document.getElementById('autoStart').checked,
             start_minimized: document.getElementById('startMinimized').checked,
             dark_mode: document.getElementById('darkMode').checked,
             api_key: apiKey || null,
+            credential: document.getElementById('credential').value.trim() || null,
+            write_env_file: document.getElementById('writeEnvFile').checked,
             typing_wpm: parseInt(document.getElementById('typingWPM').value) || 40,
             parallel: document.getElementById('parallelEnabled').checked ? (parseInt(document.getElementById('parallelCount').value) || 3) : 1,
+            daily_budget: readBudget('dailyBudget'),
+            monthly_budget: readBudget('monthlyBudget'),
+            budget_action: document.getElementById('budgetAction').value,
         };
         
         console.log('Config payload being sent:', JSON.stringify({ ...config, api_key: apiKey ? '(hidden)' : null }, null, 2));
@@ -623,26 +869,11 @@ async function saveConfig() {
         
         await invoke('save_config', { incoming: config });
         
-        // Auto-restart engine if it was running
+        // A running engine applies saved settings itself, before the next
+        // utterance.
         const wasRunning = await invoke('is_running');
         if (wasRunning) {
-            try {
-                await invoke('stop_engine');
-                await updateEngineStatus(false);
-                await invoke('start_engine');
-                // Verify it's actually running after start
-                const isNowRunning = await invoke('is_running');
-                await updateEngineStatus(isNowRunning);
-                if (isNowRunning) {
-                    showStatus('Settings saved and engine restarted!', 'success');
-                } else {
-                    showStatus('Settings saved, but engine failed to start.', 'error');
-                }
-            } catch (error) {
-                console.error('Failed to restart engine:', error);
-                await updateEngineStatus(false);
-                showStatus('Settings saved, but failed to restart: ' + error, 'error');
-            }
+            showStatus('Settings saved and applied!', 'success');
         } else {
             // Try to start engine if it wasn't running
             try {
@@ -665,7 +896,7 @@ async function saveConfig() {
         return true;
     } catch (error) {
         console.error('Error saving config:', error);
-        showStatus('Error saving settings: ' + error, 'error');
+        showStatus('Error saving settings: ' + formatConfigErrors(error), 'error');
         return false;
     }
 }
@@ -768,6 +999,10 @@ document.getElementById('parallelEnabled').addEventListener('change', (e) => {
 document.getElementById('saveBtn').addEventListener('click', saveConfig);
 document.getElementById('saveBtn2').addEventListener('click', saveConfig);
 document.getElementById('validateKeyBtn').addEventListener('click', validateApiKey);
+document.getElementById('exportConfigBtn').addEventListener('click', exportConfig);
+document.getElementById('credential').addEventListener('change', selectCredential);
+document.getElementById('removeCredentialBtn').addEventListener('click', removeCredential);
+document.getElementById('importConfigBtn').addEventListener('click', importConfig);
 document.getElementById('refreshDevicesBtn').addEventListener('click', loadAudioDevices);
 
 // Only add listener if button exists (it's commented out in HTML)
@@ -850,8 +1085,10 @@ document.addEventListener('DOMContentLoaded', () => {
     }
 
     (async () => {
+        await loadConfigOptions();
         await loadPTTKeys();
         await loadConfig();
+        await loadManagedSettings();
         await loadAudioDevices();
         await loadStatistics();
         console.log('Initialization complete');